@@ -7,6 +7,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             format!("{proto_dir}/users.proto"),
             format!("{proto_dir}/posts.proto"),
             format!("{proto_dir}/comments.proto"),
+            format!("{proto_dir}/google/rpc/status.proto"),
+            format!("{proto_dir}/google/rpc/error_details.proto"),
         ],
         &[proto_dir],
     )?;
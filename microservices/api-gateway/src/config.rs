@@ -1,7 +1,80 @@
-use std::env;
+use std::{env, fmt, net::IpAddr, str::FromStr, time::Duration};
+
+pub struct TlsConfig {
+    pub cert_path: String,
+    pub key_path: String,
+}
+
+pub struct GrpcClientConfig {
+    pub connect_timeout: Duration,
+    // Deadline for a single call, retries each get their own.
+    pub request_timeout: Duration,
+    pub retry_attempts: u32,
+    pub retry_backoff: Duration,
+    // Consecutive failures before a downstream's circuit opens.
+    pub failure_threshold: u32,
+    // How long an open circuit fails fast before letting a trial call through.
+    pub open_duration: Duration,
+}
+
+// `requests` per `window`, written as `10/60` in the environment.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimitPolicy {
+    pub requests: u32,
+    pub window: Duration,
+}
+
+impl FromStr for RateLimitPolicy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (requests, window) = value.split_once('/')
+            .ok_or_else(|| format!("expected `requests/seconds`, got `{value}`"))?;
+        let requests: u32 = requests.trim().parse().map_err(|_| format!("invalid request count `{requests}`"))?;
+        let window: u64 = window.trim().parse().map_err(|_| format!("invalid window `{window}`"))?;
+
+        if requests == 0 || window == 0 {
+            return Err("requests and window must be positive".to_string());
+        }
+
+        Ok(Self { requests, window: Duration::from_secs(window) })
+    }
+}
+
+pub struct RateLimitConfig {
+    pub enabled: bool,
+    // Sign up, sign in and the other unauthenticated auth endpoints.
+    pub auth: RateLimitPolicy,
+    pub write: RateLimitPolicy,
+    pub read: RateLimitPolicy,
+}
+
+#[derive(Clone)]
+pub struct CorsConfig {
+    // Exact origins such as `https://app.example.com`, empty allows no cross origin calls.
+    pub allowed_origins: Vec<String>,
+    pub allowed_methods: Vec<String>,
+    pub allowed_headers: Vec<String>,
+    pub max_age: usize,
+}
 
 pub struct Config {
     pub gateway_url: String,
+    // Defaults to one worker per physical core when unset.
+    pub workers: Option<usize>,
+    pub json_limit: usize,
+    pub payload_limit: usize,
+    pub keep_alive: Duration,
+    // How long in-flight requests get to finish after SIGTERM before workers are stopped.
+    pub shutdown_timeout: Duration,
+    pub tls: Option<TlsConfig>,
+    pub grpc: GrpcClientConfig,
+    pub rate_limit: RateLimitConfig,
+    pub cors: CorsConfig,
+    // `Strict-Transport-Security` max-age in seconds, 0 leaves the header out.
+    pub hsts_max_age: u64,
+    // Reverse proxies whose forwarding headers name the client, empty uses the peer address as is.
+    pub trusted_proxies: Vec<IpAddr>,
     pub auth_service_url: String,
     pub users_service_url: String,
     pub posts_service_url: String,
@@ -9,7 +82,8 @@ pub struct Config {
 }
 
 impl Config {
-    pub fn from_env() -> Self {
+    // Settings that can't work are refused instead of failing later or quietly falling back.
+    pub fn from_env() -> Result<Self, String> {
         let auth_service = env::var("AUTH_SERVICE")
             .unwrap_or_else(|_| "127.0.0.1:50051".to_string());
         let users_service = env::var("USERS_SERVICE")
@@ -19,16 +93,107 @@ impl Config {
         let comments_service = env::var("COMMENTS_SERVICE")
             .unwrap_or_else(|_| "127.0.0.1:50054".to_string());
         
-        Self {
+        let tls = Self::tls_config(env::var("TLS_CERT_PATH").ok(), env::var("TLS_KEY_PATH").ok())?;
+        let workers = Self::check_workers(Self::parse_var("GATEWAY_WORKERS")?)?;
+        let trusted_proxies = Self::parse_ips("TRUSTED_PROXIES", Self::list_var("TRUSTED_PROXIES", ""))?;
+
+        Ok(Self {
             gateway_url: env::var("API_GATEWAY")
+                .map(|addr| Self::strip_http_prefix(&addr).to_string())
                 .unwrap_or_else(|_| "127.0.0.1:8080".to_string()),
+            workers,
+            json_limit: Self::parse_var("GATEWAY_JSON_LIMIT")?.unwrap_or(256 * 1024),
+            payload_limit: Self::parse_var("GATEWAY_PAYLOAD_LIMIT")?.unwrap_or(1024 * 1024),
+            keep_alive: Duration::from_secs(Self::parse_var("GATEWAY_KEEP_ALIVE_SECS")?.unwrap_or(5)),
+            shutdown_timeout: Duration::from_secs(Self::parse_var("GATEWAY_SHUTDOWN_TIMEOUT_SECS")?.unwrap_or(30)),
+            tls,
+            grpc: GrpcClientConfig {
+                connect_timeout: Duration::from_millis(Self::parse_var("GRPC_CONNECT_TIMEOUT_MS")?.unwrap_or(1000)),
+                request_timeout: Duration::from_millis(Self::parse_var("GRPC_REQUEST_TIMEOUT_MS")?.unwrap_or(5000)),
+                retry_attempts: Self::parse_var("GRPC_RETRY_ATTEMPTS")?.unwrap_or(3),
+                retry_backoff: Duration::from_millis(Self::parse_var("GRPC_RETRY_BACKOFF_MS")?.unwrap_or(50)),
+                failure_threshold: Self::parse_var("CIRCUIT_BREAKER_THRESHOLD")?.unwrap_or(5),
+                open_duration: Duration::from_secs(Self::parse_var("CIRCUIT_BREAKER_OPEN_SECS")?.unwrap_or(30)),
+            },
+            rate_limit: RateLimitConfig {
+                enabled: Self::parse_var("RATE_LIMIT_ENABLED")?.unwrap_or(true),
+                auth: Self::parse_var("RATE_LIMIT_AUTH")?.unwrap_or(RateLimitPolicy { requests: 10, window: Duration::from_secs(60) }),
+                write: Self::parse_var("RATE_LIMIT_WRITE")?.unwrap_or(RateLimitPolicy { requests: 30, window: Duration::from_secs(60) }),
+                read: Self::parse_var("RATE_LIMIT_READ")?.unwrap_or(RateLimitPolicy { requests: 300, window: Duration::from_secs(60) }),
+            },
+            cors: CorsConfig {
+                allowed_origins: Self::list_var("CORS_ALLOWED_ORIGINS", ""),
+                allowed_methods: Self::list_var("CORS_ALLOWED_METHODS", "GET,POST,PUT,PATCH,DELETE"),
+                allowed_headers: Self::list_var("CORS_ALLOWED_HEADERS", "authorization,content-type,x-request-id,if-match,if-none-match,traceparent"),
+                max_age: Self::parse_var("CORS_MAX_AGE_SECS")?.unwrap_or(3600),
+            },
+            hsts_max_age: Self::parse_var("HSTS_MAX_AGE_SECS")?.unwrap_or(31_536_000),
+            trusted_proxies,
             auth_service_url: Self::ensure_http_prefix(&auth_service),
             users_service_url: Self::ensure_http_prefix(&users_service),
             posts_service_url: Self::ensure_http_prefix(&posts_service),
             comments_service_url: Self::ensure_http_prefix(&comments_service),
+        })
+    }
+
+    // Half a TLS setup would otherwise quietly serve plain HTTP.
+    fn tls_config(cert_path: Option<String>, key_path: Option<String>) -> Result<Option<TlsConfig>, String> {
+        match (cert_path, key_path) {
+            (Some(cert_path), Some(key_path)) => Ok(Some(TlsConfig { cert_path, key_path })),
+            (None, None) => Ok(None),
+            (Some(_), None) => Err("TLS_CERT_PATH is set without TLS_KEY_PATH".to_string()),
+            (None, Some(_)) => Err("TLS_KEY_PATH is set without TLS_CERT_PATH".to_string()),
+        }
+    }
+
+    // A mistyped proxy would leave its clients sharing its rate limit, so it's an error rather than skipped.
+    fn parse_ips(key: &str, values: Vec<String>) -> Result<Vec<IpAddr>, String> {
+        values.iter()
+            .map(|value| value.parse().map_err(|_| format!("{key} has an invalid ip address `{value}`")))
+            .collect()
+    }
+
+    // actix panics when asked for zero workers.
+    fn check_workers(workers: Option<usize>) -> Result<Option<usize>, String> {
+        match workers {
+            Some(0) => Err("GATEWAY_WORKERS must be at least 1".to_string()),
+            workers => Ok(workers),
         }
     }
     
+    // Comma separated, e.g. `CORS_ALLOWED_ORIGINS=https://a.example.com,https://b.example.com`.
+    fn list_var(key: &str, default: &str) -> Vec<String> {
+        env::var(key)
+            .unwrap_or_else(|_| default.to_string())
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+
+    // Unset falls back to the default, a value that doesn't parse is an error.
+    fn parse_var<T: FromStr>(key: &str) -> Result<Option<T>, String>
+    where
+        T::Err: fmt::Display,
+    {
+        Self::parse_value(key, env::var(key).ok())
+    }
+
+    fn parse_value<T: FromStr>(key: &str, value: Option<String>) -> Result<Option<T>, String>
+    where
+        T::Err: fmt::Display,
+    {
+        value
+            .map(|value| value.trim().parse().map_err(|err| format!("{key} has an invalid value `{value}`: {err}")))
+            .transpose()
+    }
+
+    // `bind` wants a bare `host:port`, but API_GATEWAY is often written as a URL.
+    fn strip_http_prefix(addr: &str) -> &str {
+        addr.trim_start_matches("http://").trim_start_matches("https://").trim_end_matches('/')
+    }
+
     fn ensure_http_prefix(addr: &str) -> String {
         if addr.starts_with("http://") || addr.starts_with("https://") {
             addr.to_string()
@@ -37,3 +202,59 @@ impl Config {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(value: &str) -> Option<String> {
+        Some(value.to_string())
+    }
+
+    #[test]
+    fn tls_needs_both_cert_and_key() {
+        assert!(Config::tls_config(None, None).unwrap().is_none());
+
+        let tls = Config::tls_config(path("cert.pem"), path("key.pem")).unwrap().unwrap();
+        assert_eq!((tls.cert_path.as_str(), tls.key_path.as_str()), ("cert.pem", "key.pem"));
+
+        assert!(Config::tls_config(path("cert.pem"), None).is_err());
+        assert!(Config::tls_config(None, path("key.pem")).is_err());
+    }
+
+    #[test]
+    fn trusted_proxies_must_be_ip_addresses() {
+        let ips = Config::parse_ips("TRUSTED_PROXIES", vec!["10.0.0.1".to_string(), "::1".to_string()]).unwrap();
+        assert_eq!(ips.len(), 2);
+
+        assert!(Config::parse_ips("TRUSTED_PROXIES", vec!["10.0.0.0/8".to_string()]).is_err());
+    }
+
+    #[test]
+    fn zero_workers_are_rejected() {
+        assert!(Config::check_workers(Some(0)).is_err());
+        assert_eq!(Config::check_workers(Some(4)).unwrap(), Some(4));
+        assert_eq!(Config::check_workers(None).unwrap(), None);
+    }
+
+    #[test]
+    fn unset_settings_fall_back_and_invalid_ones_are_refused() {
+        assert_eq!(Config::parse_value::<usize>("GATEWAY_WORKERS", None).unwrap(), None);
+        assert_eq!(Config::parse_value::<usize>("GATEWAY_WORKERS", path(" 4 ")).unwrap(), Some(4));
+        assert!(Config::parse_value::<usize>("GATEWAY_WORKERS", path("abc")).is_err());
+        assert!(Config::parse_value::<bool>("RATE_LIMIT_ENABLED", path("yes")).is_err());
+
+        let err = Config::parse_value::<RateLimitPolicy>("RATE_LIMIT_AUTH", path("0/60")).unwrap_err();
+        assert!(err.starts_with("RATE_LIMIT_AUTH"), "{err}");
+    }
+
+    #[test]
+    fn rate_limit_policy_parses_requests_per_window() {
+        let policy: RateLimitPolicy = "10/60".parse().unwrap();
+        assert_eq!((policy.requests, policy.window), (10, Duration::from_secs(60)));
+
+        assert!("10".parse::<RateLimitPolicy>().is_err());
+        assert!("0/60".parse::<RateLimitPolicy>().is_err());
+        assert!("10/0".parse::<RateLimitPolicy>().is_err());
+    }
+}
@@ -0,0 +1,81 @@
+use actix_web::HttpRequest;
+use actix_web::http::header::{self, ETag, EntityTag, Header, IfMatch};
+
+use crate::error::ApiError;
+
+// Posts and comments are tagged with their row version.
+pub fn etag(version: i64) -> ETag {
+    ETag(EntityTag::new_strong(version.to_string()))
+}
+
+// Versions listed in `If-Match`, empty when the header is missing or `*` so the service skips the check.
+// If-Match compares strongly, a weak or foreign tag can never match and fails here already.
+pub fn if_match_versions(req: &HttpRequest) -> Result<Vec<i64>, ApiError> {
+    if !req.headers().contains_key(header::IF_MATCH) {
+        return Ok(vec![]);
+    }
+
+    // The parser drops entries that are not entity tags, nothing left means nothing was valid.
+    let tags = match IfMatch::parse(req) {
+        Ok(IfMatch::Any) => return Ok(vec![]),
+        Ok(IfMatch::Items(tags)) if !tags.is_empty() => tags,
+        _ => return Err(ApiError::bad_request("Invalid If-Match header")),
+    };
+
+    let versions: Vec<i64> = tags.iter()
+        .filter(|tag| !tag.weak)
+        .filter_map(|tag| tag.tag().parse().ok())
+        .collect();
+
+    if versions.is_empty() {
+        return Err(ApiError::precondition_failed("If-Match does not match the current version"));
+    }
+
+    Ok(versions)
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::ResponseError;
+    use actix_web::http::StatusCode;
+    use actix_web::test::TestRequest;
+
+    use super::*;
+
+    fn versions(if_match: Option<&str>) -> Result<Vec<i64>, StatusCode> {
+        let mut request = TestRequest::default();
+        if let Some(value) = if_match {
+            request = request.insert_header((header::IF_MATCH, value));
+        }
+
+        if_match_versions(&request.to_http_request()).map_err(|err| err.status_code())
+    }
+
+    #[test]
+    fn etag_is_the_quoted_version() {
+        assert_eq!(etag(7).to_string(), "\"7\"");
+    }
+
+    #[test]
+    fn missing_or_any_if_match_skips_the_check() {
+        assert_eq!(versions(None), Ok(vec![]));
+        assert_eq!(versions(Some("*")), Ok(vec![]));
+    }
+
+    #[test]
+    fn if_match_lists_every_strong_version() {
+        assert_eq!(versions(Some("\"3\"")), Ok(vec![3]));
+        assert_eq!(versions(Some("\"3\", W/\"4\", \"5\"")), Ok(vec![3, 5]));
+    }
+
+    #[test]
+    fn weak_or_foreign_tags_never_match() {
+        assert_eq!(versions(Some("W/\"3\"")), Err(StatusCode::PRECONDITION_FAILED));
+        assert_eq!(versions(Some("\"abc\"")), Err(StatusCode::PRECONDITION_FAILED));
+    }
+
+    #[test]
+    fn malformed_if_match_is_a_bad_request() {
+        assert_eq!(versions(Some("3")), Err(StatusCode::BAD_REQUEST));
+    }
+}
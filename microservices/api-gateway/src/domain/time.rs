@@ -4,7 +4,18 @@ use prost_types::Timestamp;
 pub fn timestamp_to_datetime(ts: Option<Timestamp>) -> DateTime<Utc> {
     ts.map(|t| {
         DateTime::from_timestamp(t.seconds, t.nanos as u32)
-            .unwrap_or_else(|| Utc::now())
+            .unwrap_or_else(Utc::now)
     })
-    .unwrap_or_else(|| Utc::now())
-}
\ No newline at end of file
+    .unwrap_or_else(Utc::now)
+}
+
+pub fn datetime_to_timestamp(dt: DateTime<Utc>) -> Timestamp {
+    Timestamp {
+        seconds: dt.timestamp(),
+        nanos: dt.timestamp_subsec_nanos() as i32,
+    }
+}
+
+pub fn optional_timestamp_to_datetime(ts: Option<Timestamp>) -> Option<DateTime<Utc>> {
+    ts.and_then(|t| DateTime::from_timestamp(t.seconds, t.nanos as u32))
+}
@@ -0,0 +1,66 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tonic::Status;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::{domain::time::{optional_timestamp_to_datetime, timestamp_to_datetime}, proto::auth::ApiKey as ProtoApiKey};
+
+#[derive(Serialize, ToSchema)]
+pub struct ApiKey {
+    pub id: Uuid,
+    pub name: String,
+    pub prefix: String,
+    pub scopes: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl TryFrom<ProtoApiKey> for ApiKey {
+    type Error = Status;
+
+    fn try_from(value: ProtoApiKey) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: Uuid::parse_str(&value.id)
+                .map_err(|_| Status::internal("Error converting UUID"))?,
+            name: value.name,
+            prefix: value.prefix,
+            scopes: value.scopes,
+            created_at: timestamp_to_datetime(value.created_at),
+            last_used_at: optional_timestamp_to_datetime(value.last_used_at),
+            revoked_at: optional_timestamp_to_datetime(value.revoked_at),
+        })
+    }
+}
+
+// ---------- Create Api Key ----------
+#[derive(Deserialize, ToSchema)]
+pub struct CreateApiKeyRequest {
+    pub name: String,
+    pub scopes: Vec<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CreateApiKeyResponse {
+    pub api_key: ApiKey,
+    pub key: String,
+}
+
+// ---------- List Api Keys ----------
+#[derive(Serialize, ToSchema)]
+pub struct ListApiKeysResponse {
+    pub api_keys: Vec<ApiKey>,
+}
+
+// ---------- Revoke Api Key ----------
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Path)]
+pub struct RevokeApiKeyRequest {
+    pub id: Uuid,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct RevokeApiKeyResponse {
+    pub api_key: ApiKey,
+}
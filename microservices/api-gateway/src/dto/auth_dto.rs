@@ -1,36 +1,92 @@
 use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
 
 // ---------- Sign Up ----------
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct User {
     pub id: String,
     pub username: String,
     pub email: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct SignUpRequest {
     pub username: String,
     pub email: String,
     pub password: String,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct SignUpResponse {
     pub user: User,
     pub access_token: String,
+    // Also set as the `refresh_token` cookie, for clients that don't keep cookies.
+    pub refresh_token: String,
 }
 
 // ---------- Sign In ----------
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct SignInRequest {
     pub email: String,
     pub password: String,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct SignInResponse {
     pub user: User,
     pub access_token: String,
-}
\ No newline at end of file
+    // Also set as the `refresh_token` cookie, for clients that don't keep cookies.
+    pub refresh_token: String,
+}
+
+// ---------- Refresh ----------
+
+// Clients that don't keep cookies send the refresh token in the body instead.
+#[derive(Deserialize, ToSchema)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct RefreshResponse {
+    pub access_token: String,
+    pub refresh_token: String,
+}
+
+// ---------- Oidc Sign In ----------
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Path)]
+pub struct OidcProviderPath {
+    pub provider: String,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct OidcCallbackQuery {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct OidcSignInResponse {
+    pub user: User,
+    pub access_token: String,
+    // Also set as the `refresh_token` cookie, for clients that don't keep cookies.
+    pub refresh_token: String,
+    pub created: bool,
+}
+
+// ---------- Magic Link ----------
+
+#[derive(Deserialize, ToSchema)]
+pub struct RequestMagicLinkRequest {
+    pub email: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ConsumeMagicLinkRequest {
+    pub token: String,
+}
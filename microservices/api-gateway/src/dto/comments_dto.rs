@@ -1,11 +1,12 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use tonic::Status;
+use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
-use crate::{domain::time::timestamp_to_datetime, proto::comments::{self, Comment as ProtoComment}};
+use crate::{domain::time::{optional_timestamp_to_datetime, timestamp_to_datetime}, proto::comments::{self, Comment as ProtoComment, CommentRevision as ProtoCommentRevision}};
 
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct Comment {
     pub id: Uuid,
     pub content: String,
@@ -13,6 +14,33 @@ pub struct Comment {
     pub post_id: Uuid,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub edited_at: Option<DateTime<Utc>>,
+    pub edit_count: i32,
+    // Also sent as the ETag, echo it in If-Match to update only this version.
+    pub version: i64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CommentRevision {
+    pub comment_id: Uuid,
+    pub revision: i32,
+    pub content: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl TryFrom<ProtoCommentRevision> for CommentRevision {
+    type Error = Status;
+
+    fn try_from(value: ProtoCommentRevision) -> Result<Self, Self::Error> {
+        Ok(CommentRevision {
+            comment_id: Uuid::parse_str(&value.comment_id)
+                .map_err(|_| Status::internal("Error converting UUID"))?,
+            revision: value.revision,
+            content: value.content,
+            created_at: timestamp_to_datetime(value.created_at),
+        })
+    }
 }
 
 impl TryFrom<ProtoComment> for Comment {
@@ -29,12 +57,17 @@ impl TryFrom<ProtoComment> for Comment {
                 .map_err(|_| Status::internal("Error converting UUID"))?,
             created_at: timestamp_to_datetime(value.created_at),
             updated_at: timestamp_to_datetime(value.updated_at),
+            deleted_at: optional_timestamp_to_datetime(value.deleted_at),
+            edited_at: optional_timestamp_to_datetime(value.edited_at),
+            edit_count: value.edit_count,
+            version: value.version,
         })
     }
 }
 
 // ---------- Get Post ----------
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Path)]
 pub struct GetCommentRequest {
     pub id: Uuid,
 }
@@ -42,18 +75,20 @@ pub struct GetCommentRequest {
 impl From<GetCommentRequest> for comments::GetCommentRequest {
     fn from(value: GetCommentRequest) -> Self {
         Self {
-            id: value.id.to_string()
+            id: value.id.to_string(),
+            // Filled from the caller by the handler.
+            requester_id: String::new(),
         }
     }
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct GetCommentResponse {
     pub comment: Comment
 }
 
 // ---------- Get Comments ----------
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct GetCommentsRequest {
     pub post_id: Uuid
 }
@@ -61,79 +96,117 @@ pub struct GetCommentsRequest {
 impl From<GetCommentsRequest> for comments::GetCommentsRequest {
     fn from(value: GetCommentsRequest) -> Self {
         Self {
-            post_id: value.post_id.to_string()
+            post_id: value.post_id.to_string(),
+            // Filled from the caller by the handler.
+            requester_id: String::new(),
         }
     }
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct GetCommentsResponse {
     pub comments: Vec<Comment>
 }
 
 // ---------- Create Post ----------
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct AddCommentRequest {
     pub content: String,
-    pub user_id: Uuid,
     pub post_id: Uuid,
 }
 
-impl From<AddCommentRequest> for comments::AddCommentRequest {
-    fn from(value: AddCommentRequest) -> Self {
-        Self {
-            content: value.content,
-            user_id: value.user_id.to_string(),
-            post_id: value.post_id.to_string(),
+impl AddCommentRequest {
+    // The author is always the caller, never taken from the body.
+    pub fn into_proto(self, user_id: Uuid) -> comments::AddCommentRequest {
+        comments::AddCommentRequest {
+            content: self.content,
+            user_id: user_id.to_string(),
+            post_id: self.post_id.to_string(),
         }
     }
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct AddCommentResponse {
     pub comment: Comment
 }
 
 // ---------- Update Post ----------
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct UpdateCommentRequest {
     pub id: Uuid,
     pub content: String,
-    pub user_id: Uuid,
     pub post_id: Uuid,
 }
 
-impl From<UpdateCommentRequest> for comments::UpdateCommentRequest {
-    fn from(value: UpdateCommentRequest) -> Self {
-        Self {
-            id: value.id.to_string(),
-            content: value.content,
-            user_id: value.user_id.to_string(),
-            post_id: value.post_id.to_string(),
+impl UpdateCommentRequest {
+    pub fn into_proto(self, user_id: Uuid) -> comments::UpdateCommentRequest {
+        comments::UpdateCommentRequest {
+            id: self.id.to_string(),
+            content: self.content,
+            user_id: user_id.to_string(),
+            post_id: self.post_id.to_string(),
+            // Filled from If-Match by the handler.
+            expected_versions: vec![],
         }
     }
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct UpdateCommentResponse {
     pub comment: Comment
 }
 
 // ---------- Delete Post ----------
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Path)]
 pub struct DeleteCommentRequest {
     pub id: Uuid,
 }
 
-impl From<DeleteCommentRequest> for comments::DeleteCommentRequest {
-    fn from(value: DeleteCommentRequest) -> Self {
-        Self {
-            id: value.id.to_string()
+impl DeleteCommentRequest {
+    pub fn into_proto(self, requester_id: Uuid) -> comments::DeleteCommentRequest {
+        comments::DeleteCommentRequest {
+            id: self.id.to_string(),
+            requester_id: requester_id.to_string(),
         }
     }
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct DeleteCommentResponse {
     pub comment: Comment
+}
+
+// ---------- Restore Comment ----------
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Path)]
+pub struct RestoreCommentRequest {
+    pub id: Uuid,
+}
+
+impl RestoreCommentRequest {
+    pub fn into_proto(self, requester_id: Uuid) -> comments::RestoreCommentRequest {
+        comments::RestoreCommentRequest {
+            id: self.id.to_string(),
+            requester_id: requester_id.to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct RestoreCommentResponse {
+    pub comment: Comment
+}
+
+// ---------- List Comment Revisions ----------
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Path)]
+pub struct ListCommentRevisionsRequest {
+    pub id: Uuid,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ListCommentRevisionsResponse {
+    pub revisions: Vec<CommentRevision>
 }
\ No newline at end of file
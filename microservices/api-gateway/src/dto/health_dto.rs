@@ -0,0 +1,24 @@
+use serde::Serialize;
+use utoipa::ToSchema;
+
+#[derive(Serialize, ToSchema)]
+pub struct LivenessResponse {
+    pub status: &'static str,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ServiceHealth {
+    pub name: &'static str,
+    // `SERVING`, `NOT_SERVING`, `UNKNOWN` or `UNREACHABLE`.
+    pub status: String,
+    pub latency_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ReadinessResponse {
+    // `ready` only when every service is serving.
+    pub status: &'static str,
+    pub services: Vec<ServiceHealth>,
+}
@@ -1,3 +1,10 @@
 pub mod auth_dto;
 pub mod posts_dto;
-pub mod comments_dto;
\ No newline at end of file
+pub mod comments_dto;
+pub mod api_keys_dto;
+pub mod sessions_dto;
+pub mod security_events_dto;
+pub mod health_dto;
+pub mod users_dto;
+pub mod tags_dto;
+pub mod search_dto;
@@ -1,17 +1,90 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use tonic::Status;
+use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 
-use crate::{domain::time::timestamp_to_datetime, proto::posts::{self, Post as ProtoPost}};
+use crate::{domain::time::{datetime_to_timestamp, optional_timestamp_to_datetime, timestamp_to_datetime}, proto::posts::{self, Post as ProtoPost, PostRevision as ProtoPostRevision}};
 
-#[derive(Serialize)]
+// Drafts and scheduled posts are only visible to their author.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum PostStatus {
+    Draft,
+    Scheduled,
+    Published,
+}
+
+impl PostStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Draft => "draft",
+            Self::Scheduled => "scheduled",
+            Self::Published => "published",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "draft" => Some(Self::Draft),
+            "scheduled" => Some(Self::Scheduled),
+            "published" => Some(Self::Published),
+            _ => None,
+        }
+    }
+}
+
+// Who besides the author can see a published post.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum PostVisibility {
+    Public,
+    // Users following the author. Anyone can follow without the author's approval, so this
+    // narrows who gets the post rather than keeping it private.
+    Followers,
+    Private,
+}
+
+impl PostVisibility {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Public => "public",
+            Self::Followers => "followers",
+            Self::Private => "private",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "public" => Some(Self::Public),
+            "followers" => Some(Self::Followers),
+            "private" => Some(Self::Private),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
 pub struct Post {
     pub id: Uuid,
     pub title: String,
     pub description: String,
     pub user_id: Uuid,
     pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    // True once the title or description changed after creation, see the post's revisions.
+    pub edited: bool,
+    pub edited_at: Option<DateTime<Utc>>,
+    pub revision: i32,
+    // Also sent as the ETag, echo it in If-Match to update only this version.
+    pub version: i64,
+    pub status: PostStatus,
+    // When a scheduled post goes out.
+    pub publish_at: Option<DateTime<Utc>>,
+    pub published_at: Option<DateTime<Utc>>,
+    pub visibility: PostVisibility,
+    pub locked_at: Option<DateTime<Utc>>,
+    pub deleted_at: Option<DateTime<Utc>>,
 }
 
 impl TryFrom<ProtoPost> for Post {
@@ -26,12 +99,50 @@ impl TryFrom<ProtoPost> for Post {
             user_id: Uuid::parse_str(&value.user_id)
                 .map_err(|_| Status::internal("Error converting UUID"))?,
             created_at: timestamp_to_datetime(value.created_at),
+            updated_at: timestamp_to_datetime(value.updated_at),
+            edited: value.edited_at.is_some(),
+            edited_at: optional_timestamp_to_datetime(value.edited_at),
+            revision: value.revision,
+            version: value.version,
+            status: PostStatus::parse(&value.status)
+                .ok_or_else(|| Status::internal("Unknown post status"))?,
+            publish_at: optional_timestamp_to_datetime(value.publish_at),
+            published_at: optional_timestamp_to_datetime(value.published_at),
+            visibility: PostVisibility::parse(&value.visibility)
+                .ok_or_else(|| Status::internal("Unknown post visibility"))?,
+            locked_at: optional_timestamp_to_datetime(value.locked_at),
+            deleted_at: optional_timestamp_to_datetime(value.deleted_at),
+        })
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PostRevision {
+    pub post_id: Uuid,
+    pub revision: i32,
+    pub title: String,
+    pub description: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl TryFrom<ProtoPostRevision> for PostRevision {
+    type Error = Status;
+
+    fn try_from(value: ProtoPostRevision) -> Result<Self, Self::Error> {
+        Ok(Self {
+            post_id: Uuid::parse_str(&value.post_id)
+                .map_err(|_| Status::internal("Error converting UUID"))?,
+            revision: value.revision,
+            title: value.title,
+            description: value.description,
+            created_at: timestamp_to_datetime(value.created_at),
         })
     }
 }
 
 // ---------- Get Post ----------
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Path)]
 pub struct GetPostRequest {
     pub id: Uuid,
 }
@@ -39,50 +150,61 @@ pub struct GetPostRequest {
 impl From<GetPostRequest> for posts::GetPostRequest {
     fn from(value: GetPostRequest) -> Self {
         Self {
-            id: value.id.to_string()
+            id: value.id.to_string(),
+            // Filled from the caller by the handler.
+            requester_id: String::new(),
         }
     }
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct GetPostResponse {
     pub post: Post
 }
 
 // ---------- Get Posts ----------
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct GetPostsRequest { }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct GetPostsResponse {
     pub posts: Vec<Post>
 }
 
 // ---------- Create Post ----------
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct CreatePostRequest {
-    pub user_id: Uuid,
     pub title: String,
     pub description: String,
+    // Defaults to published.
+    pub status: Option<PostStatus>,
+    // Required for scheduled posts.
+    pub publish_at: Option<DateTime<Utc>>,
+    // Defaults to public.
+    pub visibility: Option<PostVisibility>,
 }
 
-impl From<CreatePostRequest> for posts::CreatePostRequest {
-    fn from(value: CreatePostRequest) -> Self {
-        Self {
-            user_id: value.user_id.to_string(),
-            title: value.title,
-            description: value.description,
+impl CreatePostRequest {
+    // The author is always the caller, never taken from the body.
+    pub fn into_proto(self, user_id: Uuid) -> posts::CreatePostRequest {
+        posts::CreatePostRequest {
+            user_id: user_id.to_string(),
+            title: self.title,
+            description: self.description,
+            status: self.status.map(|status| status.as_str().to_string()).unwrap_or_default(),
+            publish_at: self.publish_at.map(datetime_to_timestamp),
+            visibility: self.visibility.map(|visibility| visibility.as_str().to_string()).unwrap_or_default(),
         }
     }
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct CreatePostResponse {
     pub post: Post
 }
 
 // ---------- Update Post ----------
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct UpdatePostRequest {
     pub id: Uuid,
     pub title: String,
@@ -93,19 +215,61 @@ impl From<UpdatePostRequest> for posts::UpdatePostRequest {
     fn from(value: UpdatePostRequest) -> Self {
         Self {
             id: value.id.to_string(),
-            title: value.title,
-            description: value.description,
+            title: Some(value.title),
+            description: Some(value.description),
+            // Filled from If-Match by the handler.
+            expected_versions: vec![],
+            visibility: None,
+            // Filled from the caller by the handler.
+            requester_id: String::new(),
         }
     }
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct UpdatePostResponse {
     pub post: Post
 }
 
+// ---------- Patch Post ----------
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Path)]
+pub struct PatchPostPath {
+    pub id: Uuid,
+}
+
+// Only the fields present are changed. Unknown fields are rejected so a typo doesn't turn into a no-op.
+#[derive(Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct PatchPostRequest {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub visibility: Option<PostVisibility>,
+}
+
+impl PatchPostRequest {
+    pub fn into_proto(self, id: Uuid) -> posts::UpdatePostRequest {
+        posts::UpdatePostRequest {
+            id: id.to_string(),
+            title: self.title,
+            description: self.description,
+            // Filled from If-Match by the handler.
+            expected_versions: vec![],
+            visibility: self.visibility.map(|visibility| visibility.as_str().to_string()),
+            // Filled from the caller by the handler.
+            requester_id: String::new(),
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PatchPostResponse {
+    pub post: Post
+}
+
 // ---------- Delete Post ----------
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Path)]
 pub struct DeletePostRequest {
     pub id: Uuid,
 }
@@ -114,11 +278,149 @@ impl From<DeletePostRequest> for posts::DeletePostRequest {
     fn from(value: DeletePostRequest) -> Self {
         Self {
             id: value.id.to_string(),
+            // Filled from the caller by the handler.
+            requester_id: String::new(),
         }
     }
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct DeletePostResponse {
     pub post: Post
+}
+
+// ---------- Restore Post ----------
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Path)]
+pub struct RestorePostRequest {
+    pub id: Uuid,
+}
+
+impl From<RestorePostRequest> for posts::RestorePostRequest {
+    fn from(value: RestorePostRequest) -> Self {
+        Self {
+            id: value.id.to_string(),
+            // Filled from the caller by the handler.
+            requester_id: String::new(),
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct RestorePostResponse {
+    pub post: Post
+}
+
+// ---------- List Post Revisions ----------
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Path)]
+pub struct ListPostRevisionsRequest {
+    pub id: Uuid,
+}
+
+impl From<ListPostRevisionsRequest> for posts::ListPostRevisionsRequest {
+    fn from(value: ListPostRevisionsRequest) -> Self {
+        Self {
+            post_id: value.id.to_string(),
+            // Filled from the caller by the handler.
+            requester_id: String::new(),
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ListPostRevisionsResponse {
+    pub revisions: Vec<PostRevision>
+}
+
+// ---------- Get Post Revision ----------
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Path)]
+pub struct GetPostRevisionRequest {
+    pub id: Uuid,
+    pub revision: i32,
+}
+
+impl From<GetPostRevisionRequest> for posts::GetPostRevisionRequest {
+    fn from(value: GetPostRevisionRequest) -> Self {
+        Self {
+            post_id: value.id.to_string(),
+            revision: value.revision,
+            // Filled from the caller by the handler.
+            requester_id: String::new(),
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct GetPostRevisionResponse {
+    pub revision: PostRevision
+}
+
+// ---------- List Drafts ----------
+#[derive(Serialize, ToSchema)]
+pub struct ListDraftsResponse {
+    pub posts: Vec<Post>
+}
+
+// ---------- Publish Post ----------
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Path)]
+pub struct PublishPostPath {
+    pub id: Uuid,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct PublishPostRequest {
+    // A future time schedules the post instead of publishing it right away.
+    pub publish_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PublishPostResponse {
+    pub post: Post
+}
+
+// ---------- Lock Post ----------
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Path)]
+pub struct LockPostRequest {
+    pub id: Uuid,
+}
+
+impl From<LockPostRequest> for posts::LockPostRequest {
+    fn from(value: LockPostRequest) -> Self {
+        Self {
+            id: value.id.to_string(),
+            // Filled from the caller by the handler.
+            requester_id: String::new(),
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct LockPostResponse {
+    pub post: Post
+}
+
+// ---------- Unlock Post ----------
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Path)]
+pub struct UnlockPostRequest {
+    pub id: Uuid,
+}
+
+impl From<UnlockPostRequest> for posts::UnlockPostRequest {
+    fn from(value: UnlockPostRequest) -> Self {
+        Self {
+            id: value.id.to_string(),
+            // Filled from the caller by the handler.
+            requester_id: String::new(),
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct UnlockPostResponse {
+    pub post: Post
 }
\ No newline at end of file
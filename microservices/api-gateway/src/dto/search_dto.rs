@@ -0,0 +1,107 @@
+use serde::{Deserialize, Serialize};
+use tonic::Status;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::dto::{comments_dto::Comment, posts_dto::Post};
+use crate::proto::{comments::CommentSearchHit as ProtoCommentSearchHit, posts::PostSearchHit as ProtoPostSearchHit, users::UserSearchHit as ProtoUserSearchHit};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchType {
+    Posts,
+    Comments,
+    Users,
+}
+
+// Highlights are HTML safe to insert as is: matches are wrapped in <mark></mark>, the rest of the
+// text is escaped.
+#[derive(Serialize, ToSchema)]
+pub struct PostSearchHit {
+    pub post: Post,
+    pub rank: f32,
+    pub title_highlight: String,
+    pub description_highlight: String,
+}
+
+impl TryFrom<ProtoPostSearchHit> for PostSearchHit {
+    type Error = Status;
+
+    fn try_from(value: ProtoPostSearchHit) -> Result<Self, Self::Error> {
+        Ok(Self {
+            post: value.post
+                .ok_or_else(|| Status::internal("Search hit without a post"))?
+                .try_into()?,
+            rank: value.rank,
+            title_highlight: value.title_highlight,
+            description_highlight: value.description_highlight,
+        })
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CommentSearchHit {
+    pub comment: Comment,
+    pub rank: f32,
+    pub content_highlight: String,
+}
+
+impl TryFrom<ProtoCommentSearchHit> for CommentSearchHit {
+    type Error = Status;
+
+    fn try_from(value: ProtoCommentSearchHit) -> Result<Self, Self::Error> {
+        Ok(Self {
+            comment: value.comment
+                .ok_or_else(|| Status::internal("Search hit without a comment"))?
+                .try_into()?,
+            rank: value.rank,
+            content_highlight: value.content_highlight,
+        })
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct UserSearchHit {
+    pub id: Uuid,
+    pub username: String,
+    pub rank: f32,
+    pub username_highlight: String,
+}
+
+impl TryFrom<ProtoUserSearchHit> for UserSearchHit {
+    type Error = Status;
+
+    fn try_from(value: ProtoUserSearchHit) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: Uuid::parse_str(&value.id)
+                .map_err(|_| Status::internal("Error converting UUID"))?,
+            username: value.username,
+            rank: value.rank,
+            username_highlight: value.username_highlight,
+        })
+    }
+}
+
+// ---------- Search ----------
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SearchQuery {
+    // Words, "quoted phrases", or and -excluded words.
+    pub q: String,
+    // Searches everything when left out.
+    #[serde(rename = "type")]
+    pub kind: Option<SearchType>,
+    // Per type, 1-100, defaults to 20.
+    pub limit: Option<i32>,
+    pub offset: Option<i32>,
+}
+
+// Types that weren't searched stay empty, so do the ones listed in `unavailable` whose service
+// failed to answer.
+#[derive(Serialize, ToSchema)]
+pub struct SearchResponse {
+    pub posts: Vec<PostSearchHit>,
+    pub comments: Vec<CommentSearchHit>,
+    pub users: Vec<UserSearchHit>,
+    pub unavailable: Vec<SearchType>,
+}
@@ -0,0 +1,55 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tonic::Status;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::{domain::time::timestamp_to_datetime, proto::auth::SecurityEvent as ProtoSecurityEvent};
+
+#[derive(Serialize, ToSchema)]
+pub struct SecurityEvent {
+    pub id: Uuid,
+    pub user_id: Option<Uuid>,
+    pub event_type: String,
+    pub ip_address: String,
+    pub user_agent: String,
+    pub country: String,
+    #[schema(value_type = Object)]
+    pub details: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+}
+
+impl TryFrom<ProtoSecurityEvent> for SecurityEvent {
+    type Error = Status;
+
+    fn try_from(value: ProtoSecurityEvent) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: Uuid::parse_str(&value.id)
+                .map_err(|_| Status::internal("Error converting UUID"))?,
+            user_id: Uuid::parse_str(&value.user_id).ok(),
+            event_type: value.event_type,
+            ip_address: value.ip_address,
+            user_agent: value.user_agent,
+            country: value.country,
+            details: serde_json::from_str(&value.details)
+                .unwrap_or(serde_json::Value::Null),
+            created_at: timestamp_to_datetime(value.created_at),
+        })
+    }
+}
+
+// ---------- List Security Events ----------
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListSecurityEventsQuery {
+    // Defaults to the caller, other accounts need an admin.
+    pub user_id: Option<Uuid>,
+    pub limit: Option<i32>,
+    // Only events older than this, for paging through the history.
+    pub before: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ListSecurityEventsResponse {
+    pub events: Vec<SecurityEvent>,
+}
@@ -0,0 +1,53 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tonic::Status;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::{domain::time::timestamp_to_datetime, proto::auth::Session as ProtoSession};
+
+#[derive(Serialize, ToSchema)]
+pub struct Session {
+    pub id: Uuid,
+    pub user_agent: String,
+    pub ip_address: String,
+    pub current: bool,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl TryFrom<ProtoSession> for Session {
+    type Error = Status;
+
+    fn try_from(value: ProtoSession) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: Uuid::parse_str(&value.id)
+                .map_err(|_| Status::internal("Error converting UUID"))?,
+            user_agent: value.user_agent,
+            ip_address: value.ip_address,
+            current: value.current,
+            created_at: timestamp_to_datetime(value.created_at),
+            last_used_at: timestamp_to_datetime(value.last_used_at),
+            expires_at: timestamp_to_datetime(value.expires_at),
+        })
+    }
+}
+
+// ---------- List Sessions ----------
+#[derive(Serialize, ToSchema)]
+pub struct ListSessionsResponse {
+    pub sessions: Vec<Session>,
+}
+
+// ---------- Revoke Session ----------
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Path)]
+pub struct RevokeSessionRequest {
+    pub id: Uuid,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct RevokeSessionResponse {
+    pub session: Session,
+}
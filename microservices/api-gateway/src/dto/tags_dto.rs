@@ -0,0 +1,58 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{dto::posts_dto::Post, proto::posts::PopularTag as ProtoPopularTag};
+
+#[derive(Serialize, ToSchema)]
+pub struct PopularTag {
+    pub tag: String,
+    // Public posts with this tag published within the window.
+    pub post_count: i64,
+}
+
+impl From<ProtoPopularTag> for PopularTag {
+    fn from(value: ProtoPopularTag) -> Self {
+        Self {
+            tag: value.tag,
+            post_count: value.post_count,
+        }
+    }
+}
+
+// ---------- List Posts By Tag ----------
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Path)]
+pub struct ListPostsByTagPath {
+    // With or without the leading '#', case doesn't matter.
+    pub tag: String,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListPostsByTagQuery {
+    // 1-100, defaults to 50.
+    pub limit: Option<i32>,
+    // Only posts published before this, for paging.
+    pub before: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ListPostsByTagResponse {
+    pub posts: Vec<Post>,
+}
+
+// ---------- List Trending Tags ----------
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListTrendingTagsQuery {
+    // Window to count posts in, 1-720, defaults to 24.
+    pub hours: Option<i32>,
+    // 1-100, defaults to 10.
+    pub limit: Option<i32>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ListTrendingTagsResponse {
+    pub tags: Vec<PopularTag>,
+}
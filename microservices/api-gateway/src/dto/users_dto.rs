@@ -0,0 +1,18 @@
+use serde::Deserialize;
+use utoipa::IntoParams;
+use uuid::Uuid;
+
+// ---------- Follow User ----------
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Path)]
+pub struct FollowUserRequest {
+    // The user to follow.
+    pub id: Uuid,
+}
+
+// ---------- Unfollow User ----------
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Path)]
+pub struct UnfollowUserRequest {
+    pub id: Uuid,
+}
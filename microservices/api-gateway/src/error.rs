@@ -0,0 +1,410 @@
+use std::fmt;
+
+use actix_web::error::{JsonPayloadError, PathError, QueryPayloadError};
+use actix_web::{HttpRequest, HttpResponse, ResponseError, http::{StatusCode, header}};
+use prost::Message;
+use serde::{Serialize, de::DeserializeOwned};
+use tonic::Code;
+use utoipa::ToSchema;
+
+use crate::proto::google::rpc;
+
+pub const PROBLEM_JSON: &str = "application/problem+json";
+
+// Services answer a stale `expected_versions` with this reason, over HTTP it's a failed If-Match.
+pub const VERSION_MISMATCH: &str = "VERSION_MISMATCH";
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+// Every error the gateway returns, rendered as RFC 7807 `application/problem+json`.
+#[derive(Debug)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub detail: String,
+    // Stable machine readable code, from `google.rpc.ErrorInfo` when the service sent one.
+    pub reason: Option<String>,
+    pub errors: Vec<FieldError>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ProblemDetails<'a> {
+    #[serde(rename = "type")]
+    problem_type: &'a str,
+    title: &'a str,
+    status: u16,
+    detail: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<&'a str>,
+    #[serde(skip_serializing_if = "<[FieldError]>::is_empty")]
+    errors: &'a [FieldError],
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, detail: impl Into<String>) -> Self {
+        Self {
+            status,
+            detail: detail.into(),
+            reason: None,
+            errors: vec![],
+        }
+    }
+
+    pub fn bad_request(detail: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, detail)
+    }
+
+    pub fn unauthorized(detail: impl Into<String>) -> Self {
+        Self::new(StatusCode::UNAUTHORIZED, detail)
+    }
+
+    pub fn forbidden(detail: impl Into<String>) -> Self {
+        Self::new(StatusCode::FORBIDDEN, detail)
+    }
+
+    pub fn not_found(detail: impl Into<String>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, detail)
+    }
+
+    pub fn precondition_failed(detail: impl Into<String>) -> Self {
+        let mut error = Self::new(StatusCode::PRECONDITION_FAILED, detail);
+        error.reason = Some(VERSION_MISMATCH.to_string());
+        error
+    }
+
+    pub fn internal() -> Self {
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, "Internal server error")
+    }
+
+    // A service answered without a field the gateway needs, which is a bug on their side and not the client's.
+    pub fn missing_field(field: &str) -> Self {
+        tracing::error!("Upstream response is missing `{field}`");
+        Self::internal()
+    }
+
+    pub fn with_errors(mut self, errors: Vec<FieldError>) -> Self {
+        self.errors = errors;
+        self
+    }
+}
+
+pub fn status_code_for(code: Code) -> StatusCode {
+    match code {
+        Code::Ok => StatusCode::OK,
+        Code::InvalidArgument | Code::OutOfRange => StatusCode::BAD_REQUEST,
+        Code::Unauthenticated => StatusCode::UNAUTHORIZED,
+        Code::PermissionDenied => StatusCode::FORBIDDEN,
+        Code::NotFound => StatusCode::NOT_FOUND,
+        Code::AlreadyExists | Code::Aborted | Code::FailedPrecondition => StatusCode::CONFLICT,
+        Code::ResourceExhausted => StatusCode::TOO_MANY_REQUESTS,
+        // The client went away, nginx's 499 is the closest thing HTTP has.
+        Code::Cancelled => StatusCode::from_u16(499).unwrap_or(StatusCode::BAD_REQUEST),
+        Code::Unimplemented => StatusCode::NOT_IMPLEMENTED,
+        Code::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+        Code::DeadlineExceeded => StatusCode::GATEWAY_TIMEOUT,
+        Code::Unknown | Code::Internal | Code::DataLoss => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+impl From<tonic::Status> for ApiError {
+    fn from(status: tonic::Status) -> Self {
+        let status_code = status_code_for(status.code());
+
+        // Messages of internal failures may carry SQL or stack details, they stay in the logs.
+        let detail = match status.code() {
+            Code::Unknown | Code::Internal | Code::DataLoss => {
+                tracing::error!("Upstream error: {}", status.message());
+                "Internal server error".to_string()
+            },
+            Code::Unavailable => "Service is temporarily unavailable".to_string(),
+            Code::DeadlineExceeded => "Service did not respond in time".to_string(),
+            _ => status.message().to_string(),
+        };
+
+        let mut error = Self::new(status_code, detail);
+
+        if let Ok(details) = rpc::Status::decode(status.details()) {
+            for any in details.details {
+                match any.type_url.rsplit('/').next() {
+                    Some("google.rpc.BadRequest") => {
+                        if let Ok(bad_request) = rpc::BadRequest::decode(any.value.as_slice()) {
+                            error.errors.extend(bad_request.field_violations.into_iter().map(|violation| FieldError {
+                                field: violation.field,
+                                message: violation.description,
+                            }));
+                        }
+                    },
+                    Some("google.rpc.ErrorInfo") => {
+                        if let Ok(info) = rpc::ErrorInfo::decode(any.value.as_slice()) {
+                            if info.reason == VERSION_MISMATCH {
+                                error.status = StatusCode::PRECONDITION_FAILED;
+                            }
+
+                            error.reason = Some(info.reason);
+                        }
+                    },
+                    _ => {},
+                }
+            }
+        }
+
+        error
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.status, self.detail)
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        self.status
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let body = ProblemDetails {
+            problem_type: "about:blank",
+            title: self.status.canonical_reason().unwrap_or("Error"),
+            status: self.status.as_u16(),
+            detail: &self.detail,
+            reason: self.reason.as_deref(),
+            errors: &self.errors,
+        };
+
+        HttpResponse::build(self.status)
+            .insert_header((header::CONTENT_TYPE, PROBLEM_JSON))
+            .body(serde_json::to_string(&body).unwrap_or_default())
+    }
+}
+
+// serde names the offending field in backticks, e.g. "missing field `content` at line 1 column 2".
+fn field_errors(message: &str) -> Vec<FieldError> {
+    ["missing field `", "unknown field `"].iter()
+        .find_map(|pattern| {
+            let start = message.find(pattern)?;
+            let field = message[start + pattern.len()..].split('`').next()?;
+            let description = message[start..].split(" at line").next().unwrap_or(message);
+
+            Some(FieldError {
+                field: field.to_string(),
+                message: description.to_string(),
+            })
+        })
+        .into_iter()
+        .collect()
+}
+
+// Extractor failures are rendered like every other error instead of actix's plain text.
+pub fn json_error_handler(error: JsonPayloadError, _req: &HttpRequest) -> actix_web::Error {
+    let status = match &error {
+        JsonPayloadError::OverflowKnownLength { .. } | JsonPayloadError::Overflow { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+        JsonPayloadError::ContentType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        _ => StatusCode::BAD_REQUEST,
+    };
+
+    let message = error.to_string();
+    let errors = match &error {
+        JsonPayloadError::Deserialize(error) => field_errors(&error.to_string()),
+        _ => vec![],
+    };
+
+    ApiError::new(status, message).with_errors(errors).into()
+}
+
+// For endpoints whose body may be left out. Unlike `Option<web::Json<T>>`, which reads any
+// failure as no body, a body that is there but doesn't parse is refused.
+pub fn optional_json<T: DeserializeOwned>(body: &[u8]) -> Result<Option<T>, ApiError> {
+    if body.iter().all(u8::is_ascii_whitespace) {
+        return Ok(None);
+    }
+
+    serde_json::from_slice(body).map(Some).map_err(|error| {
+        let errors = field_errors(&error.to_string());
+
+        ApiError::bad_request(format!("Json deserialize error: {error}")).with_errors(errors)
+    })
+}
+
+// The route matched, so a segment that doesn't parse is a malformed request and not a missing resource.
+pub fn path_error_handler(error: PathError, _req: &HttpRequest) -> actix_web::Error {
+    ApiError::bad_request(error.to_string()).into()
+}
+
+pub fn query_error_handler(error: QueryPayloadError, _req: &HttpRequest) -> actix_web::Error {
+    let message = error.to_string();
+    let errors = field_errors(&message);
+
+    ApiError::bad_request(message).with_errors(errors).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_missing_field_is_named() {
+        let errors = field_errors("missing field `content` at line 1 column 2");
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].field, "content");
+        assert_eq!(errors[0].message, "missing field `content`");
+    }
+
+    #[test]
+    fn an_unknown_field_is_named_along_with_the_expected_ones() {
+        let errors = field_errors("unknown field `titel`, expected `title` or `content` at line 1 column 9");
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].field, "titel");
+        assert_eq!(errors[0].message, "unknown field `titel`, expected `title` or `content`");
+    }
+
+    #[test]
+    fn a_query_string_error_is_parsed_too() {
+        let errors = field_errors("Query deserialize error: missing field `q`");
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].field, "q");
+        assert_eq!(errors[0].message, "missing field `q`");
+    }
+
+    fn any<M: Message>(type_name: &str, message: &M) -> prost_types::Any {
+        prost_types::Any {
+            type_url: format!("type.googleapis.com/google.rpc.{type_name}"),
+            value: message.encode_to_vec(),
+        }
+    }
+
+    fn status_with(code: Code, message: &str, details: Vec<prost_types::Any>) -> tonic::Status {
+        let status = rpc::Status { code: code as i32, message: message.to_string(), details };
+
+        tonic::Status::with_details(code, message, status.encode_to_vec().into())
+    }
+
+    fn error_info(reason: &str) -> prost_types::Any {
+        any("ErrorInfo", &rpc::ErrorInfo { reason: reason.to_string(), ..Default::default() })
+    }
+
+    #[test]
+    fn grpc_codes_map_to_http_statuses() {
+        let cases = [
+            (Code::InvalidArgument, 400),
+            (Code::OutOfRange, 400),
+            (Code::Unauthenticated, 401),
+            (Code::PermissionDenied, 403),
+            (Code::NotFound, 404),
+            (Code::AlreadyExists, 409),
+            (Code::Aborted, 409),
+            (Code::FailedPrecondition, 409),
+            (Code::ResourceExhausted, 429),
+            (Code::Cancelled, 499),
+            (Code::Unimplemented, 501),
+            (Code::Unavailable, 503),
+            (Code::DeadlineExceeded, 504),
+            (Code::Unknown, 500),
+            (Code::Internal, 500),
+            (Code::DataLoss, 500),
+        ];
+
+        for (code, status) in cases {
+            assert_eq!(status_code_for(code).as_u16(), status, "{code:?}");
+        }
+    }
+
+    #[test]
+    fn internal_messages_are_not_passed_on() {
+        let error = ApiError::from(tonic::Status::internal("relation \"posts\" does not exist"));
+
+        assert_eq!(error.status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(error.detail, "Internal server error");
+    }
+
+    #[test]
+    fn error_info_and_bad_request_details_are_decoded() {
+        let bad_request = rpc::BadRequest {
+            field_violations: vec![rpc::bad_request::FieldViolation {
+                field: "title".to_string(),
+                description: "Title must be 1-50 characters long".to_string(),
+            }],
+        };
+        let status = status_with(
+            Code::InvalidArgument,
+            "Title must be 1-50 characters long",
+            vec![error_info("INVALID_TITLE"), any("BadRequest", &bad_request)],
+        );
+
+        let error = ApiError::from(status);
+
+        assert_eq!(error.status, StatusCode::BAD_REQUEST);
+        assert_eq!(error.reason.as_deref(), Some("INVALID_TITLE"));
+        assert_eq!(error.errors.len(), 1);
+        assert_eq!(error.errors[0].field, "title");
+        assert_eq!(error.errors[0].message, "Title must be 1-50 characters long");
+    }
+
+    #[test]
+    fn a_version_mismatch_is_a_failed_precondition() {
+        let status = status_with(Code::FailedPrecondition, "Post was changed since it was read", vec![error_info(VERSION_MISMATCH)]);
+
+        let error = ApiError::from(status);
+
+        assert_eq!(error.status, StatusCode::PRECONDITION_FAILED);
+        assert_eq!(error.reason.as_deref(), Some(VERSION_MISMATCH));
+    }
+
+    #[test]
+    fn a_status_without_details_keeps_its_message() {
+        let error = ApiError::from(tonic::Status::not_found("Post with this id not found"));
+
+        assert_eq!(error.status, StatusCode::NOT_FOUND);
+        assert_eq!(error.detail, "Post with this id not found");
+        assert!(error.reason.is_none());
+    }
+
+    #[actix_web::test]
+    async fn a_path_segment_that_does_not_parse_is_a_bad_request() {
+        use actix_web::{App, HttpResponse, test::{TestRequest, call_service, init_service}, web};
+
+        let app = init_service(
+            App::new()
+                .app_data(web::PathConfig::default().error_handler(path_error_handler))
+                .route("/posts/{id}", web::get().to(|id: web::Path<i64>| async move { HttpResponse::Ok().body(id.to_string()) })),
+        ).await;
+
+        let response = call_service(&app, TestRequest::get().uri("/posts/abc").to_request()).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = call_service(&app, TestRequest::get().uri("/posts/7").to_request()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[derive(Debug, serde::Deserialize)]
+    struct Body {
+        #[allow(dead_code)]
+        name: String,
+    }
+
+    #[test]
+    fn an_optional_body_may_be_left_out_but_not_malformed() {
+        assert!(optional_json::<Body>(b"").unwrap().is_none());
+        assert!(optional_json::<Body>(b" \n").unwrap().is_none());
+        assert!(optional_json::<Body>(br#"{"name": "a"}"#).unwrap().is_some());
+
+        let err = optional_json::<Body>(b"{").unwrap_err();
+        assert_eq!(err.status, StatusCode::BAD_REQUEST);
+
+        let err = optional_json::<Body>(b"{}").unwrap_err();
+        assert_eq!(err.errors[0].field, "name");
+    }
+
+    #[test]
+    fn errors_that_do_not_name_a_field_have_none() {
+        assert!(field_errors("invalid type: string \"a\", expected u32 at line 1 column 10").is_empty());
+        assert!(field_errors("EOF while parsing a value at line 1 column 0").is_empty());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,405 @@
+use std::{future::Future, pin::Pin, sync::{Arc, Mutex}, task::{Context, Poll}, time::{Duration, Instant}};
+
+use http::HeaderValue;
+use metrics::{counter, histogram};
+use opentelemetry::global;
+use opentelemetry_http::HeaderInjector;
+use tonic::{Code, Status, body::Body, transport::{Channel, Endpoint}};
+use tower::Service;
+use tracing::{Instrument, Span};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use crate::config::GrpcClientConfig;
+use crate::middleware::request_id::{REQUEST_ID_HEADER, current_request_id};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+enum BreakerState {
+    Closed { failures: u32 },
+    Open { until: Instant },
+    // A single trial call is in flight, if it never reports back another one is allowed after `open_duration`.
+    HalfOpen { since: Instant },
+}
+
+pub struct CircuitBreaker {
+    service: &'static str,
+    failure_threshold: u32,
+    open_duration: Duration,
+    state: Mutex<BreakerState>,
+}
+
+impl CircuitBreaker {
+    pub fn new(service: &'static str, config: &GrpcClientConfig) -> Self {
+        Self {
+            service,
+            failure_threshold: config.failure_threshold.max(1),
+            open_duration: config.open_duration,
+            state: Mutex::new(BreakerState::Closed { failures: 0 }),
+        }
+    }
+
+    fn try_acquire(&self) -> bool {
+        let mut state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let now = Instant::now();
+
+        match *state {
+            BreakerState::Closed { .. } => true,
+            BreakerState::Open { until } if now < until => false,
+            BreakerState::HalfOpen { since } if now < since + self.open_duration => false,
+            _ => {
+                *state = BreakerState::HalfOpen { since: now };
+                true
+            },
+        }
+    }
+
+    // Only the trial call closes an open circuit, a late answer to a call made before it opened doesn't.
+    fn on_success(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        match *state {
+            BreakerState::HalfOpen { .. } => {
+                tracing::info!("Circuit for {} closed", self.service);
+                *state = BreakerState::Closed { failures: 0 };
+            },
+            BreakerState::Closed { .. } => *state = BreakerState::Closed { failures: 0 },
+            BreakerState::Open { .. } => {},
+        }
+    }
+
+    fn on_failure(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        let failures = match *state {
+            BreakerState::Closed { failures } => failures + 1,
+            BreakerState::HalfOpen { .. } => self.failure_threshold,
+            // Late failures of calls made before the circuit opened don't extend it.
+            BreakerState::Open { .. } => return,
+        };
+
+        *state = if failures >= self.failure_threshold {
+            tracing::warn!("Circuit for {} open for {}s", self.service, self.open_duration.as_secs());
+            BreakerState::Open { until: Instant::now() + self.open_duration }
+        } else {
+            BreakerState::Closed { failures }
+        };
+    }
+}
+
+// Channel to one downstream service that enforces a per-call deadline and fails fast while its circuit is open.
+// Errors are returned as `Status` so tonic hands them to the caller unchanged.
+#[derive(Clone)]
+pub struct ResilientChannel {
+    inner: Channel,
+    breaker: Arc<CircuitBreaker>,
+    timeout: Duration,
+}
+
+impl ResilientChannel {
+    // Connects lazily and reconnects on its own, so a service that is down at boot only fails its own requests.
+    pub fn new(service: &'static str, url: String, config: &GrpcClientConfig) -> Result<Self, tonic::transport::Error> {
+        let inner = Endpoint::from_shared(url)?
+            .connect_timeout(config.connect_timeout)
+            .connect_lazy();
+
+        Ok(Self {
+            inner,
+            breaker: Arc::new(CircuitBreaker::new(service, config)),
+            timeout: config.request_timeout,
+        })
+    }
+}
+
+impl Service<http::Request<Body>> for ResilientChannel {
+    type Response = http::Response<Body>;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, mut request: http::Request<Body>) -> Self::Future {
+        let breaker = self.breaker.clone();
+        let timeout = self.timeout;
+        let rpc = request.uri().path().to_string();
+
+        let span = tracing::info_span!("grpc_client", service = breaker.service, rpc = %rpc);
+        propagate(&span, request.headers_mut());
+
+        if !breaker.try_acquire() {
+            counter!("grpc_client_rejected_total", "service" => breaker.service, "rpc" => rpc).increment(1);
+            let status = Status::unavailable(format!("{} is unavailable, try again later", breaker.service));
+            return Box::pin(async move { Err(status.into()) });
+        }
+
+        // The ready channel is the one that has to be called, leave a fresh clone behind for the next request.
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+
+        Box::pin(async move {
+            let started = Instant::now();
+
+            let result = match tokio::time::timeout(timeout, inner.call(request)).await {
+                Ok(Ok(response)) => Ok(response),
+                Ok(Err(err)) => {
+                    tracing::warn!("Call to {} failed: {err}", breaker.service);
+                    Err(Status::unavailable(format!("{} is unreachable", breaker.service)))
+                },
+                Err(_) => Err(Status::deadline_exceeded(format!("{} did not respond within {}ms", breaker.service, timeout.as_millis()))),
+            };
+
+            // Errors are sent trailers only, so their status shows up in the headers.
+            let code = match &result {
+                Ok(response) => response.headers().get("grpc-status")
+                    .and_then(|code| code.to_str().ok()?.parse::<i32>().ok())
+                    .map(Code::from_i32)
+                    .unwrap_or(Code::Ok),
+                Err(status) => status.code(),
+            };
+
+            // Only failing to get an answer counts against the circuit. A service that answers with an error,
+            // even `Unavailable` because one of its own dependencies is down, is up.
+            if result.is_err() { breaker.on_failure() } else { breaker.on_success() }
+
+            counter!("grpc_client_requests_total", "service" => breaker.service, "rpc" => rpc.clone(), "code" => format!("{code:?}")).increment(1);
+            histogram!("grpc_client_request_duration_seconds", "service" => breaker.service, "rpc" => rpc).record(started.elapsed().as_secs_f64());
+
+            result.map_err(Into::into)
+        }.instrument(span))
+    }
+}
+
+// Services continue the trace from `traceparent` and tag their logs with the gateway's request id.
+fn propagate(span: &Span, headers: &mut http::HeaderMap) {
+    let context = span.context();
+    global::get_text_map_propagator(|propagator| propagator.inject_context(&context, &mut HeaderInjector(headers)));
+
+    if let Some(value) = current_request_id().and_then(|id| HeaderValue::from_str(&id).ok()) {
+        headers.insert(REQUEST_ID_HEADER, value);
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    attempts: u32,
+    backoff: Duration,
+}
+
+impl RetryPolicy {
+    pub fn new(config: &GrpcClientConfig) -> Self {
+        Self {
+            attempts: config.retry_attempts.max(1),
+            backoff: config.retry_backoff,
+        }
+    }
+
+    // Only for idempotent reads, the call is repeated with exponential backoff while the downstream is unreachable.
+    pub async fn run<T, F, Fut>(&self, mut call: F) -> Result<T, Status>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, Status>>,
+    {
+        let mut attempt = 1;
+
+        loop {
+            match call().await {
+                Err(status) if attempt < self.attempts && matches!(status.code(), Code::Unavailable | Code::DeadlineExceeded) => {
+                    tokio::time::sleep(self.backoff * 2u32.pow(attempt - 1)).await;
+                    attempt += 1;
+                },
+                result => return result,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{App, HttpResponse, middleware::from_fn, test::{TestRequest, call_and_read_body, init_service}, web};
+    use opentelemetry::trace::{TraceContextExt, TracerProvider as _};
+    use opentelemetry_sdk::{propagation::TraceContextPropagator, trace::SdkTracerProvider};
+    use tonic::transport::{Server, server::TcpIncoming};
+    use tonic_health::pb::{HealthCheckRequest, HealthCheckResponse, health_client::HealthClient, health_server::{Health, HealthServer}};
+    use tracing_subscriber::{Registry, layer::SubscriberExt};
+
+    use super::*;
+    use crate::middleware::request_id::trace_request;
+
+    const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+
+    fn config(failure_threshold: u32, open_duration: Duration) -> GrpcClientConfig {
+        GrpcClientConfig {
+            connect_timeout: Duration::from_millis(200),
+            request_timeout: Duration::from_millis(500),
+            retry_attempts: 3,
+            retry_backoff: Duration::from_millis(1),
+            failure_threshold,
+            open_duration,
+        }
+    }
+
+    #[test]
+    fn breaker_opens_after_consecutive_failures() {
+        let breaker = CircuitBreaker::new("posts", &config(2, Duration::from_secs(60)));
+
+        assert!(breaker.try_acquire());
+        breaker.on_failure();
+        breaker.on_success();
+        breaker.on_failure();
+        assert!(breaker.try_acquire(), "a success in between resets the count");
+
+        breaker.on_failure();
+        assert!(!breaker.try_acquire());
+    }
+
+    #[test]
+    fn open_breaker_lets_a_single_trial_call_through() {
+        let breaker = CircuitBreaker::new("posts", &config(1, Duration::from_millis(20)));
+        breaker.on_failure();
+        assert!(!breaker.try_acquire());
+
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(breaker.try_acquire());
+        assert!(!breaker.try_acquire(), "only one trial at a time");
+
+        breaker.on_failure();
+        assert!(!breaker.try_acquire(), "a failed trial opens the circuit again");
+
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(breaker.try_acquire());
+        breaker.on_success();
+        assert!(breaker.try_acquire());
+        assert!(breaker.try_acquire());
+    }
+
+    #[test]
+    fn a_late_success_does_not_close_an_open_breaker() {
+        let breaker = CircuitBreaker::new("posts", &config(1, Duration::from_secs(60)));
+        breaker.on_failure();
+
+        breaker.on_success();
+        assert!(!breaker.try_acquire());
+    }
+
+    // Answers every health check with `Unavailable`, like posts does when users-service is down.
+    struct DependencyDown;
+
+    #[tonic::async_trait]
+    impl Health for DependencyDown {
+        type WatchStream = tonic::codegen::BoxStream<HealthCheckResponse>;
+
+        async fn check(&self, _request: tonic::Request<HealthCheckRequest>) -> Result<tonic::Response<HealthCheckResponse>, Status> {
+            Err(Status::unavailable("users service is unavailable"))
+        }
+
+        async fn watch(&self, _request: tonic::Request<HealthCheckRequest>) -> Result<tonic::Response<Self::WatchStream>, Status> {
+            Err(Status::unimplemented("watch"))
+        }
+    }
+
+    #[tokio::test]
+    async fn unavailable_answers_do_not_open_the_circuit() {
+        let incoming = TcpIncoming::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let url = format!("http://{}", incoming.local_addr().unwrap());
+        tokio::spawn(Server::builder().add_service(HealthServer::new(DependencyDown)).serve_with_incoming(incoming));
+
+        let channel = ResilientChannel::new("posts", url, &config(1, Duration::from_secs(60))).unwrap();
+        let mut client = HealthClient::new(channel);
+
+        for _ in 0..3 {
+            let status = client.check(HealthCheckRequest::default()).await.unwrap_err();
+            assert_eq!(status.message(), "users service is unavailable");
+        }
+    }
+
+    #[tokio::test]
+    async fn unreachable_service_fails_fast_once_its_circuit_opens() {
+        // Nothing listens on port 1.
+        let channel = ResilientChannel::new("posts", "http://127.0.0.1:1".to_string(), &config(1, Duration::from_secs(60))).unwrap();
+        let mut client = HealthClient::new(channel);
+
+        let status = client.check(HealthCheckRequest::default()).await.unwrap_err();
+        assert_eq!(status.code(), Code::Unavailable);
+        assert_eq!(status.message(), "posts is unreachable");
+
+        let status = client.check(HealthCheckRequest::default()).await.unwrap_err();
+        assert_eq!(status.code(), Code::Unavailable);
+        assert_eq!(status.message(), "posts is unavailable, try again later");
+    }
+
+    // Runs a call that fails with each of `failures` in turn and then succeeds, returns how often it ran.
+    async fn run(failures: Vec<Code>) -> (Result<(), Status>, u32) {
+        let policy = RetryPolicy::new(&config(1, Duration::from_secs(60)));
+        let calls = std::cell::Cell::new(0);
+
+        let result = policy.run(|| {
+            let attempt = calls.get();
+            calls.set(attempt + 1);
+            let result = match failures.get(attempt as usize) {
+                Some(code) => Err(Status::new(*code, "failed")),
+                None => Ok(()),
+            };
+            async move { result }
+        }).await;
+
+        (result, calls.get())
+    }
+
+    #[tokio::test]
+    async fn retry_repeats_calls_while_the_service_is_unreachable() {
+        let (result, calls) = run(vec![Code::Unavailable, Code::DeadlineExceeded]).await;
+        assert!(result.is_ok());
+        assert_eq!(calls, 3);
+
+        let (result, calls) = run(vec![Code::Unavailable; 5]).await;
+        assert_eq!(result.unwrap_err().code(), Code::Unavailable);
+        assert_eq!(calls, 3, "gives up after the configured attempts");
+    }
+
+    #[tokio::test]
+    async fn retry_passes_other_errors_straight_through() {
+        let (result, calls) = run(vec![Code::NotFound]).await;
+        assert_eq!(result.unwrap_err().code(), Code::NotFound);
+        assert_eq!(calls, 1);
+    }
+
+    // What a service sees of a call the gateway makes while handling a request.
+    async fn call_service() -> HttpResponse {
+        let mut headers = http::HeaderMap::new();
+        propagate(&tracing::info_span!("grpc_client"), &mut headers);
+
+        let mut request = http::Request::builder().uri("/posts.Posts/GetPost").body(()).unwrap();
+        *request.headers_mut() = headers;
+
+        let span = common::telemetry::request_span(&request);
+        let trace_id = span.context().span().span_context().trace_id();
+        let request_id = request.headers()[REQUEST_ID_HEADER].to_str().unwrap();
+
+        HttpResponse::Ok().body(format!("{trace_id} {request_id}"))
+    }
+
+    #[actix_web::test]
+    async fn services_continue_the_trace_of_the_incoming_request() {
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        let provider = SdkTracerProvider::builder().build();
+        let _subscriber = tracing::subscriber::set_default(
+            Registry::default().with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test"))),
+        );
+
+        let app = init_service(
+            App::new()
+                .wrap(from_fn(trace_request))
+                .route("/", web::get().to(call_service)),
+        ).await;
+
+        let request = TestRequest::get()
+            .uri("/")
+            .insert_header(("traceparent", format!("00-{TRACE_ID}-00f067aa0ba902b7-01")))
+            .insert_header((REQUEST_ID_HEADER, "request-1"))
+            .to_request();
+        let body = call_and_read_body(&app, request).await;
+
+        assert_eq!(body, format!("{TRACE_ID} request-1"));
+    }
+}
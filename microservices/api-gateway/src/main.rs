@@ -1,9 +1,13 @@
-use actix_web::{App, HttpServer, middleware::Logger, web };
+use std::sync::Arc;
 
-use crate::routes::comments::comments_routes;
-use crate::routes::posts::posts_routes;
+use actix_web::{App, HttpServer, middleware::{Logger, from_fn}, web };
+use tokio::signal::unix::{SignalKind, signal};
+
+use crate::error::{json_error_handler, path_error_handler, query_error_handler};
 use crate::{config::Config, state::AppState};
-use crate::routes::{ auth::auth_routes, health::health_routes };
+use crate::middleware::{client_info::TrustedProxies, cors::cors, csrf::TrustedOrigins, security_headers::security_headers};
+use crate::middleware::{metrics::track_requests, rate_limit::{InMemoryStore, RateLimiter, rate_limit}, request_id::trace_request};
+use crate::routes::{api_routes, metrics::metrics_routes};
 
 pub mod state;
 pub mod config;
@@ -11,38 +15,118 @@ pub mod proto;
 pub mod dto;
 pub mod domain;
 pub mod routes;
+pub mod middleware;
+pub mod error;
+pub mod grpc;
+pub mod openapi;
+pub mod tls;
+
+// actix's default format plus the id `trace_request` echoes back.
+const ACCESS_LOG_FORMAT: &str = r#"%a "%r" %s %b "%{Referer}i" "%{User-Agent}i" %T request_id=%{x-request-id}o"#;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     dotenvy::dotenv().ok();
 
-    env_logger::init();
+    let tracer_provider = common::telemetry::init("api-gateway")
+        .map_err(|err| std::io::Error::other(format!("Failed to set up tracing: {err}")))?;
 
-    let config = Config::from_env();
+    let config = Config::from_env()
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("Invalid configuration: {err}")))?;
     let state = AppState::new(&config)
-        .await.map_err(|_| {
-            println!("Application state error");
+        .map_err(|err| {
+            tracing::error!("Application state error: {err}");
             std::io::Error::new(
-                std::io::ErrorKind::ConnectionRefused,
-                "Failed to connect to microservices"
+                std::io::ErrorKind::InvalidInput,
+                "Invalid microservice url"
             )
-        }).unwrap();
+        })?;
+
+    let metrics_handle = middleware::metrics::install()
+        .map_err(|err| std::io::Error::other(format!("Failed to install metrics recorder: {err}")))?;
+
+    let rate_limiter = web::Data::new(RateLimiter::new(Arc::new(InMemoryStore::default()), config.rate_limit));
 
-    HttpServer::new(move || {
+    let trusted_origins = web::Data::new(TrustedOrigins(config.cors.allowed_origins.clone()));
+    let trusted_proxies = web::Data::new(TrustedProxies(config.trusted_proxies.clone()));
+
+    let json_limit = config.json_limit;
+    let payload_limit = config.payload_limit;
+    let hsts_max_age = config.hsts_max_age;
+    let cors_config = config.cors;
+
+    let mut server = HttpServer::new(move || {
         App::new()
-        .wrap(Logger::default())
+        .wrap(from_fn(rate_limit))
+        .wrap(from_fn(track_requests))
+        .wrap(from_fn(trace_request))
+        .wrap(security_headers(hsts_max_age))
+        .wrap(cors(&cors_config))
+        .wrap(Logger::new(ACCESS_LOG_FORMAT))
         .app_data(web::Data::new(state.clone()))
-        .service(
-            web::scope("/api")
-                .service(health_routes())
-                .service(auth_routes())
-                .service(posts_routes())
-                .service(comments_routes())
-        )
+        .app_data(web::Data::new(metrics_handle.clone()))
+        .app_data(rate_limiter.clone())
+        .app_data(trusted_origins.clone())
+        .app_data(trusted_proxies.clone())
+        .app_data(web::JsonConfig::default().limit(json_limit).error_handler(json_error_handler))
+        .app_data(web::PayloadConfig::default().limit(payload_limit))
+        .app_data(web::PathConfig::default().error_handler(path_error_handler))
+        .app_data(web::QueryConfig::default().error_handler(query_error_handler))
+        .configure(metrics_routes)
+        .service(api_routes())
     })
-    .bind(("127.0.0.1", 8080))?
-    .run()
-    .await?;
+    .keep_alive(config.keep_alive)
+    .shutdown_timeout(config.shutdown_timeout.as_secs())
+    // Signals are handled below so SIGTERM and Ctrl-C both drain instead of SIGINT forcing a stop.
+    .disable_signals();
+
+    if let Some(workers) = config.workers {
+        server = server.workers(workers);
+    }
+
+    let server = match &config.tls {
+        Some(tls) => server.bind_rustls_0_23(&config.gateway_url, tls::load_server_config(tls)?)?,
+        None => server.bind(&config.gateway_url)?,
+    };
+
+    tracing::info!(
+        "Api gateway listening on {}://{}",
+        if config.tls.is_some() { "https" } else { "http" },
+        config.gateway_url,
+    );
+
+    let server = server.run();
+    let handle = server.handle();
+    let shutdown_timeout = config.shutdown_timeout;
+
+    actix_web::rt::spawn(async move {
+        shutdown_signal().await;
+        tracing::info!("Shutting down, draining in-flight requests for up to {}s", shutdown_timeout.as_secs());
+        handle.stop(true).await;
+    });
+
+    server.await?;
+
+    // Flushes spans still waiting in the batch exporter.
+    if let Err(err) = tracer_provider.shutdown() {
+        tracing::warn!("Failed to flush traces: {err}");
+    }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+async fn shutdown_signal() {
+    let mut terminate = match signal(SignalKind::terminate()) {
+        Ok(terminate) => terminate,
+        Err(err) => {
+            tracing::warn!("Failed to listen for SIGTERM: {err}");
+            let _ = tokio::signal::ctrl_c().await;
+            return;
+        },
+    };
+
+    tokio::select! {
+        _ = terminate.recv() => {},
+        _ = tokio::signal::ctrl_c() => {},
+    }
+}
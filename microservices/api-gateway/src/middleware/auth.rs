@@ -0,0 +1,250 @@
+use std::{future::Future, pin::Pin};
+
+use actix_web::{FromRequest, HttpMessage, HttpRequest, dev::Payload, http::header, web};
+use uuid::Uuid;
+
+use crate::error::ApiError;
+use crate::{proto::auth, state::AppState};
+
+// Caller resolved from `Authorization: Bearer <access token | api key>`.
+#[derive(Debug, Clone)]
+pub struct AuthenticatedUser {
+    pub user_id: Uuid,
+    pub username: String,
+    pub scopes: Vec<String>,
+    pub is_api_key: bool,
+    pub session_id: Option<Uuid>,
+}
+
+impl AuthenticatedUser {
+    // Access tokens carry every scope, api keys only the ones they were created with.
+    pub fn has_scope(&self, scope: &str) -> bool {
+        !self.is_api_key || self.scopes.iter().any(|s| s == scope)
+    }
+
+    pub fn require_scope(&self, scope: &str) -> Result<(), ApiError> {
+        if !self.has_scope(scope) {
+            return Err(ApiError::forbidden(format!("Api key is missing the `{scope}` scope")));
+        }
+
+        Ok(())
+    }
+
+    // Anonymous callers pass, signed in ones need the scope like on any other route.
+    pub fn require_optional_scope(user: Option<&Self>, scope: &str) -> Result<(), ApiError> {
+        user.map_or(Ok(()), |user| user.require_scope(scope))
+    }
+
+    pub fn require_session(&self) -> Result<(), ApiError> {
+        if self.is_api_key {
+            return Err(ApiError::forbidden("This action requires signing in"));
+        }
+
+        Ok(())
+    }
+}
+
+pub fn bearer_token(req: &HttpRequest) -> Option<String> {
+    req.headers()
+        .get(header::AUTHORIZATION)?
+        .to_str().ok()?
+        .strip_prefix("Bearer ")
+        .map(|token| token.trim().to_string())
+        .filter(|token| !token.is_empty())
+}
+
+pub async fn authenticate(state: &AppState, token: String) -> Result<AuthenticatedUser, ApiError> {
+    let mut client = state.auth_client.clone();
+
+    let response = client
+        .authenticate(tonic::Request::new(auth::AuthenticateRequest { token }))
+        .await?
+        .into_inner();
+
+    Ok(AuthenticatedUser {
+        user_id: Uuid::parse_str(&response.user_id)
+            .map_err(|_| ApiError::unauthorized("Invalid credentials"))?,
+        username: response.username,
+        scopes: response.scopes,
+        is_api_key: response.is_api_key,
+        session_id: Uuid::parse_str(&response.session_id).ok(),
+    })
+}
+
+impl FromRequest for AuthenticatedUser {
+    type Error = ApiError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        // Already resolved by the rate limiter, no need to ask the auth service twice.
+        let cached = req.extensions().get::<AuthenticatedUser>().cloned();
+        let state = req.app_data::<web::Data<AppState>>().cloned();
+        let token = bearer_token(req);
+
+        Box::pin(async move {
+            if let Some(user) = cached {
+                return Ok(user);
+            }
+
+            let state = state
+                .ok_or_else(ApiError::internal)?;
+            let token = token
+                .ok_or_else(|| ApiError::unauthorized("Missing bearer token"))?;
+
+            authenticate(&state, token).await
+        })
+    }
+}
+
+// Caller of an endpoint anonymous callers can use too. Only a request without an `Authorization`
+// header is anonymous. A token that doesn't check out, or one that can't be checked because the
+// auth service is down, fails the request instead of quietly showing the anonymous view.
+#[derive(Debug, Clone)]
+pub struct OptionalUser(pub Option<AuthenticatedUser>);
+
+impl FromRequest for OptionalUser {
+    type Error = ApiError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        if !req.headers().contains_key(header::AUTHORIZATION) {
+            return Box::pin(async { Ok(OptionalUser(None)) });
+        }
+
+        let user = AuthenticatedUser::from_request(req, payload);
+
+        Box::pin(async move { Ok(OptionalUser(Some(user.await?))) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::StatusCode;
+    use actix_web::test::{TestRequest, call_service, init_service};
+    use actix_web::{App, HttpResponse};
+    use tonic::{Status, service::InterceptorLayer, transport::{Server, server::TcpIncoming}};
+
+    use super::*;
+
+    fn api_key(scopes: &[&str]) -> AuthenticatedUser {
+        AuthenticatedUser {
+            user_id: Uuid::new_v4(),
+            username: "bot".to_string(),
+            scopes: scopes.iter().map(|s| s.to_string()).collect(),
+            is_api_key: true,
+            session_id: None,
+        }
+    }
+
+    fn signed_in() -> AuthenticatedUser {
+        AuthenticatedUser {
+            is_api_key: false,
+            session_id: Some(Uuid::new_v4()),
+            ..api_key(&[])
+        }
+    }
+
+    #[test]
+    fn access_tokens_have_every_scope() {
+        let user = signed_in();
+
+        assert!(user.require_scope("posts:write").is_ok());
+        assert!(user.require_scope("comments:write").is_ok());
+    }
+
+    #[test]
+    fn api_keys_only_have_their_scopes() {
+        let user = api_key(&["posts:read"]);
+
+        assert!(user.require_scope("posts:read").is_ok());
+
+        let err = user.require_scope("posts:write").unwrap_err();
+        assert_eq!(err.status, StatusCode::FORBIDDEN);
+    }
+
+    #[test]
+    fn optional_scope_lets_anonymous_callers_through() {
+        assert!(AuthenticatedUser::require_optional_scope(None, "comments:read").is_ok());
+    }
+
+    #[test]
+    fn optional_scope_checks_signed_in_callers() {
+        let user = api_key(&["posts:read"]);
+
+        assert!(AuthenticatedUser::require_optional_scope(Some(&user), "posts:read").is_ok());
+        assert!(AuthenticatedUser::require_optional_scope(Some(&user), "comments:read").is_err());
+    }
+
+    #[test]
+    fn api_keys_have_no_session() {
+        assert!(signed_in().require_session().is_ok());
+        assert!(api_key(&["posts:write"]).require_session().is_err());
+    }
+
+    async fn caller(user: OptionalUser) -> HttpResponse {
+        match user.0 {
+            Some(user) => HttpResponse::Ok().body(user.username),
+            None => HttpResponse::Ok().body("anonymous"),
+        }
+    }
+
+    async fn call(auth_url: &str, authorization: Option<&str>) -> (StatusCode, String) {
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(AppState::for_tests(auth_url)))
+                .route("/", web::get().to(caller)),
+        )
+        .await;
+
+        let mut request = TestRequest::get().uri("/");
+        if let Some(authorization) = authorization {
+            request = request.insert_header((header::AUTHORIZATION, authorization));
+        }
+
+        let response = call_service(&app, request.to_request()).await;
+        let status = response.status();
+        let body = actix_web::body::to_bytes(response.into_body()).await.unwrap();
+
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    // Nothing listens on port 1, as if auth-service were down.
+    const AUTH_DOWN: &str = "http://127.0.0.1:1";
+
+    #[actix_web::test]
+    async fn no_authorization_header_is_anonymous() {
+        assert_eq!(call(AUTH_DOWN, None).await, (StatusCode::OK, "anonymous".to_string()));
+    }
+
+    #[actix_web::test]
+    async fn a_rejected_token_is_not_read_as_anonymous() {
+        let incoming = TcpIncoming::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let url = format!("http://{}", incoming.local_addr().unwrap());
+        let reject = InterceptorLayer::new(|_request| Err(Status::unauthenticated("Invalid credentials")));
+        let (_, health) = tonic_health::server::health_reporter();
+        tokio::spawn(Server::builder().layer(reject).add_service(health).serve_with_incoming(incoming));
+
+        assert_eq!(call(&url, Some("Bearer expired")).await.0, StatusCode::UNAUTHORIZED);
+        assert_eq!(call(&url, Some("Basic dXNlcjpwYXNz")).await.0, StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn a_token_that_cant_be_checked_fails_the_request() {
+        assert_eq!(call(AUTH_DOWN, Some("Bearer token")).await.0, StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[actix_web::test]
+    async fn a_user_resolved_by_the_rate_limiter_is_reused() {
+        let app = init_service(App::new().route("/", web::get().to(caller))).await;
+
+        let request = TestRequest::get()
+            .uri("/")
+            .insert_header((header::AUTHORIZATION, "Bearer token"))
+            .to_request();
+        request.extensions_mut().insert(signed_in());
+
+        let response = call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(actix_web::body::to_bytes(response.into_body()).await.unwrap(), "bot");
+    }
+}
@@ -0,0 +1,220 @@
+use std::{future::{Ready, ready}, net::{IpAddr, SocketAddr}};
+
+use actix_web::{FromRequest, HttpRequest, dev::Payload, http::header, web};
+use tonic::metadata::MetadataValue;
+
+// Metadata keys the downstream services read the end client details from.
+pub const CLIENT_IP_KEY: &str = "x-client-ip";
+pub const CLIENT_USER_AGENT_KEY: &str = "x-client-user-agent";
+pub const CLIENT_COUNTRY_KEY: &str = "x-client-country";
+
+// Country of the client as resolved by the CDN in front of the gateway, only believed from a trusted proxy.
+const COUNTRY_HEADER: &str = "cf-ipcountry";
+
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+
+// Proxies in front of the gateway, only their `Forwarded` / `X-Forwarded-For` headers are believed.
+// Anyone else could put any address there and dodge per ip rate limits.
+#[derive(Debug, Clone, Default)]
+pub struct TrustedProxies(pub Vec<IpAddr>);
+
+impl TrustedProxies {
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        self.0.contains(ip)
+    }
+
+    // Walks the forwarded chain from the nearest hop back. The first address that isn't a trusted
+    // proxy is the client, hops before it could have been made up by the client itself.
+    pub fn client_ip(&self, peer: IpAddr, hops: &[String]) -> IpAddr {
+        let mut client = peer;
+
+        for hop in hops.iter().rev() {
+            if !self.contains(&client) {
+                break;
+            }
+
+            match parse_hop(hop) {
+                Some(ip) => client = ip,
+                None => break,
+            }
+        }
+
+        client
+    }
+}
+
+// `for` addresses of `Forwarded`, or else `X-Forwarded-For`, oldest hop first.
+fn forwarded_hops(req: &HttpRequest) -> Vec<String> {
+    let values = |name| {
+        req.headers()
+            .get_all(name)
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .collect::<Vec<_>>()
+    };
+
+    let forwarded: Vec<String> = values(header::FORWARDED.as_str())
+        .into_iter()
+        .filter_map(|element| {
+            element.split(';').find_map(|pair| {
+                let (key, value) = pair.split_once('=')?;
+                key.trim().eq_ignore_ascii_case("for").then(|| value.trim().trim_matches('"').to_string())
+            })
+        })
+        .collect();
+
+    if !forwarded.is_empty() {
+        return forwarded;
+    }
+
+    values(X_FORWARDED_FOR).into_iter().map(str::to_string).collect()
+}
+
+// `203.0.113.7`, `203.0.113.7:4711`, `2001:db8::1` or `[2001:db8::1]:4711`.
+fn parse_hop(hop: &str) -> Option<IpAddr> {
+    hop.parse::<IpAddr>().ok()
+        .or_else(|| hop.parse::<SocketAddr>().ok().map(|addr| addr.ip()))
+        .or_else(|| hop.strip_prefix('[')?.split(']').next()?.parse().ok())
+}
+
+// Details about the end client that are forwarded to the microservices as gRPC metadata.
+#[derive(Debug, Clone, Default)]
+pub struct ClientInfo {
+    pub ip_address: String,
+    pub user_agent: String,
+    pub country: String,
+}
+
+impl ClientInfo {
+    pub fn from_request(req: &HttpRequest) -> Self {
+        let peer = req.peer_addr().map(|peer| peer.ip());
+        let trusted = req.app_data::<web::Data<TrustedProxies>>();
+
+        let from_proxy = matches!((peer, trusted), (Some(peer), Some(trusted)) if trusted.contains(&peer));
+
+        let ip_address = match (peer, trusted) {
+            (Some(peer), Some(trusted)) if from_proxy => trusted.client_ip(peer, &forwarded_hops(req)).to_string(),
+            (Some(peer), _) => peer.to_string(),
+            (None, _) => String::new(),
+        };
+
+        let header_value = |name| {
+            req.headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default()
+                .to_string()
+        };
+
+        let user_agent = header_value(header::USER_AGENT.as_str());
+        // Clients that reach the gateway directly could claim any country and dodge new country alerts.
+        let country = if from_proxy { header_value(COUNTRY_HEADER) } else { String::new() };
+
+        Self { ip_address, user_agent, country }
+    }
+
+    pub fn into_request<T>(self, message: T) -> tonic::Request<T> {
+        let mut request = tonic::Request::new(message);
+
+        if let Ok(value) = MetadataValue::try_from(self.ip_address) {
+            request.metadata_mut().insert(CLIENT_IP_KEY, value);
+        }
+        if let Ok(value) = MetadataValue::try_from(self.user_agent) {
+            request.metadata_mut().insert(CLIENT_USER_AGENT_KEY, value);
+        }
+        if let Ok(value) = MetadataValue::try_from(self.country) {
+            request.metadata_mut().insert(CLIENT_COUNTRY_KEY, value);
+        }
+
+        request
+    }
+}
+
+impl FromRequest for ClientInfo {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(Ok(ClientInfo::from_request(req)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::TestRequest;
+
+    use super::*;
+
+    const PROXY: &str = "10.0.0.1:443";
+
+    fn request(peer: &str, header: Option<(&str, &str)>) -> HttpRequest {
+        let mut request = TestRequest::default()
+            .peer_addr(peer.parse().unwrap())
+            .app_data(web::Data::new(TrustedProxies(vec!["10.0.0.1".parse().unwrap(), "10.0.0.2".parse().unwrap()])));
+
+        if let Some(header) = header {
+            request = request.insert_header(header);
+        }
+
+        request.to_http_request()
+    }
+
+    #[test]
+    fn untrusted_peers_cant_pick_their_address() {
+        let req = request("198.51.100.9:5000", Some(("x-forwarded-for", "203.0.113.7")));
+
+        assert_eq!(ClientInfo::from_request(&req).ip_address, "198.51.100.9");
+
+        let req = request("198.51.100.9:5000", Some(("forwarded", "for=203.0.113.7")));
+
+        assert_eq!(ClientInfo::from_request(&req).ip_address, "198.51.100.9");
+    }
+
+    #[test]
+    fn trusted_proxies_name_the_client() {
+        let req = request(PROXY, Some(("x-forwarded-for", "203.0.113.7")));
+
+        assert_eq!(ClientInfo::from_request(&req).ip_address, "203.0.113.7");
+
+        let req = request(PROXY, Some(("forwarded", r#"for="[2001:db8::7]:4711";proto=https"#)));
+
+        assert_eq!(ClientInfo::from_request(&req).ip_address, "2001:db8::7");
+    }
+
+    #[test]
+    fn hops_before_the_first_untrusted_one_are_ignored() {
+        // The client made up 192.0.2.1, the proxies appended what they actually saw.
+        let req = request(PROXY, Some(("x-forwarded-for", "192.0.2.1, 203.0.113.7, 10.0.0.2")));
+
+        assert_eq!(ClientInfo::from_request(&req).ip_address, "203.0.113.7");
+    }
+
+    #[test]
+    fn unparsable_hops_stop_at_the_last_known_address() {
+        let req = request(PROXY, Some(("forwarded", "for=unknown")));
+
+        assert_eq!(ClientInfo::from_request(&req).ip_address, "10.0.0.1");
+    }
+
+    #[test]
+    fn country_is_only_taken_from_trusted_proxies() {
+        let req = request(PROXY, Some(("cf-ipcountry", "NL")));
+
+        assert_eq!(ClientInfo::from_request(&req).country, "NL");
+
+        let req = request("198.51.100.9:5000", Some(("cf-ipcountry", "NL")));
+
+        assert_eq!(ClientInfo::from_request(&req).country, "");
+    }
+
+    #[test]
+    fn without_trusted_proxies_the_peer_is_the_client() {
+        let req = TestRequest::default()
+            .peer_addr("10.0.0.1:443".parse().unwrap())
+            .insert_header(("x-forwarded-for", "203.0.113.7"))
+            .to_http_request();
+
+        assert_eq!(ClientInfo::from_request(&req).ip_address, "10.0.0.1");
+    }
+}
@@ -0,0 +1,116 @@
+use actix_cors::Cors;
+
+use crate::config::CorsConfig;
+
+// Response headers the SPA needs to read.
+const EXPOSED_HEADERS: &[&str] = &[
+    "x-request-id",
+    "ratelimit-limit",
+    "ratelimit-remaining",
+    "ratelimit-reset",
+    "ratelimit-policy",
+    "retry-after",
+    "etag",
+];
+
+// Credentials are allowed so a trusted origin can use the refresh token cookie, which is why
+// origins must be listed one by one instead of `*`.
+pub fn cors(config: &CorsConfig) -> Cors {
+    let mut cors = Cors::default()
+        .allowed_methods(config.allowed_methods.iter().map(String::as_str))
+        .allowed_headers(config.allowed_headers.iter().map(String::as_str))
+        .expose_headers(EXPOSED_HEADERS.iter().copied())
+        .supports_credentials()
+        .max_age(config.max_age);
+
+    for origin in &config.allowed_origins {
+        if origin == "*" {
+            tracing::warn!("Ignoring `*` in CORS_ALLOWED_ORIGINS, list the origins explicitly");
+            continue;
+        }
+
+        cors = cors.allowed_origin(origin);
+    }
+
+    cors
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::{Method, StatusCode, header};
+    use actix_web::test::{TestRequest, call_service, init_service};
+    use actix_web::{App, HttpResponse, web};
+
+    use super::*;
+
+    fn config() -> CorsConfig {
+        CorsConfig {
+            allowed_origins: vec!["https://app.example.com".to_string(), "*".to_string()],
+            allowed_methods: vec!["GET".to_string(), "POST".to_string()],
+            allowed_headers: vec!["authorization".to_string(), "content-type".to_string()],
+            max_age: 600,
+        }
+    }
+
+    macro_rules! app {
+        () => {
+            init_service(
+                App::new()
+                    .wrap(cors(&config()))
+                    .route("/api/posts", web::get().to(|| async { HttpResponse::Ok().finish() })),
+            )
+            .await
+        };
+    }
+
+    #[actix_web::test]
+    async fn an_allowed_origin_gets_the_cors_headers() {
+        let app = app!();
+
+        let request = TestRequest::get()
+            .uri("/api/posts")
+            .insert_header((header::ORIGIN, "https://app.example.com"))
+            .to_request();
+        let response = call_service(&app, request).await;
+        let headers = response.headers();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(headers.get(header::ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(), "https://app.example.com");
+        assert_eq!(headers.get(header::ACCESS_CONTROL_ALLOW_CREDENTIALS).unwrap(), "true");
+
+        let exposed = headers.get(header::ACCESS_CONTROL_EXPOSE_HEADERS).unwrap().to_str().unwrap();
+        assert!(exposed.contains("x-request-id") && exposed.contains("ratelimit-remaining"));
+    }
+
+    #[actix_web::test]
+    async fn a_preflight_from_an_allowed_origin_is_answered() {
+        let app = app!();
+
+        let request = TestRequest::default()
+            .method(Method::OPTIONS)
+            .uri("/api/posts")
+            .insert_header((header::ORIGIN, "https://app.example.com"))
+            .insert_header((header::ACCESS_CONTROL_REQUEST_METHOD, "POST"))
+            .insert_header((header::ACCESS_CONTROL_REQUEST_HEADERS, "authorization"))
+            .to_request();
+        let response = call_service(&app, request).await;
+        let headers = response.headers();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(headers.get(header::ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(), "https://app.example.com");
+        assert_eq!(headers.get(header::ACCESS_CONTROL_MAX_AGE).unwrap(), "600");
+    }
+
+    #[actix_web::test]
+    async fn other_origins_get_no_cors_headers_even_with_a_wildcard_configured() {
+        let app = app!();
+
+        let request = TestRequest::get()
+            .uri("/api/posts")
+            .insert_header((header::ORIGIN, "https://evil.example"))
+            .to_request();
+        let response = call_service(&app, request).await;
+
+        assert!(response.headers().get(header::ACCESS_CONTROL_ALLOW_ORIGIN).is_none());
+    }
+}
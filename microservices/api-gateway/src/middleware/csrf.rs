@@ -0,0 +1,115 @@
+use std::future::{Ready, ready};
+
+use actix_web::{FromRequest, HttpRequest, dev::Payload, http::header, web};
+
+use crate::error::ApiError;
+
+// Origins besides the gateway's own that may call cookie authenticated endpoints, the CORS allow list.
+#[derive(Debug, Clone, Default)]
+pub struct TrustedOrigins(pub Vec<String>);
+
+// Guard for endpoints authenticated by a cookie instead of a bearer token, such as refresh.
+// Browsers send `Origin` (or at least `Referer`) on cross site POSTs, so a request naming an
+// untrusted origin is rejected. Requests without either come from non browser clients, which
+// can't be tricked into sending someone else's cookie.
+#[derive(Debug, Clone, Copy)]
+pub struct SameOrigin;
+
+// `https://app.example.com/some/page` -> `https://app.example.com`
+fn origin_of(url: &str) -> Option<&str> {
+    let host_start = url.find("://")? + 3;
+    let end = url[host_start..].find('/').map_or(url.len(), |i| host_start + i);
+
+    Some(&url[..end])
+}
+
+fn check(req: &HttpRequest) -> Result<SameOrigin, ApiError> {
+    let header_value = |name| req.headers().get(name).and_then(|value| value.to_str().ok());
+
+    let origin = match header_value(header::ORIGIN) {
+        Some(origin) => Some(origin),
+        None => header_value(header::REFERER).and_then(origin_of),
+    };
+
+    let Some(origin) = origin else {
+        return Ok(SameOrigin);
+    };
+
+    let own_origin = {
+        let info = req.connection_info();
+        format!("{}://{}", info.scheme(), info.host())
+    };
+
+    let trusted = origin == own_origin
+        || req.app_data::<web::Data<TrustedOrigins>>()
+            .is_some_and(|trusted| trusted.0.iter().any(|allowed| allowed == origin));
+
+    if !trusted {
+        return Err(ApiError::forbidden("Cross site request rejected"));
+    }
+
+    Ok(SameOrigin)
+}
+
+impl FromRequest for SameOrigin {
+    type Error = ApiError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(check(req))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::StatusCode;
+    use actix_web::test::{TestRequest, call_service, init_service};
+    use actix_web::{App, HttpResponse};
+
+    use super::*;
+
+    async fn status(request: TestRequest) -> StatusCode {
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(TrustedOrigins(vec!["https://app.example.com".to_string()])))
+                .route("/api/auth/refresh", web::post().to(|_: SameOrigin| async { HttpResponse::Ok().finish() })),
+        )
+        .await;
+
+        let request = request.uri("/api/auth/refresh").insert_header((header::HOST, "api.example.com"));
+        call_service(&app, request.to_request()).await.status()
+    }
+
+    #[test]
+    fn origin_is_taken_from_a_referer() {
+        assert_eq!(origin_of("https://app.example.com/some/page"), Some("https://app.example.com"));
+        assert_eq!(origin_of("https://app.example.com"), Some("https://app.example.com"));
+        assert_eq!(origin_of("not a url"), None);
+    }
+
+    #[actix_web::test]
+    async fn a_cross_site_post_is_rejected() {
+        let request = TestRequest::post().insert_header((header::ORIGIN, "https://evil.example"));
+        assert_eq!(status(request).await, StatusCode::FORBIDDEN);
+
+        let request = TestRequest::post().insert_header((header::REFERER, "https://evil.example/page"));
+        assert_eq!(status(request).await, StatusCode::FORBIDDEN);
+    }
+
+    #[actix_web::test]
+    async fn own_and_trusted_origins_pass() {
+        let request = TestRequest::post().insert_header((header::ORIGIN, "http://api.example.com"));
+        assert_eq!(status(request).await, StatusCode::OK);
+
+        let request = TestRequest::post().insert_header((header::ORIGIN, "https://app.example.com"));
+        assert_eq!(status(request).await, StatusCode::OK);
+
+        let request = TestRequest::post().insert_header((header::REFERER, "https://app.example.com/feed"));
+        assert_eq!(status(request).await, StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn requests_without_an_origin_come_from_non_browser_clients() {
+        assert_eq!(status(TestRequest::post()).await, StatusCode::OK);
+    }
+}
@@ -0,0 +1,49 @@
+use std::time::{Duration, Instant};
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use metrics::{counter, histogram};
+use metrics_exporter_prometheus::{BuildError, PrometheusBuilder, PrometheusHandle};
+
+const LATENCY_BUCKETS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0];
+const UPKEEP_INTERVAL: Duration = Duration::from_secs(5);
+
+// The gateway serves `/metrics` itself, so nothing drains the histograms unless upkeep runs.
+pub fn install() -> Result<PrometheusHandle, BuildError> {
+    let handle = PrometheusBuilder::new()
+        .add_global_label("service", "api-gateway")
+        .set_buckets(LATENCY_BUCKETS)?
+        .install_recorder()?;
+
+    let upkeep = handle.clone();
+    actix_web::rt::spawn(async move {
+        let mut interval = actix_web::rt::time::interval(UPKEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            upkeep.run_upkeep();
+        }
+    });
+
+    Ok(handle)
+}
+
+// Labels use the route pattern, e.g. `/api/posts/{id}`, anything unmatched shares one label
+// so random paths can't blow up the number of series.
+pub async fn track_requests(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let method = req.method().to_string();
+    let started = Instant::now();
+
+    let response = next.call(req).await?;
+
+    let route = response.request().match_pattern().unwrap_or_else(|| "unmatched".to_string());
+    let status = response.status().as_u16().to_string();
+
+    counter!("http_requests_total", "method" => method.clone(), "route" => route.clone(), "status" => status).increment(1);
+    histogram!("http_request_duration_seconds", "method" => method, "route" => route).record(started.elapsed().as_secs_f64());
+
+    Ok(response)
+}
@@ -0,0 +1,8 @@
+pub mod auth;
+pub mod client_info;
+pub mod metrics;
+pub mod request_id;
+pub mod rate_limit;
+pub mod cors;
+pub mod csrf;
+pub mod security_headers;
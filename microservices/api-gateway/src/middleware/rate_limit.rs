@@ -0,0 +1,370 @@
+use std::{collections::HashMap, sync::{Arc, Mutex}, time::{Duration, Instant}};
+
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue, RETRY_AFTER};
+use actix_web::http::{Method, StatusCode};
+use actix_web::middleware::Next;
+use actix_web::{HttpMessage, ResponseError, web};
+
+use crate::config::{RateLimitConfig, RateLimitPolicy};
+use crate::error::ApiError;
+use crate::middleware::auth::{authenticate, bearer_token};
+use crate::middleware::client_info::ClientInfo;
+use crate::state::AppState;
+
+// Stores stop tracking more clients than this by dropping buckets that have refilled.
+const SWEEP_THRESHOLD: usize = 10_000;
+
+// Unauthenticated auth endpoints, the ones worth brute forcing.
+const AUTH_PATHS: &[&str] = &[
+    "/api/auth/sign-up",
+    "/api/auth/sign-in",
+    "/api/auth/refresh",
+    "/api/auth/magic-link",
+    "/api/auth/magic-link/consume",
+];
+
+const UNLIMITED_PATHS: &[&str] = &["/metrics", "/api/openapi.json", "/api/docs"];
+
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimitDecision {
+    pub allowed: bool,
+    pub limit: u32,
+    pub remaining: u32,
+    // Until the bucket is full again.
+    pub reset: Duration,
+    // Until the next request would be allowed, zero when this one was.
+    pub retry_after: Duration,
+}
+
+// Where the token buckets live. The in-memory store is per gateway instance, a shared
+// backend is needed for limits to hold across replicas.
+#[tonic::async_trait]
+pub trait RateLimitStore: Send + Sync {
+    async fn acquire(&self, key: &str, policy: &RateLimitPolicy) -> RateLimitDecision;
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    full_at: Instant,
+}
+
+#[derive(Default)]
+pub struct InMemoryStore {
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+#[tonic::async_trait]
+impl RateLimitStore for InMemoryStore {
+    async fn acquire(&self, key: &str, policy: &RateLimitPolicy) -> RateLimitDecision {
+        let capacity = f64::from(policy.requests);
+        let refill_per_sec = capacity / policy.window.as_secs_f64();
+        let now = Instant::now();
+
+        let mut buckets = self.buckets.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        if buckets.len() >= SWEEP_THRESHOLD {
+            buckets.retain(|_, bucket| bucket.full_at > now);
+        }
+
+        let bucket = buckets.entry(key.to_string()).or_insert(Bucket {
+            tokens: capacity,
+            updated: now,
+            full_at: now,
+        });
+
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * refill_per_sec).min(capacity);
+        bucket.updated = now;
+
+        let allowed = bucket.tokens >= 1.0;
+        if allowed {
+            bucket.tokens -= 1.0;
+        }
+
+        let reset = Duration::from_secs_f64((capacity - bucket.tokens) / refill_per_sec);
+        bucket.full_at = now + reset;
+
+        RateLimitDecision {
+            allowed,
+            limit: policy.requests,
+            remaining: bucket.tokens.floor() as u32,
+            reset,
+            retry_after: if allowed {
+                Duration::ZERO
+            } else {
+                Duration::from_secs_f64((1.0 - bucket.tokens) / refill_per_sec)
+            },
+        }
+    }
+}
+
+pub struct RateLimiter {
+    store: Arc<dyn RateLimitStore>,
+    config: RateLimitConfig,
+}
+
+impl RateLimiter {
+    pub fn new(store: Arc<dyn RateLimitStore>, config: RateLimitConfig) -> Self {
+        Self { store, config }
+    }
+
+    fn policy_for(&self, method: &Method, path: &str) -> Option<(&'static str, RateLimitPolicy)> {
+        if !self.config.enabled
+            || *method == Method::OPTIONS
+            || path.starts_with("/api/health")
+            || UNLIMITED_PATHS.contains(&path)
+        {
+            return None;
+        }
+
+        if AUTH_PATHS.contains(&path) || path.starts_with("/api/auth/oidc/") {
+            return Some(("auth", self.config.auth));
+        }
+
+        if *method == Method::GET || *method == Method::HEAD {
+            Some(("read", self.config.read))
+        } else {
+            Some(("write", self.config.write))
+        }
+    }
+}
+
+// Every caller is charged to its ip before the token is looked at, so a flood of made up tokens or
+// api keys is cut off here instead of reaching the auth service. Signed in callers are then also
+// charged to their own bucket wherever they connect from.
+fn ip_key(req: &ServiceRequest) -> String {
+    format!("ip:{}", ClientInfo::from_request(req.request()).ip_address)
+}
+
+// The resolved user is kept on the request so the `AuthenticatedUser` extractor doesn't ask again.
+async fn user_key(req: &ServiceRequest) -> Option<String> {
+    let state = req.app_data::<web::Data<AppState>>()?;
+    let user = authenticate(state, bearer_token(req.request())?).await.ok()?;
+
+    let key = format!("user:{}", user.user_id);
+    req.extensions_mut().insert(user);
+    Some(key)
+}
+
+// A denial wins, otherwise the bucket closest to running out is the one worth reporting.
+fn tighter(a: RateLimitDecision, b: RateLimitDecision) -> RateLimitDecision {
+    match (a.allowed, b.allowed) {
+        (false, true) => a,
+        (true, false) => b,
+        _ if a.remaining <= b.remaining => a,
+        _ => b,
+    }
+}
+
+fn seconds_header(duration: Duration) -> HeaderValue {
+    HeaderValue::from(duration.as_secs_f64().ceil() as u64)
+}
+
+// Headers from the IETF `RateLimit` header fields draft.
+fn insert_headers(headers: &mut HeaderMap, policy: &RateLimitPolicy, decision: &RateLimitDecision) {
+    headers.insert(HeaderName::from_static("ratelimit-limit"), HeaderValue::from(decision.limit));
+    headers.insert(HeaderName::from_static("ratelimit-remaining"), HeaderValue::from(decision.remaining));
+    headers.insert(HeaderName::from_static("ratelimit-reset"), seconds_header(decision.reset));
+
+    if let Ok(value) = HeaderValue::from_str(&format!("{};w={}", policy.requests, policy.window.as_secs())) {
+        headers.insert(HeaderName::from_static("ratelimit-policy"), value);
+    }
+}
+
+pub async fn rate_limit(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, actix_web::Error> {
+    let limiter = req.app_data::<web::Data<RateLimiter>>().cloned();
+    let policy = limiter.as_ref().and_then(|limiter| limiter.policy_for(req.method(), req.path()));
+
+    let (Some(limiter), Some((name, policy))) = (limiter, policy) else {
+        return next.call(req).await.map(ServiceResponse::map_into_left_body);
+    };
+
+    let mut decision = limiter.store.acquire(&format!("{name}:{}", ip_key(&req)), &policy).await;
+
+    if decision.allowed
+        && let Some(key) = user_key(&req).await
+    {
+        decision = tighter(decision, limiter.store.acquire(&format!("{name}:{key}"), &policy).await);
+    }
+
+    if !decision.allowed {
+        let mut error = ApiError::new(StatusCode::TOO_MANY_REQUESTS, "Too many requests, try again later");
+        error.reason = Some("RATE_LIMITED".to_string());
+
+        let mut response = error.error_response();
+        insert_headers(response.headers_mut(), &policy, &decision);
+        response.headers_mut().insert(RETRY_AFTER, seconds_header(decision.retry_after));
+
+        return Ok(req.into_response(response).map_into_right_body());
+    }
+
+    let mut response = next.call(req).await?;
+    insert_headers(response.headers_mut(), &policy, &decision);
+
+    Ok(response.map_into_left_body())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use actix_web::test::{TestRequest, call_service, init_service};
+    use actix_web::{App, HttpResponse, http::header::AUTHORIZATION, middleware::from_fn};
+    use tonic::{Status, service::InterceptorLayer, transport::{Server, server::TcpIncoming}};
+
+    use super::*;
+
+    fn policy(requests: u32, window_secs: u64) -> RateLimitPolicy {
+        RateLimitPolicy { requests, window: Duration::from_secs(window_secs) }
+    }
+
+    fn limiter(enabled: bool) -> RateLimiter {
+        RateLimiter::new(Arc::new(InMemoryStore::default()), RateLimitConfig {
+            enabled,
+            auth: policy(10, 60),
+            write: policy(30, 60),
+            read: policy(300, 60),
+        })
+    }
+
+    #[tokio::test]
+    async fn bucket_allows_a_burst_up_to_its_capacity() {
+        let store = InMemoryStore::default();
+        let policy = policy(3, 60);
+
+        for remaining in [2, 1, 0] {
+            let decision = store.acquire("ip:203.0.113.7", &policy).await;
+            assert!(decision.allowed);
+            assert_eq!(decision.remaining, remaining);
+            assert_eq!(decision.retry_after, Duration::ZERO);
+        }
+
+        let decision = store.acquire("ip:203.0.113.7", &policy).await;
+        assert!(!decision.allowed);
+        assert_eq!(decision.limit, 3);
+        // One token comes back every 20 seconds.
+        assert!(decision.retry_after > Duration::from_secs(19) && decision.retry_after <= Duration::from_secs(20));
+        assert!(decision.reset <= Duration::from_secs(60));
+    }
+
+    #[tokio::test]
+    async fn buckets_are_per_key() {
+        let store = InMemoryStore::default();
+        let policy = policy(1, 60);
+
+        assert!(store.acquire("ip:203.0.113.7", &policy).await.allowed);
+        assert!(!store.acquire("ip:203.0.113.7", &policy).await.allowed);
+        assert!(store.acquire("ip:203.0.113.8", &policy).await.allowed);
+    }
+
+    #[tokio::test]
+    async fn bucket_refills_over_time() {
+        let store = InMemoryStore::default();
+        let policy = RateLimitPolicy { requests: 10, window: Duration::from_secs(1) };
+
+        for _ in 0..10 {
+            assert!(store.acquire("user:1", &policy).await.allowed);
+        }
+        assert!(!store.acquire("user:1", &policy).await.allowed);
+
+        tokio::time::sleep(Duration::from_millis(250)).await;
+
+        assert!(store.acquire("user:1", &policy).await.allowed);
+    }
+
+    #[test]
+    fn policy_depends_on_path_and_method() {
+        let limiter = limiter(true);
+        let name = |method, path| limiter.policy_for(&method, path).map(|(name, _)| name);
+
+        assert_eq!(name(Method::POST, "/api/auth/sign-in"), Some("auth"));
+        assert_eq!(name(Method::GET, "/api/auth/oidc/google/callback"), Some("auth"));
+        assert_eq!(name(Method::GET, "/api/posts"), Some("read"));
+        assert_eq!(name(Method::DELETE, "/api/posts/1"), Some("write"));
+        assert_eq!(name(Method::GET, "/api/health/ready"), None);
+        assert_eq!(name(Method::GET, "/metrics"), None);
+        assert_eq!(name(Method::OPTIONS, "/api/posts"), None);
+    }
+
+    #[test]
+    fn disabled_limiter_has_no_policies() {
+        assert!(limiter(false).policy_for(&Method::POST, "/api/auth/sign-in").is_none());
+    }
+
+    // Every service behind a server that counts the calls it gets and turns them all down.
+    async fn counting_state() -> (AppState, Arc<AtomicUsize>) {
+        let incoming = TcpIncoming::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let url = format!("http://{}", incoming.local_addr().unwrap());
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        let counter = calls.clone();
+        let count = InterceptorLayer::new(move |_request| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Err(Status::unauthenticated("Invalid credentials"))
+        });
+        let (_, health) = tonic_health::server::health_reporter();
+        tokio::spawn(Server::builder().layer(count).add_service(health).serve_with_incoming(incoming));
+
+        (AppState::for_tests(&url), calls)
+    }
+
+    #[actix_web::test]
+    async fn an_exhausted_ip_is_refused_before_its_token_is_checked() {
+        let (state, calls) = counting_state().await;
+        let limiter = RateLimiter::new(Arc::new(InMemoryStore::default()), RateLimitConfig {
+            enabled: true,
+            auth: policy(10, 60),
+            write: policy(1, 60),
+            read: policy(300, 60),
+        });
+
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .app_data(web::Data::new(limiter))
+                .wrap(from_fn(rate_limit))
+                .route("/api/posts", web::post().to(HttpResponse::Created)),
+        )
+        .await;
+
+        let request = || {
+            TestRequest::post()
+                .uri("/api/posts")
+                .peer_addr("203.0.113.7:4000".parse().unwrap())
+                .insert_header((AUTHORIZATION, "Bearer made-up-token"))
+                .to_request()
+        };
+
+        // Under the limit the token is looked up, and being made up it gets no bucket of its own.
+        assert_eq!(call_service(&app, request()).await.status(), StatusCode::CREATED);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        for _ in 0..5 {
+            let response = call_service(&app, request()).await;
+            assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn the_tighter_decision_is_reported() {
+        let decision = |allowed, remaining| RateLimitDecision {
+            allowed,
+            limit: 10,
+            remaining,
+            reset: Duration::ZERO,
+            retry_after: Duration::ZERO,
+        };
+
+        assert_eq!(tighter(decision(true, 5), decision(true, 2)).remaining, 2);
+        assert_eq!(tighter(decision(true, 1), decision(true, 2)).remaining, 1);
+        assert!(!tighter(decision(true, 0), decision(false, 3)).allowed);
+        assert!(!tighter(decision(false, 3), decision(true, 0)).allowed);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,63 @@
+use actix_web::middleware::DefaultHeaders;
+
+// Only fills in headers a handler didn't set, the docs page brings its own CSP.
+pub fn security_headers(hsts_max_age: u64) -> DefaultHeaders {
+    let headers = DefaultHeaders::new()
+        .add(("X-Content-Type-Options", "nosniff"))
+        .add(("X-Frame-Options", "DENY"))
+        .add(("Referrer-Policy", "no-referrer"))
+        .add(("Content-Security-Policy", "default-src 'none'; frame-ancestors 'none'"));
+
+    if hsts_max_age == 0 {
+        return headers;
+    }
+
+    headers.add(("Strict-Transport-Security", format!("max-age={hsts_max_age}; includeSubDomains")))
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::header::{self, HeaderValue};
+    use actix_web::test::{TestRequest, call_service, init_service};
+    use actix_web::{App, HttpResponse, web};
+
+    use super::*;
+
+    #[actix_web::test]
+    async fn responses_carry_the_security_headers() {
+        let app = init_service(
+            App::new()
+                .wrap(security_headers(31_536_000))
+                .route("/api/posts", web::get().to(|| async { HttpResponse::Ok().finish() })),
+        )
+        .await;
+
+        let response = call_service(&app, TestRequest::get().uri("/api/posts").to_request()).await;
+        let headers = response.headers();
+
+        assert_eq!(headers.get(header::X_CONTENT_TYPE_OPTIONS).unwrap(), "nosniff");
+        assert_eq!(headers.get(header::X_FRAME_OPTIONS).unwrap(), "DENY");
+        assert_eq!(headers.get(header::REFERRER_POLICY).unwrap(), "no-referrer");
+        assert_eq!(headers.get(header::CONTENT_SECURITY_POLICY).unwrap(), "default-src 'none'; frame-ancestors 'none'");
+        assert_eq!(headers.get(header::STRICT_TRANSPORT_SECURITY).unwrap(), "max-age=31536000; includeSubDomains");
+    }
+
+    #[actix_web::test]
+    async fn a_handler_keeps_its_own_csp_and_hsts_can_be_left_out() {
+        let app = init_service(
+            App::new()
+                .wrap(security_headers(0))
+                .route("/api/docs", web::get().to(|| async {
+                    HttpResponse::Ok().insert_header((header::CONTENT_SECURITY_POLICY, "default-src 'self'")).finish()
+                })),
+        )
+        .await;
+
+        let response = call_service(&app, TestRequest::get().uri("/api/docs").to_request()).await;
+        let headers = response.headers();
+
+        assert_eq!(headers.get(header::CONTENT_SECURITY_POLICY), Some(&HeaderValue::from_static("default-src 'self'")));
+        assert!(headers.get(header::STRICT_TRANSPORT_SECURITY).is_none());
+        assert_eq!(headers.get(header::X_FRAME_OPTIONS).unwrap(), "DENY");
+    }
+}
@@ -0,0 +1,137 @@
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::openapi::{ContentBuilder, Ref, ResponseBuilder};
+use utoipa::{Modify, OpenApi};
+
+use crate::error::{FieldError, PROBLEM_JSON, ProblemDetails};
+use crate::routes::{api_keys::ApiKeysApi, auth::AuthApi, comments::CommentsApi, health::HealthApi, oidc::OidcApi, posts::PostsApi, search::SearchApi, security_events::SecurityEventsApi, sessions::SessionsApi, tags::TagsApi, users::UsersApi};
+
+// Every route module documents its own handlers, this only mounts them where `main` does.
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Social API", description = "Public HTTP API of the api gateway."),
+    nest(
+        (path = "/api/health", api = HealthApi),
+        (path = "/api/auth", api = AuthApi),
+        (path = "/api/auth/api-keys", api = ApiKeysApi),
+        (path = "/api/auth/sessions", api = SessionsApi),
+        (path = "/api/auth/security-events", api = SecurityEventsApi),
+        (path = "/api/auth/oidc", api = OidcApi),
+        (path = "/api/posts", api = PostsApi),
+        (path = "/api/comments", api = CommentsApi),
+        (path = "/api/users", api = UsersApi),
+        (path = "/api/tags", api = TagsApi),
+        (path = "/api/search", api = SearchApi),
+    ),
+    components(schemas(ProblemDetails, FieldError)),
+    modifiers(&SecuritySchemes, &ProblemResponses),
+)]
+pub struct ApiDoc;
+
+struct SecuritySchemes;
+
+impl Modify for SecuritySchemes {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+
+        components.add_security_scheme(
+            "bearer_token",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .description(Some("Access token from sign in, or an api key"))
+                    .build(),
+            ),
+        );
+    }
+}
+
+// Any route can fail with a problem+json body, documenting it once beats repeating it on every handler.
+struct ProblemResponses;
+
+impl Modify for ProblemResponses {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let problem = ResponseBuilder::new()
+            .description("Error described as RFC 7807 problem details")
+            .content(
+                PROBLEM_JSON,
+                ContentBuilder::new()
+                    .schema(Some(Ref::from_schema_name("ProblemDetails")))
+                    .build(),
+            )
+            .build();
+
+        for item in openapi.paths.paths.values_mut() {
+            let operations = [
+                &mut item.get,
+                &mut item.post,
+                &mut item.put,
+                &mut item.patch,
+                &mut item.delete,
+            ];
+
+            for operation in operations.into_iter().flatten() {
+                operation.responses.responses
+                    .entry("default".to_string())
+                    .or_insert_with(|| problem.clone().into());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use actix_web::dev::ResourceMap;
+    use actix_web::{App, test};
+
+    use super::*;
+    use crate::routes::{api_routes, metrics::metrics_routes};
+
+    // Served on purpose without being part of the document.
+    const UNDOCUMENTED: [&str; 3] = ["metrics", "openapi_json", "docs_redirect"];
+
+    // actix has no API to list its resources, but its debug output names every handler the route macros registered.
+    fn handler_names(map: &ResourceMap) -> BTreeSet<String> {
+        format!("{map:?}")
+            .split("name: Some(\"")
+            .skip(1)
+            .filter_map(|rest| rest.split('"').next())
+            .filter(|name| !UNDOCUMENTED.contains(name))
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[actix_web::test]
+    async fn every_route_is_documented_where_it_is_served() {
+        let app = test::init_service(App::new().configure(metrics_routes).service(api_routes())).await;
+        let response = test::call_service(&app, test::TestRequest::get().uri("/unknown").to_request()).await;
+        let map = response.request().resource_map();
+
+        let mut documented = BTreeSet::new();
+
+        for (path, item) in &ApiDoc::openapi().paths.paths {
+            let concrete = path.split('/')
+                .map(|segment| if segment.starts_with('{') { "x" } else { segment })
+                .collect::<Vec<_>>()
+                .join("/");
+            assert_eq!(map.match_pattern(&concrete).as_deref(), Some(path.as_str()), "{path} is documented but not served there");
+
+            let operations = [&item.get, &item.post, &item.put, &item.patch, &item.delete];
+            documented.extend(operations.into_iter().flatten().filter_map(|operation| operation.operation_id.clone()));
+        }
+
+        let registered = handler_names(map);
+        assert!(!registered.is_empty());
+        assert_eq!(
+            registered.difference(&documented).collect::<Vec<_>>(),
+            Vec::<&String>::new(),
+            "routes missing from the OpenAPI document",
+        );
+        assert_eq!(
+            documented.difference(&registered).collect::<Vec<_>>(),
+            Vec::<&String>::new(),
+            "documented operations without a route",
+        );
+    }
+}
@@ -9,4 +9,9 @@ pub mod posts {
 }
 pub mod comments {
     tonic::include_proto!("comments");
+}
+pub mod google {
+    pub mod rpc {
+        tonic::include_proto!("google.rpc");
+    }
 }
\ No newline at end of file
@@ -0,0 +1,131 @@
+use actix_web::{HttpResponse, Result, Scope, delete, get, post, web};
+use tonic::Status;
+use utoipa::OpenApi;
+
+use crate::error::ApiError;
+use crate::dto::api_keys_dto::{CreateApiKeyRequest, CreateApiKeyResponse, ListApiKeysResponse, RevokeApiKeyRequest, RevokeApiKeyResponse};
+use crate::middleware::auth::AuthenticatedUser;
+use crate::middleware::client_info::ClientInfo;
+use crate::{proto::auth, state::AppState};
+
+pub fn api_keys_routes() -> Scope {
+    web::scope("/api-keys")
+        .service(create_api_key)
+        .service(list_api_keys)
+        .service(revoke_api_key)
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(create_api_key, list_api_keys, revoke_api_key))]
+pub struct ApiKeysApi;
+
+#[utoipa::path(
+    tag = "api-keys",
+    summary = "Create an api key, the key is only returned once",
+    request_body = CreateApiKeyRequest,
+    responses((status = 201, description = "Created", body = CreateApiKeyResponse)),
+    security(("bearer_token" = [])),
+)]
+#[post("")]
+async fn create_api_key(
+    state: web::Data<AppState>,
+    user: AuthenticatedUser,
+    client_info: ClientInfo,
+    body: web::Json<CreateApiKeyRequest>,
+) -> Result<HttpResponse, ApiError> {
+    user.require_session()?;
+
+    let mut client = state.auth_client.clone();
+
+    let body = body.into_inner();
+
+    let request = auth::CreateApiKeyRequest {
+        user_id: user.user_id.to_string(),
+        name: body.name,
+        scopes: body.scopes,
+    };
+
+    let response = client
+        .create_api_key(client_info.into_request(request))
+        .await?
+        .into_inner();
+
+    let api_key = response.api_key
+        .ok_or_else(|| ApiError::missing_field("api_key"))?;
+
+    let http_response = CreateApiKeyResponse {
+        api_key: api_key.try_into()?,
+        key: response.key,
+    };
+
+    Ok(HttpResponse::Created().json(http_response))
+}
+
+#[utoipa::path(
+    tag = "api-keys",
+    summary = "List api keys of the caller",
+    responses((status = 200, description = "OK", body = ListApiKeysResponse)),
+    security(("bearer_token" = [])),
+)]
+#[get("")]
+async fn list_api_keys(
+    state: web::Data<AppState>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, ApiError> {
+    user.require_session()?;
+
+    let mut client = state.auth_client.clone();
+
+    let request = auth::ListApiKeysRequest {
+        user_id: user.user_id.to_string(),
+    };
+
+    let response = client
+        .list_api_keys(tonic::Request::new(request))
+        .await?
+        .into_inner().api_keys;
+
+    let http_response = ListApiKeysResponse {
+        api_keys: response.into_iter().map(|k| k.try_into()).collect::<Result<_, Status>>()?
+    };
+
+    Ok(HttpResponse::Ok().json(http_response))
+}
+
+#[utoipa::path(
+    tag = "api-keys",
+    summary = "Revoke an api key",
+    params(RevokeApiKeyRequest),
+    responses((status = 200, description = "OK", body = RevokeApiKeyResponse)),
+    security(("bearer_token" = [])),
+)]
+#[delete("/{id}")]
+async fn revoke_api_key(
+    state: web::Data<AppState>,
+    user: AuthenticatedUser,
+    client_info: ClientInfo,
+    id: web::Path<RevokeApiKeyRequest>,
+) -> Result<HttpResponse, ApiError> {
+    user.require_session()?;
+
+    let mut client = state.auth_client.clone();
+
+    let request = auth::RevokeApiKeyRequest {
+        id: id.into_inner().id.to_string(),
+        user_id: user.user_id.to_string(),
+    };
+
+    let response = client
+        .revoke_api_key(client_info.into_request(request))
+        .await?
+        .into_inner();
+
+    let api_key = response.api_key
+        .ok_or_else(|| ApiError::missing_field("api_key"))?;
+
+    let http_response = RevokeApiKeyResponse {
+        api_key: api_key.try_into()?,
+    };
+
+    Ok(HttpResponse::Ok().json(http_response))
+}
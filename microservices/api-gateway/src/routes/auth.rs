@@ -1,20 +1,56 @@
-use actix_web::{HttpResponse, Result, Scope, post, web};
-use tonic::Code;
+use actix_web::cookie::{Cookie, SameSite, time::Duration};
+use actix_web::{HttpRequest, HttpResponse, Result, Scope, post, web};
+use utoipa::OpenApi;
 
+use crate::error::{ApiError, optional_json};
+use crate::middleware::{client_info::ClientInfo, csrf::SameOrigin};
+use crate::routes::{api_keys::api_keys_routes, oidc::oidc_routes, security_events::security_events_routes, sessions::sessions_routes};
 use crate::{proto::auth, state::AppState};
-use crate::dto::auth_dto::{ User, SignUpRequest, SignUpResponse, SignInRequest, SignInResponse };
+use crate::dto::auth_dto::{ User, SignUpRequest, SignUpResponse, SignInRequest, SignInResponse, RefreshRequest, RefreshResponse, RequestMagicLinkRequest, ConsumeMagicLinkRequest };
+
+pub const REFRESH_TOKEN_COOKIE: &str = "refresh_token";
 
 pub fn auth_routes() -> Scope {
     web::scope("/auth")
         .service(sign_up)
         .service(sign_in)
+        .service(refresh)
+        .service(request_magic_link)
+        .service(consume_magic_link)
+        .service(api_keys_routes())
+        .service(sessions_routes())
+        .service(security_events_routes())
+        .service(oidc_routes())
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(sign_up, sign_in, refresh, request_magic_link, consume_magic_link))]
+pub struct AuthApi;
+
+// Browsers keep the refresh token in an http-only cookie scoped to `/api/auth`, it's in the
+// response body as well for clients that don't keep cookies.
+pub fn refresh_token_cookie(token: String) -> Cookie<'static> {
+    Cookie::build(REFRESH_TOKEN_COOKIE, token)
+        .path("/api/auth")
+        .http_only(true)
+        .secure(true)
+        .same_site(SameSite::Strict)
+        .max_age(Duration::days(30))
+        .finish()
 }
 
+#[utoipa::path(
+    tag = "auth",
+    summary = "Create an account and sign in, sets the refresh token cookie",
+    request_body = SignUpRequest,
+    responses((status = 200, description = "OK", body = SignUpResponse)),
+)]
 #[post("/sign-up")]
 async fn sign_up(
     state: web::Data<AppState>,
+    client_info: ClientInfo,
     payload: web::Json<SignUpRequest>
-) -> Result<HttpResponse> {
+) -> Result<HttpResponse, ApiError> {
     let mut client = state.auth_client.clone();
 
     let request = auth::SignUpRequest {
@@ -24,40 +60,41 @@ async fn sign_up(
     };
 
     let response = client
-        .sign_up(tonic::Request::new(request))
-        .await
-        .map_err(|error| {
-            match error.code() {
-                Code::AlreadyExists => actix_web::error::ErrorConflict(error.message().to_string()),
-                _ => actix_web::error::ErrorInternalServerError(error.message().to_string()),
-            }
-        })?
+        .sign_up(client_info.into_request(request))
+        .await?
         .into_inner();
 
+    let user = response.user
+        .ok_or_else(|| ApiError::missing_field("user"))?;
+
     let http_response = SignUpResponse {
         user: User {
-            id: response.user.as_ref()
-                .map(|u| u.id.clone())
-                .unwrap_or_default(),
-            username: response.user.as_ref()
-                .map(|u| u.username.clone())
-                .unwrap_or_default(),
-            email: response.user.as_ref()
-                .map(|u| u.email.clone())
-                .unwrap_or_default(),
+            id: user.id,
+            username: user.username,
+            email: user.email,
         },
         access_token: response.access_token,
+        refresh_token: response.refresh_token.clone(),
     };
 
-    Ok(HttpResponse::Ok().json(http_response))
+    Ok(HttpResponse::Ok()
+        .cookie(refresh_token_cookie(response.refresh_token))
+        .json(http_response))
 }
 
 
+#[utoipa::path(
+    tag = "auth",
+    summary = "Sign in with email and password, sets the refresh token cookie",
+    request_body = SignInRequest,
+    responses((status = 200, description = "OK", body = SignInResponse)),
+)]
 #[post("/sign-in")]
 async fn sign_in(
     state: web::Data<AppState>,
+    client_info: ClientInfo,
     data: web::Json<SignInRequest>,
-) -> Result<HttpResponse> {
+) -> Result<HttpResponse, ApiError> {
     let mut client = state.auth_client.clone();
 
     let request = auth::SignInRequest {
@@ -66,32 +103,131 @@ async fn sign_in(
     };
 
     let response = client
-        .sign_in(tonic::Request::new(request))
-        .await
-        .map_err(|error| {
-            match error.code() {
-                Code::Unauthenticated => {
-                    actix_web::error::ErrorUnauthorized(error.message().to_string())
-                },
-                _ => actix_web::error::ErrorInternalServerError(error.message().to_string())
-            }
-        })?
+        .sign_in(client_info.into_request(request))
+        .await?
         .into_inner();
-    
+
+    let user = response.user
+        .ok_or_else(|| ApiError::missing_field("user"))?;
+
     let http_response = SignInResponse {
         user: User {
-            id: response.user.as_ref()
-                .map(|u| u.id.clone())
-                .unwrap_or_default(),
-            username: response.user.as_ref()
-                .map(|u| u.username.clone())
-                .unwrap_or_default(),
-            email: response.user.as_ref()
-                .map(|u| u.email.clone())
-                .unwrap_or_default(),
+            id: user.id,
+            username: user.username,
+            email: user.email,
         },
         access_token: response.access_token,
+        refresh_token: response.refresh_token.clone(),
+    };
+
+    Ok(HttpResponse::Ok()
+        .cookie(refresh_token_cookie(response.refresh_token))
+        .json(http_response))
+}
+
+#[utoipa::path(
+    tag = "auth",
+    summary = "Rotate the refresh token and issue a new access token",
+    description = "Takes the refresh token from the body, or from the cookie when there's no body.",
+    request_body(content = Option<RefreshRequest>),
+    responses((status = 200, description = "OK", body = RefreshResponse)),
+)]
+#[post("/refresh")]
+async fn refresh(
+    state: web::Data<AppState>,
+    client_info: ClientInfo,
+    _same_origin: SameOrigin,
+    req: HttpRequest,
+    body: web::Bytes,
+) -> Result<HttpResponse, ApiError> {
+    let mut client = state.auth_client.clone();
+
+    let refresh_token = match optional_json::<RefreshRequest>(&body)? {
+        Some(body) => body.refresh_token,
+        None => req.cookie(REFRESH_TOKEN_COOKIE)
+            .map(|cookie| cookie.value().to_string())
+            .ok_or_else(|| ApiError::unauthorized("Missing refresh token"))?,
+    };
+
+    let request = auth::RefreshRequest { refresh_token };
+
+    let response = client
+        .refresh(client_info.into_request(request))
+        .await?
+        .into_inner();
+
+    let http_response = RefreshResponse {
+        access_token: response.access_token,
+        refresh_token: response.refresh_token.clone(),
     };
 
-    Ok(HttpResponse::Ok().json(http_response))
-}
\ No newline at end of file
+    Ok(HttpResponse::Ok()
+        .cookie(refresh_token_cookie(response.refresh_token))
+        .json(http_response))
+}
+
+#[utoipa::path(
+    tag = "auth",
+    summary = "Email a single use sign in link",
+    request_body = RequestMagicLinkRequest,
+    responses((status = 202, description = "Accepted")),
+)]
+#[post("/magic-link")]
+async fn request_magic_link(
+    state: web::Data<AppState>,
+    client_info: ClientInfo,
+    body: web::Json<RequestMagicLinkRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let mut client = state.auth_client.clone();
+
+    let request = auth::RequestMagicLinkRequest {
+        email: body.into_inner().email,
+    };
+
+    client
+        .request_magic_link(client_info.into_request(request))
+        .await?;
+
+    Ok(HttpResponse::Accepted().finish())
+}
+
+#[utoipa::path(
+    tag = "auth",
+    summary = "Sign in with a magic link token, sets the refresh token cookie",
+    request_body = ConsumeMagicLinkRequest,
+    responses((status = 200, description = "OK", body = SignInResponse)),
+)]
+#[post("/magic-link/consume")]
+async fn consume_magic_link(
+    state: web::Data<AppState>,
+    client_info: ClientInfo,
+    body: web::Json<ConsumeMagicLinkRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let mut client = state.auth_client.clone();
+
+    let request = auth::ConsumeMagicLinkRequest {
+        token: body.into_inner().token,
+    };
+
+    let response = client
+        .consume_magic_link(client_info.into_request(request))
+        .await?
+        .into_inner();
+
+    let user = response.user
+        .ok_or_else(|| ApiError::missing_field("user"))?;
+
+    let http_response = SignInResponse {
+        user: User {
+            id: user.id,
+            username: user.username,
+            email: user.email,
+        },
+        access_token: response.access_token,
+        refresh_token: response.refresh_token.clone(),
+    };
+
+    Ok(HttpResponse::Ok()
+        .cookie(refresh_token_cookie(response.refresh_token))
+        .json(http_response))
+}
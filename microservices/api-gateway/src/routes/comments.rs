@@ -1,6 +1,11 @@
-use actix_web::{HttpResponse, Result, Scope, delete, get, patch, post, web::{self, ReqData}};
+use actix_web::{HttpRequest, HttpResponse, Result, Scope, delete, get, patch, post, web};
+use tonic::Status;
+use utoipa::OpenApi;
 
-use crate::{dto::comments_dto::{AddCommentRequest, AddCommentResponse, DeleteCommentRequest, DeleteCommentResponse, GetCommentRequest, GetCommentResponse, GetCommentsRequest, GetCommentsResponse, UpdateCommentRequest, UpdateCommentResponse}, proto::comments, state::AppState};
+use crate::domain::etag::{etag, if_match_versions};
+use crate::error::ApiError;
+use crate::middleware::auth::{AuthenticatedUser, OptionalUser};
+use crate::{dto::comments_dto::{AddCommentRequest, AddCommentResponse, DeleteCommentRequest, DeleteCommentResponse, GetCommentRequest, GetCommentResponse, GetCommentsRequest, GetCommentsResponse, ListCommentRevisionsRequest, ListCommentRevisionsResponse, RestoreCommentRequest, RestoreCommentResponse, UpdateCommentRequest, UpdateCommentResponse}, proto::comments, state::AppState};
 
 pub fn comments_routes() -> Scope {
     web::scope("/comments")
@@ -9,152 +14,263 @@ pub fn comments_routes() -> Scope {
         .service(add_comment)
         .service(update_comment)
         .service(delete_comment)
+        .service(restore_comment)
+        .service(list_comment_revisions)
 }
 
+#[derive(OpenApi)]
+#[openapi(paths(get_comment, get_comments, add_comment, update_comment, delete_comment, restore_comment, list_comment_revisions))]
+pub struct CommentsApi;
+
+#[utoipa::path(
+    tag = "comments",
+    summary = "Get a comment",
+    description = "Only found when the caller can see the post it's under.",
+    params(GetCommentRequest),
+    responses((
+        status = 200, description = "OK", body = GetCommentResponse,
+        headers(("ETag" = String, description = "Version of the comment, for If-Match on updates")),
+    )),
+    security((), ("bearer_token" = [])),
+)]
 #[get("/{id}")]
 async fn get_comment(
     state: web::Data<AppState>,
+    OptionalUser(user): OptionalUser,
     id: web::Path<GetCommentRequest>,
-) -> Result<HttpResponse> {
+) -> Result<HttpResponse, ApiError> {
+    AuthenticatedUser::require_optional_scope(user.as_ref(), "comments:read")?;
 
     let mut client = state.comments_client.clone();
 
-    let request = id.into_inner();
-
-    let request = comments::GetCommentRequest::from(request);
+    let mut request = comments::GetCommentRequest::from(id.into_inner());
+    request.requester_id = user.map(|user| user.user_id.to_string()).unwrap_or_default();
 
     let response = client
         .get_comment(tonic::Request::new(request))
-        .await
-        .map_err(|error| {
-            match error.code() {
-                _ => actix_web::error::ErrorInternalServerError(error.message().to_string()),
-            }
-        })?
-        .into_inner().comment.unwrap();
+        .await?
+        .into_inner().comment
+        .ok_or_else(|| ApiError::missing_field("comment"))?;
+
+    let version = response.version;
 
     let http_response = GetCommentResponse {
-        comment: response.try_into()
-            .map_err(|_| actix_web::error::ErrorInternalServerError("Internal server error"))?
+        comment: response.try_into()?
     };
 
-    Ok(HttpResponse::Ok().json(http_response))
+    Ok(HttpResponse::Ok().insert_header(etag(version)).json(http_response))
 }
 
+#[utoipa::path(
+    tag = "comments",
+    summary = "List comments of a post",
+    description = "Only listed when the caller can see the post.",
+    request_body = GetCommentsRequest,
+    responses((status = 200, description = "OK", body = GetCommentsResponse)),
+    security((), ("bearer_token" = [])),
+)]
 #[get("")]
 async fn get_comments(
     state: web::Data<AppState>,
+    OptionalUser(user): OptionalUser,
     body: web::Json<GetCommentsRequest>,
-) -> Result<HttpResponse> {
-    println!("GETTING COMMENTS");
-
-    let mut client = state.comments_client.clone();
+) -> Result<HttpResponse, ApiError> {
+    AuthenticatedUser::require_optional_scope(user.as_ref(), "comments:read")?;
 
-    let request = body.into_inner();
+    tracing::debug!("Getting comments");
 
-    let request = comments::GetCommentsRequest::from(request);
+    let mut request = comments::GetCommentsRequest::from(body.into_inner());
+    request.requester_id = user.map(|user| user.user_id.to_string()).unwrap_or_default();
 
-    let response = client
-        .get_comments(tonic::Request::new(request))
-        .await
-        .map_err(|error| {
-            match error.code() {
-                _ => actix_web::error::ErrorInternalServerError(error.message().to_string()),
-            }
-        })?
+    let response = state.retry
+        .run(|| {
+            let mut client = state.comments_client.clone();
+            let request = request.clone();
+            async move { client.get_comments(tonic::Request::new(request)).await }
+        })
+        .await?
         .into_inner().comments;
 
     let http_response = GetCommentsResponse {
-        comments: response.into_iter().map(|c| c.try_into()).collect::<Result<_, _>>()
-        .map_err(|_| actix_web::error::ErrorInternalServerError("Internal server error"))?
+        comments: response.into_iter().map(|c| c.try_into()).collect::<Result<_, Status>>()?
     };
 
     Ok(HttpResponse::Ok().json(http_response))
 }
 
+#[utoipa::path(
+    tag = "comments",
+    summary = "Add a comment to a post",
+    request_body = AddCommentRequest,
+    responses((status = 200, description = "OK", body = AddCommentResponse)),
+    security(("bearer_token" = [])),
+)]
 #[post("")]
 async fn add_comment(
     state: web::Data<AppState>,
+    user: AuthenticatedUser,
     body: web::Json<AddCommentRequest>,
-) -> Result<HttpResponse> {
-    let mut client = state.comments_client.clone();
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope("comments:write")?;
 
-    let request = body.into_inner();
+    let mut client = state.comments_client.clone();
 
-    let request = comments::AddCommentRequest::from(request);
+    let request = body.into_inner().into_proto(user.user_id);
 
     let response = client
         .add_comment(tonic::Request::new(request))
-        .await
-        .map_err(|error| {
-            match error.code() {
-                _ => actix_web::error::ErrorInternalServerError(error.message().to_string()),
-            }
-        })?
-        .into_inner().comment.unwrap();
+        .await?
+        .into_inner().comment
+        .ok_or_else(|| ApiError::missing_field("comment"))?;
 
     let http_response = AddCommentResponse {
-        comment: response.try_into()
-            .map_err(|_| actix_web::error::ErrorInternalServerError("Internal server error"))?
+        comment: response.try_into()?
     };
 
     Ok(HttpResponse::Ok().json(http_response))
 }
 
+#[utoipa::path(
+    tag = "comments",
+    summary = "Update a comment",
+    description = "With If-Match, the update is rejected with 412 when the comment changed since that ETag was read.",
+    request_body = UpdateCommentRequest,
+    params(("If-Match" = Option<String>, Header, description = "ETag from a previous read")),
+    responses(
+        (
+            status = 200, description = "OK", body = UpdateCommentResponse,
+            headers(("ETag" = String, description = "Version of the updated comment")),
+        ),
+        (status = 412, description = "The comment was changed since the If-Match version"),
+    ),
+    security(("bearer_token" = [])),
+)]
 #[patch("")]
 async fn update_comment(
     state: web::Data<AppState>,
+    user: AuthenticatedUser,
+    req: HttpRequest,
     body: web::Json<UpdateCommentRequest>,
-) -> Result<HttpResponse> {
-    let mut client = state.comments_client.clone();
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope("comments:write")?;
 
-    let request = body.into_inner();
+    let mut client = state.comments_client.clone();
 
-    let request = comments::UpdateCommentRequest::from(request);
+    let mut request = body.into_inner().into_proto(user.user_id);
+    request.expected_versions = if_match_versions(&req)?;
 
     let response = client
         .update_comment(tonic::Request::new(request))
-        .await
-        .map_err(|error| {
-            match error.code() {
-                _ => actix_web::error::ErrorInternalServerError(error.message().to_string()),
-            }
-        })?
-        .into_inner().comment.unwrap();
+        .await?
+        .into_inner().comment
+        .ok_or_else(|| ApiError::missing_field("comment"))?;
+
+    let version = response.version;
 
     let http_response = UpdateCommentResponse {
-        comment: response.try_into()
-            .map_err(|_| actix_web::error::ErrorInternalServerError("Internal server error"))?
+        comment: response.try_into()?
     };
 
-    Ok(HttpResponse::Ok().json(http_response))
+    Ok(HttpResponse::Ok().insert_header(etag(version)).json(http_response))
 }
 
+#[utoipa::path(
+    tag = "comments",
+    summary = "Delete a comment",
+    params(DeleteCommentRequest),
+    responses((status = 200, description = "OK", body = DeleteCommentResponse)),
+    security(("bearer_token" = [])),
+)]
 #[delete("/{id}")]
 async fn delete_comment(
     state: web::Data<AppState>,
+    user: AuthenticatedUser,
     id: web::Path<DeleteCommentRequest>,
-) -> Result<HttpResponse> {
-    let mut client = state.comments_client.clone();
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope("comments:write")?;
 
-    let request = id.into_inner();
+    let mut client = state.comments_client.clone();
 
-    let request = comments::DeleteCommentRequest::from(request);
+    let request = id.into_inner().into_proto(user.user_id);
 
     let response = client
         .delete_comment(tonic::Request::new(request))
-        .await
-        .map_err(|error| {
-            match error.code() {
-                _ => actix_web::error::ErrorInternalServerError(error.message().to_string()),
-            }
-        })?
-        .into_inner().comment.unwrap();
+        .await?
+        .into_inner().comment
+        .ok_or_else(|| ApiError::missing_field("comment"))?;
 
     let http_response = DeleteCommentResponse {
-        comment: response.try_into()
-            .map_err(|_| actix_web::error::ErrorInternalServerError("Internal server error"))?
+        comment: response.try_into()?
     };
 
     Ok(HttpResponse::Ok().json(http_response))
-}
\ No newline at end of file
+}
+
+#[utoipa::path(
+    tag = "comments",
+    summary = "Restore a deleted comment",
+    params(RestoreCommentRequest),
+    responses((status = 200, description = "OK", body = RestoreCommentResponse)),
+    security(("bearer_token" = [])),
+)]
+#[post("/{id}/restore")]
+async fn restore_comment(
+    state: web::Data<AppState>,
+    user: AuthenticatedUser,
+    id: web::Path<RestoreCommentRequest>,
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope("comments:write")?;
+
+    let mut client = state.comments_client.clone();
+
+    let request = id.into_inner().into_proto(user.user_id);
+
+    let response = client
+        .restore_comment(tonic::Request::new(request))
+        .await?
+        .into_inner().comment
+        .ok_or_else(|| ApiError::missing_field("comment"))?;
+
+    let http_response = RestoreCommentResponse {
+        comment: response.try_into()?
+    };
+
+    Ok(HttpResponse::Ok().json(http_response))
+}
+
+#[utoipa::path(
+    tag = "comments",
+    summary = "List revisions of a comment",
+    description = "Every version of the content, newest first. Revisions of a deleted comment are only listed for moderators.",
+    params(ListCommentRevisionsRequest),
+    responses((status = 200, description = "OK", body = ListCommentRevisionsResponse)),
+    security((), ("bearer_token" = [])),
+)]
+#[get("/{id}/revisions")]
+async fn list_comment_revisions(
+    state: web::Data<AppState>,
+    OptionalUser(user): OptionalUser,
+    id: web::Path<ListCommentRevisionsRequest>,
+) -> Result<HttpResponse, ApiError> {
+    AuthenticatedUser::require_optional_scope(user.as_ref(), "comments:read")?;
+
+    let request = comments::ListCommentRevisionsRequest {
+        id: id.into_inner().id.to_string(),
+        requester_id: user.map(|user| user.user_id.to_string()).unwrap_or_default(),
+    };
+
+    let response = state.retry
+        .run(|| {
+            let mut client = state.comments_client.clone();
+            let request = request.clone();
+            async move { client.list_comment_revisions(tonic::Request::new(request)).await }
+        })
+        .await?
+        .into_inner().revisions;
+
+    let http_response = ListCommentRevisionsResponse {
+        revisions: response.into_iter().map(|r| r.try_into()).collect::<Result<_, Status>>()?
+    };
+
+    Ok(HttpResponse::Ok().json(http_response))
+}
@@ -0,0 +1,43 @@
+use std::sync::LazyLock;
+
+use actix_web::http::header::{self, ContentType};
+use actix_web::{HttpResponse, get, middleware::DefaultHeaders, web};
+use utoipa::OpenApi;
+use utoipa_swagger_ui::{Config, SwaggerUi};
+
+use crate::openapi::ApiDoc;
+
+// The document only depends on the code, so it is rendered once.
+static OPENAPI_JSON: LazyLock<String> = LazyLock::new(|| {
+    ApiDoc::openapi().to_json().unwrap_or_default()
+});
+
+// Swagger UI is served from assets built into the binary, the docs work without internet access.
+pub fn docs_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(openapi_json)
+        .service(docs_redirect)
+        .service(
+            web::scope("/docs")
+                .wrap(DefaultHeaders::new().add((header::CONTENT_SECURITY_POLICY, SWAGGER_UI_CSP)))
+                .service(SwaggerUi::new("/{_:.*}").config(Config::from("/api/openapi.json"))),
+        );
+}
+
+#[get("/openapi.json")]
+async fn openapi_json() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(ContentType::json())
+        .body(OPENAPI_JSON.as_str())
+}
+
+// Swagger UI loads its own scripts and styles and sets inline styles, the rest of the API allows nothing.
+const SWAGGER_UI_CSP: &str = "default-src 'none'; script-src 'self'; style-src 'self' 'unsafe-inline'; \
+    img-src 'self' data:; connect-src 'self'; frame-ancestors 'none'";
+
+// The UI refers to its assets relative to the page, so it has to be served under `/docs/`.
+#[get("/docs")]
+async fn docs_redirect() -> HttpResponse {
+    HttpResponse::PermanentRedirect()
+        .insert_header((header::LOCATION, "docs/"))
+        .finish()
+}
\ No newline at end of file
@@ -1,11 +1,103 @@
+use std::time::Instant;
+
 use actix_web::{HttpResponse, Result, Scope, get, web};
+use tonic_health::pb::{HealthCheckRequest, health_check_response::ServingStatus};
+use utoipa::OpenApi;
+
+use crate::dto::health_dto::{LivenessResponse, ReadinessResponse, ServiceHealth};
+use crate::state::AppState;
 
 pub fn health_routes() -> Scope {
     web::scope("/health")
         .service(health)
+        .service(live)
+        .service(ready)
 }
 
+#[derive(OpenApi)]
+#[openapi(paths(health, live, ready))]
+pub struct HealthApi;
+
+#[utoipa::path(
+    tag = "health",
+    summary = "Check that the gateway is up",
+    responses((status = 200, description = "OK", body = String)),
+)]
 #[get("")]
 async fn health() -> Result<HttpResponse> {
     Ok(HttpResponse::Ok().json("API Gateway is working"))
-}
\ No newline at end of file
+}
+
+// Liveness only covers the gateway itself, a downstream outage must not get it restarted.
+#[utoipa::path(
+    tag = "health",
+    summary = "Liveness probe, the gateway process is up",
+    responses((status = 200, description = "OK", body = LivenessResponse)),
+)]
+#[get("/live")]
+async fn live() -> Result<HttpResponse> {
+    Ok(HttpResponse::Ok().json(LivenessResponse { status: "up" }))
+}
+
+#[utoipa::path(
+    tag = "health",
+    summary = "Readiness probe, checks every service over grpc.health.v1",
+    responses(
+        (status = 200, description = "Every service is serving", body = ReadinessResponse),
+        (status = 503, description = "At least one service is not serving", body = ReadinessResponse),
+    ),
+)]
+#[get("/ready")]
+async fn ready(state: web::Data<AppState>) -> Result<HttpResponse> {
+    let checks = state.health_clients.iter()
+        .map(|(name, client)| {
+            let name = *name;
+            let mut client = client.clone();
+
+            tokio::spawn(async move {
+                let started = Instant::now();
+                let response = client
+                    .check(tonic::Request::new(HealthCheckRequest { service: String::new() }))
+                    .await;
+                let latency_ms = started.elapsed().as_millis() as u64;
+
+                match response {
+                    Ok(response) => ServiceHealth {
+                        name,
+                        status: response.into_inner().status().as_str_name().to_string(),
+                        latency_ms,
+                        error: None,
+                    },
+                    Err(status) => ServiceHealth {
+                        name,
+                        status: "UNREACHABLE".to_string(),
+                        latency_ms,
+                        error: Some(status.message().to_string()),
+                    },
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut services = Vec::with_capacity(checks.len());
+    for check in checks {
+        if let Ok(service) = check.await {
+            services.push(service);
+        }
+    }
+
+    let serving = ServingStatus::Serving.as_str_name();
+    let all_serving = services.len() == state.health_clients.len()
+        && services.iter().all(|service| service.status == serving);
+
+    let body = ReadinessResponse {
+        status: if all_serving { "ready" } else { "degraded" },
+        services,
+    };
+
+    if all_serving {
+        Ok(HttpResponse::Ok().json(body))
+    } else {
+        Ok(HttpResponse::ServiceUnavailable().json(body))
+    }
+}
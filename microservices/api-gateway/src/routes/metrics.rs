@@ -0,0 +1,14 @@
+use actix_web::{HttpResponse, get, web};
+use metrics_exporter_prometheus::PrometheusHandle;
+
+// Mounted at the root, outside `/api`, where Prometheus looks by default.
+pub fn metrics_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(metrics);
+}
+
+#[get("/metrics")]
+async fn metrics(handle: web::Data<PrometheusHandle>) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(handle.render())
+}
@@ -1,4 +1,28 @@
 pub mod health;
 pub mod auth;
 pub mod posts;
-pub mod comments;
\ No newline at end of file
+pub mod comments;
+pub mod api_keys;
+pub mod sessions;
+pub mod oidc;
+pub mod security_events;
+pub mod docs;
+pub mod metrics;
+pub mod users;
+pub mod tags;
+pub mod search;
+
+use actix_web::{Scope, web};
+
+// Everything under `/api`, `main` mounts it and the OpenAPI coverage test checks it.
+pub fn api_routes() -> Scope {
+    web::scope("/api")
+        .service(health::health_routes())
+        .configure(docs::docs_routes)
+        .service(auth::auth_routes())
+        .service(posts::posts_routes())
+        .service(comments::comments_routes())
+        .service(users::users_routes())
+        .service(tags::tags_routes())
+        .service(search::search_routes())
+}
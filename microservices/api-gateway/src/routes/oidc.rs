@@ -0,0 +1,127 @@
+use actix_web::cookie::{Cookie, SameSite, time::Duration};
+use actix_web::http::header;
+use actix_web::{HttpRequest, HttpResponse, Result, Scope, get, web};
+use utoipa::OpenApi;
+
+use crate::error::ApiError;
+use crate::dto::auth_dto::{OidcCallbackQuery, OidcProviderPath, OidcSignInResponse, User};
+use crate::middleware::client_info::ClientInfo;
+use crate::routes::auth::refresh_token_cookie;
+use crate::{proto::auth, state::AppState};
+
+const OIDC_STATE_COOKIE: &str = "oidc_state";
+
+pub fn oidc_routes() -> Scope {
+    web::scope("/oidc")
+        .service(authorize)
+        .service(callback)
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(authorize, callback))]
+pub struct OidcApi;
+
+// Binds the `state` to the browser that started the flow. `Lax` because the provider redirects back cross-site.
+fn oidc_state_cookie(state: String) -> Cookie<'static> {
+    Cookie::build(OIDC_STATE_COOKIE, state)
+        .path("/api/auth/oidc")
+        .http_only(true)
+        .secure(true)
+        .same_site(SameSite::Lax)
+        .max_age(Duration::minutes(10))
+        .finish()
+}
+
+#[utoipa::path(
+    tag = "oidc",
+    summary = "Redirect to the OpenID Connect provider",
+    params(OidcProviderPath),
+    responses((status = 302, description = "Redirect to the provider")),
+)]
+#[get("/{provider}/authorize")]
+async fn authorize(
+    state: web::Data<AppState>,
+    path: web::Path<OidcProviderPath>,
+) -> Result<HttpResponse, ApiError> {
+    let mut client = state.auth_client.clone();
+
+    let request = auth::StartOidcSignInRequest {
+        provider: path.into_inner().provider,
+    };
+
+    let response = client
+        .start_oidc_sign_in(tonic::Request::new(request))
+        .await?
+        .into_inner();
+
+    Ok(HttpResponse::Found()
+        .cookie(oidc_state_cookie(response.state))
+        .insert_header((header::LOCATION, response.authorization_url))
+        .finish())
+}
+
+#[utoipa::path(
+    tag = "oidc",
+    summary = "Finish sign in with the OpenID Connect provider, sets the refresh token cookie",
+    params(OidcProviderPath, OidcCallbackQuery),
+    responses((status = 200, description = "OK", body = OidcSignInResponse)),
+)]
+#[get("/{provider}/callback")]
+async fn callback(
+    state: web::Data<AppState>,
+    client_info: ClientInfo,
+    req: HttpRequest,
+    path: web::Path<OidcProviderPath>,
+    query: web::Query<OidcCallbackQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let mut client = state.auth_client.clone();
+
+    let query = query.into_inner();
+
+    if let Some(error) = query.error {
+        return Err(ApiError::unauthorized(format!("Sign in was cancelled: {error}")));
+    }
+
+    let (code, oidc_state) = query.code.zip(query.state)
+        .ok_or_else(|| ApiError::bad_request("Missing code or state"))?;
+
+    let expected_state = req.cookie(OIDC_STATE_COOKIE)
+        .map(|cookie| cookie.value().to_string());
+
+    if expected_state.as_deref() != Some(oidc_state.as_str()) {
+        return Err(ApiError::bad_request("Sign in state does not match"));
+    }
+
+    let request = auth::FinishOidcSignInRequest {
+        provider: path.into_inner().provider,
+        state: oidc_state,
+        code,
+    };
+
+    let response = client
+        .finish_oidc_sign_in(client_info.into_request(request))
+        .await?
+        .into_inner();
+
+    let user = response.user
+        .ok_or_else(|| ApiError::missing_field("user"))?;
+
+    let http_response = OidcSignInResponse {
+        user: User {
+            id: user.id,
+            username: user.username,
+            email: user.email,
+        },
+        access_token: response.access_token,
+        refresh_token: response.refresh_token.clone(),
+        created: response.created,
+    };
+
+    let mut expired_state = oidc_state_cookie(String::new());
+    expired_state.make_removal();
+
+    Ok(HttpResponse::Ok()
+        .cookie(refresh_token_cookie(response.refresh_token))
+        .cookie(expired_state)
+        .json(http_response))
+}
@@ -1,134 +1,574 @@
-use actix_web::{HttpResponse, Result, Scope, delete, get, post, put, web};
-use tonic::Code;
+use actix_web::{HttpRequest, HttpResponse, Result, Scope, delete, get, patch, post, put, web};
+use tonic::Status;
+use utoipa::OpenApi;
 
-use crate::dto::posts_dto::{CreatePostRequest, CreatePostResponse, DeletePostRequest, DeletePostResponse, GetPostRequest, GetPostResponse, GetPostsResponse, UpdatePostRequest, UpdatePostResponse};
+use crate::domain::etag::{etag, if_match_versions};
+use crate::domain::time::datetime_to_timestamp;
+use crate::error::{ApiError, optional_json};
+use crate::middleware::auth::{AuthenticatedUser, OptionalUser};
+use crate::dto::posts_dto::{CreatePostRequest, CreatePostResponse, DeletePostRequest, DeletePostResponse, GetPostRequest, GetPostResponse, GetPostRevisionRequest, GetPostRevisionResponse, GetPostsResponse, ListDraftsResponse, ListPostRevisionsRequest, ListPostRevisionsResponse, LockPostRequest, LockPostResponse, PatchPostPath, PatchPostRequest, PatchPostResponse, PublishPostPath, PublishPostRequest, PublishPostResponse, RestorePostRequest, RestorePostResponse, UnlockPostRequest, UnlockPostResponse, UpdatePostRequest, UpdatePostResponse};
 use crate::proto::posts;
 use crate::{state::AppState};
 
 pub fn posts_routes() -> Scope {
     web::scope("/posts")
+        // Before `/{id}`, which would otherwise take "drafts" for an id.
+        .service(list_drafts)
         .service(get_post)
         .service(get_posts)
         .service(create_post)
         .service(update_post)
+        .service(patch_post)
         .service(delete_post)
+        .service(restore_post)
+        .service(lock_post)
+        .service(unlock_post)
+        .service(list_post_revisions)
+        .service(get_post_revision)
+        .service(publish_post)
 }
 
+#[derive(OpenApi)]
+#[openapi(paths(list_drafts, get_post, get_posts, create_post, update_post, patch_post, delete_post, restore_post, lock_post, unlock_post, list_post_revisions, get_post_revision, publish_post))]
+pub struct PostsApi;
+
+#[utoipa::path(
+    tag = "posts",
+    summary = "Get a post",
+    description = "Drafts, scheduled and private posts are only found for their author, followers-only posts for the author's followers.",
+    params(GetPostRequest),
+    responses((
+        status = 200, description = "OK", body = GetPostResponse,
+        headers(("ETag" = String, description = "Version of the post, for If-Match on updates")),
+    )),
+    security((), ("bearer_token" = [])),
+)]
 #[get("/{id}")]
 async fn get_post(
     state: web::Data<AppState>,
+    OptionalUser(user): OptionalUser,
     id: web::Path<GetPostRequest>,
-) -> Result<HttpResponse> {
-    let mut client = state.posts_client.clone();
+) -> Result<HttpResponse, ApiError> {
+    AuthenticatedUser::require_optional_scope(user.as_ref(), "posts:read")?;
 
-    let request = id.into_inner();
+    let mut request = posts::GetPostRequest::from(id.into_inner());
+    request.requester_id = user.map(|user| user.user_id.to_string()).unwrap_or_default();
 
-    let response = client
-        .get_post(tonic::Request::new(request.into()))
-        .await
-        .map_err(|error| {
-            match error.code() {
-                Code::NotFound => actix_web::error::ErrorNotFound(error.message().to_string()),
-                _ => actix_web::error::ErrorInternalServerError(error.message().to_string()),
-            }
-        })?
-        .into_inner().post.unwrap();
+    let response = state.retry
+        .run(|| {
+            let mut client = state.posts_client.clone();
+            let request = request.clone();
+            async move { client.get_post(tonic::Request::new(request)).await }
+        })
+        .await?
+        .into_inner().post
+        .ok_or_else(|| ApiError::missing_field("post"))?;
+
+    let version = response.version;
 
     let http_response = GetPostResponse {
-        post: response.try_into()
-            .map_err(|_| actix_web::error::ErrorInternalServerError("Internal server error"))?
+        post: response.try_into()?
     };
 
-    Ok(HttpResponse::Ok().json(http_response))
+    Ok(HttpResponse::Ok().insert_header(etag(version)).json(http_response))
 }
 
+#[utoipa::path(
+    tag = "posts",
+    summary = "List posts",
+    description = "Anonymous callers get public posts, signed in ones also their own and followers-only posts of users they follow.",
+    responses((status = 200, description = "OK", body = GetPostsResponse)),
+    security((), ("bearer_token" = [])),
+)]
 #[get("")]
 async fn get_posts(
     state: web::Data<AppState>,
-) -> Result<HttpResponse> {
+    OptionalUser(user): OptionalUser,
+) -> Result<HttpResponse, ApiError> {
+    AuthenticatedUser::require_optional_scope(user.as_ref(), "posts:read")?;
+
     let mut client = state.posts_client.clone();
 
-    let request = posts::GetPostsRequest { };
+    let request = posts::GetPostsRequest {
+        requester_id: user.map(|user| user.user_id.to_string()).unwrap_or_default(),
+    };
 
     let response = client
         .get_posts(tonic::Request::new(request))
-        .await
-        .map_err(|e| actix_web::error::ErrorInternalServerError(e.message().to_string()))?
+        .await?
         .into_inner().posts;
 
     let http_response = GetPostsResponse {
-        posts: response.into_iter().map(|c| c.try_into()).collect::<Result<_, _>>()
-        .map_err(|_| actix_web::error::ErrorInternalServerError("Internal server error"))?
+        posts: response.into_iter().map(|c| c.try_into()).collect::<Result<_, Status>>()?
     };
 
     Ok(HttpResponse::Ok().json(http_response))
 }
 
+#[utoipa::path(
+    tag = "posts",
+    summary = "Create a post",
+    description = "The caller is the author.",
+    request_body = CreatePostRequest,
+    responses((status = 200, description = "OK", body = CreatePostResponse)),
+    security(("bearer_token" = [])),
+)]
 #[post("")]
 async fn create_post(
     state: web::Data<AppState>,
+    user: AuthenticatedUser,
     body: web::Json<CreatePostRequest>
-) -> Result<HttpResponse> {
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope("posts:write")?;
+
     let mut client = state.posts_client.clone();
 
-    let request = body.into_inner();
+    let request = body.into_inner().into_proto(user.user_id);
 
     let response = client
-        .create_post(tonic::Request::new(request.into()))
-        .await
-        .map_err(|e| actix_web::error::ErrorInternalServerError(e.message().to_string()))?
-        .into_inner().post.unwrap();
+        .create_post(tonic::Request::new(request))
+        .await?
+        .into_inner().post
+        .ok_or_else(|| ApiError::missing_field("post"))?;
 
     let http_response = CreatePostResponse {
-        post: response.try_into()
-            .map_err(|_| actix_web::error::ErrorInternalServerError("Internal server error"))?
+        post: response.try_into()?
     };
 
     Ok(HttpResponse::Ok().json(http_response))
 }
 
+#[utoipa::path(
+    tag = "posts",
+    summary = "Update a post",
+    description = "Only the author can update the post. With If-Match, the update is rejected with 412 when the post changed since that ETag was read.",
+    request_body = UpdatePostRequest,
+    params(("If-Match" = Option<String>, Header, description = "ETag from a previous read")),
+    responses(
+        (
+            status = 200, description = "OK", body = UpdatePostResponse,
+            headers(("ETag" = String, description = "Version of the updated post")),
+        ),
+        (status = 412, description = "The post was changed since the If-Match version"),
+    ),
+    security(("bearer_token" = [])),
+)]
 #[put("")]
 async fn update_post(
     state: web::Data<AppState>,
+    user: AuthenticatedUser,
+    req: HttpRequest,
     body: web::Json<UpdatePostRequest>
-) -> Result<HttpResponse> {
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope("posts:write")?;
+
     let mut client = state.posts_client.clone();
 
-    let request = body.into_inner();
+    let mut request = posts::UpdatePostRequest::from(body.into_inner());
+    request.expected_versions = if_match_versions(&req)?;
+    request.requester_id = user.user_id.to_string();
 
     let response = client
-        .update_post(tonic::Request::new(request.into()))
-        .await
-        .map_err(|e| actix_web::error::ErrorInternalServerError(e.message().to_string()))?
-        .into_inner().post.unwrap();
+        .update_post(tonic::Request::new(request))
+        .await?
+        .into_inner().post
+        .ok_or_else(|| ApiError::missing_field("post"))?;
+
+    let version = response.version;
 
     let http_response = UpdatePostResponse {
-        post: response.try_into()
-            .map_err(|_| actix_web::error::ErrorInternalServerError("Internal server error"))?
+        post: response.try_into()?
     };
 
-    Ok(HttpResponse::Ok().json(http_response))
+    Ok(HttpResponse::Ok().insert_header(etag(version)).json(http_response))
 }
 
+#[utoipa::path(
+    tag = "posts",
+    summary = "Partially update a post",
+    description = "Changes only the fields present in the body, only the author can update the post. With If-Match, the update is rejected with 412 when the post changed since that ETag was read.",
+    request_body = PatchPostRequest,
+    params(PatchPostPath, ("If-Match" = Option<String>, Header, description = "ETag from a previous read")),
+    responses(
+        (
+            status = 200, description = "OK", body = PatchPostResponse,
+            headers(("ETag" = String, description = "Version of the updated post")),
+        ),
+        (status = 412, description = "The post was changed since the If-Match version"),
+    ),
+    security(("bearer_token" = [])),
+)]
+#[patch("/{id}")]
+async fn patch_post(
+    state: web::Data<AppState>,
+    user: AuthenticatedUser,
+    req: HttpRequest,
+    path: web::Path<PatchPostPath>,
+    body: web::Json<PatchPostRequest>
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope("posts:write")?;
+
+    let mut client = state.posts_client.clone();
+
+    let mut request = body.into_inner().into_proto(path.into_inner().id);
+    request.expected_versions = if_match_versions(&req)?;
+    request.requester_id = user.user_id.to_string();
+
+    let response = client
+        .update_post(tonic::Request::new(request))
+        .await?
+        .into_inner().post
+        .ok_or_else(|| ApiError::missing_field("post"))?;
+
+    let version = response.version;
+
+    let http_response = PatchPostResponse {
+        post: response.try_into()?
+    };
+
+    Ok(HttpResponse::Ok().insert_header(etag(version)).json(http_response))
+}
+
+#[utoipa::path(
+    tag = "posts",
+    summary = "Delete a post",
+    description = "Soft deletes the post together with its comments, both can be restored within the retention window. Only the author can delete the post.",
+    params(DeletePostRequest),
+    responses((status = 200, description = "OK", body = DeletePostResponse)),
+    security(("bearer_token" = [])),
+)]
 #[delete("/{id}")]
 async fn delete_post(
     state: web::Data<AppState>,
+    user: AuthenticatedUser,
     id: web::Path<DeletePostRequest>
-) -> Result<HttpResponse> {
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope("posts:write")?;
+
     let mut client = state.posts_client.clone();
 
-    let request = id.into_inner();
+    let mut request = posts::DeletePostRequest::from(id.into_inner());
+    request.requester_id = user.user_id.to_string();
 
     let response = client
-        .delete_post(tonic::Request::new(request.into()))
-        .await
-        .map_err(|e| actix_web::error::ErrorInternalServerError(e.message().to_string()))?
-        .into_inner().post.unwrap();
+        .delete_post(tonic::Request::new(request))
+        .await?
+        .into_inner().post
+        .ok_or_else(|| ApiError::missing_field("post"))?;
 
     let http_response = DeletePostResponse {
-        post: response.try_into()
-            .map_err(|_| actix_web::error::ErrorInternalServerError("Internal server error"))?
+        post: response.try_into()?
         };
 
     Ok(HttpResponse::Ok().json(http_response))
-}
\ No newline at end of file
+}
+
+#[utoipa::path(
+    tag = "posts",
+    summary = "Restore a deleted post",
+    description = "Also restores the comments that were deleted along with the post. Only the author can restore the post.",
+    params(RestorePostRequest),
+    responses((status = 200, description = "OK", body = RestorePostResponse)),
+    security(("bearer_token" = [])),
+)]
+#[post("/{id}/restore")]
+async fn restore_post(
+    state: web::Data<AppState>,
+    user: AuthenticatedUser,
+    id: web::Path<RestorePostRequest>
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope("posts:write")?;
+
+    let mut client = state.posts_client.clone();
+
+    let mut request = posts::RestorePostRequest::from(id.into_inner());
+    request.requester_id = user.user_id.to_string();
+
+    let response = client
+        .restore_post(tonic::Request::new(request))
+        .await?
+        .into_inner().post
+        .ok_or_else(|| ApiError::missing_field("post"))?;
+
+    let http_response = RestorePostResponse {
+        post: response.try_into()?
+    };
+
+    Ok(HttpResponse::Ok().json(http_response))
+}
+
+#[utoipa::path(
+    tag = "posts",
+    summary = "Lock a post",
+    description = "Stops new comments on the post, existing ones stay. Only the author or a moderator can lock the post, locking it again is not an error.",
+    params(LockPostRequest),
+    responses((status = 200, description = "OK", body = LockPostResponse)),
+    security(("bearer_token" = [])),
+)]
+#[post("/{id}/lock")]
+async fn lock_post(
+    state: web::Data<AppState>,
+    user: AuthenticatedUser,
+    id: web::Path<LockPostRequest>
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope("posts:write")?;
+
+    let mut client = state.posts_client.clone();
+
+    let mut request = posts::LockPostRequest::from(id.into_inner());
+    request.requester_id = user.user_id.to_string();
+
+    let response = client
+        .lock_post(tonic::Request::new(request))
+        .await?
+        .into_inner().post
+        .ok_or_else(|| ApiError::missing_field("post"))?;
+
+    let http_response = LockPostResponse {
+        post: response.try_into()?
+    };
+
+    Ok(HttpResponse::Ok().json(http_response))
+}
+
+#[utoipa::path(
+    tag = "posts",
+    summary = "Unlock a post",
+    description = "Accepts new comments on the post again. Only the author or a moderator can unlock the post.",
+    params(UnlockPostRequest),
+    responses((status = 200, description = "OK", body = UnlockPostResponse)),
+    security(("bearer_token" = [])),
+)]
+#[delete("/{id}/lock")]
+async fn unlock_post(
+    state: web::Data<AppState>,
+    user: AuthenticatedUser,
+    id: web::Path<UnlockPostRequest>
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope("posts:write")?;
+
+    let mut client = state.posts_client.clone();
+
+    let mut request = posts::UnlockPostRequest::from(id.into_inner());
+    request.requester_id = user.user_id.to_string();
+
+    let response = client
+        .unlock_post(tonic::Request::new(request))
+        .await?
+        .into_inner().post
+        .ok_or_else(|| ApiError::missing_field("post"))?;
+
+    let http_response = UnlockPostResponse {
+        post: response.try_into()?
+    };
+
+    Ok(HttpResponse::Ok().json(http_response))
+}
+
+#[utoipa::path(
+    tag = "posts",
+    summary = "List revisions of a post",
+    description = "Every version of the title and description, newest first. Revision 1 is the post as created.",
+    params(ListPostRevisionsRequest),
+    responses((status = 200, description = "OK", body = ListPostRevisionsResponse)),
+    security((), ("bearer_token" = [])),
+)]
+#[get("/{id}/revisions")]
+async fn list_post_revisions(
+    state: web::Data<AppState>,
+    OptionalUser(user): OptionalUser,
+    id: web::Path<ListPostRevisionsRequest>,
+) -> Result<HttpResponse, ApiError> {
+    AuthenticatedUser::require_optional_scope(user.as_ref(), "posts:read")?;
+
+    let mut request = posts::ListPostRevisionsRequest::from(id.into_inner());
+    request.requester_id = user.map(|user| user.user_id.to_string()).unwrap_or_default();
+
+    let response = state.retry
+        .run(|| {
+            let mut client = state.posts_client.clone();
+            let request = request.clone();
+            async move { client.list_post_revisions(tonic::Request::new(request)).await }
+        })
+        .await?
+        .into_inner().revisions;
+
+    let http_response = ListPostRevisionsResponse {
+        revisions: response.into_iter().map(|r| r.try_into()).collect::<Result<_, Status>>()?
+    };
+
+    Ok(HttpResponse::Ok().json(http_response))
+}
+
+#[utoipa::path(
+    tag = "posts",
+    summary = "Get a revision of a post",
+    params(GetPostRevisionRequest),
+    responses((status = 200, description = "OK", body = GetPostRevisionResponse)),
+    security((), ("bearer_token" = [])),
+)]
+#[get("/{id}/revisions/{revision}")]
+async fn get_post_revision(
+    state: web::Data<AppState>,
+    OptionalUser(user): OptionalUser,
+    path: web::Path<GetPostRevisionRequest>,
+) -> Result<HttpResponse, ApiError> {
+    AuthenticatedUser::require_optional_scope(user.as_ref(), "posts:read")?;
+
+    let mut request = posts::GetPostRevisionRequest::from(path.into_inner());
+    request.requester_id = user.map(|user| user.user_id.to_string()).unwrap_or_default();
+
+    let response = state.retry
+        .run(|| {
+            let mut client = state.posts_client.clone();
+            let request = request.clone();
+            async move { client.get_post_revision(tonic::Request::new(request)).await }
+        })
+        .await?
+        .into_inner().revision
+        .ok_or_else(|| ApiError::missing_field("revision"))?;
+
+    let http_response = GetPostRevisionResponse {
+        revision: response.try_into()?
+    };
+
+    Ok(HttpResponse::Ok().json(http_response))
+}
+
+#[utoipa::path(
+    tag = "posts",
+    summary = "List your drafts and scheduled posts",
+    responses((status = 200, description = "OK", body = ListDraftsResponse)),
+    security(("bearer_token" = [])),
+)]
+#[get("/drafts")]
+async fn list_drafts(
+    state: web::Data<AppState>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope("posts:read")?;
+
+    let request = posts::ListDraftsRequest {
+        user_id: user.user_id.to_string(),
+    };
+
+    let response = state.retry
+        .run(|| {
+            let mut client = state.posts_client.clone();
+            let request = request.clone();
+            async move { client.list_drafts(tonic::Request::new(request)).await }
+        })
+        .await?
+        .into_inner().posts;
+
+    let http_response = ListDraftsResponse {
+        posts: response.into_iter().map(|c| c.try_into()).collect::<Result<_, Status>>()?
+    };
+
+    Ok(HttpResponse::Ok().json(http_response))
+}
+
+#[utoipa::path(
+    tag = "posts",
+    summary = "Publish a draft or scheduled post",
+    description = "Publishes right away, or schedules the post when `publish_at` is in the future. Only the author can publish.",
+    params(PublishPostPath),
+    request_body(content = Option<PublishPostRequest>),
+    responses((status = 200, description = "OK", body = PublishPostResponse)),
+    security(("bearer_token" = [])),
+)]
+#[post("/{id}/publish")]
+async fn publish_post(
+    state: web::Data<AppState>,
+    user: AuthenticatedUser,
+    path: web::Path<PublishPostPath>,
+    body: web::Bytes,
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope("posts:write")?;
+
+    let body = optional_json::<PublishPostRequest>(&body)?;
+
+    let mut client = state.posts_client.clone();
+
+    let request = posts::PublishPostRequest {
+        id: path.into_inner().id.to_string(),
+        requester_id: user.user_id.to_string(),
+        publish_at: body
+            .and_then(|body| body.publish_at)
+            .map(datetime_to_timestamp),
+    };
+
+    let response = client
+        .publish_post(tonic::Request::new(request))
+        .await?
+        .into_inner().post
+        .ok_or_else(|| ApiError::missing_field("post"))?;
+
+    let http_response = PublishPostResponse {
+        post: response.try_into()?
+    };
+
+    Ok(HttpResponse::Ok().json(http_response))
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::{StatusCode, header};
+    use actix_web::test::{TestRequest, call_service, init_service, read_body_json};
+    use actix_web::{App, HttpMessage};
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn author() -> AuthenticatedUser {
+        AuthenticatedUser {
+            user_id: Uuid::new_v4(),
+            username: "author".to_string(),
+            scopes: vec![],
+            is_api_key: false,
+            session_id: Some(Uuid::new_v4()),
+        }
+    }
+
+    async fn publish(body: &'static str) -> (StatusCode, serde_json::Value) {
+        // Nothing listens here, a request that gets past the body would fail as unavailable.
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(AppState::for_tests("http://127.0.0.1:1")))
+                .service(posts_routes()),
+        )
+        .await;
+
+        let request = TestRequest::post()
+            .uri(&format!("/posts/{}/publish", Uuid::new_v4()))
+            .insert_header((header::CONTENT_TYPE, "application/json"))
+            .set_payload(body)
+            .to_request();
+        request.extensions_mut().insert(author());
+
+        let response = call_service(&app, request).await;
+        let status = response.status();
+        let body = if status.is_success() { serde_json::Value::Null } else { read_body_json(response).await };
+
+        (status, body)
+    }
+
+    #[actix_web::test]
+    async fn a_malformed_publish_time_is_refused() {
+        let (status, body) = publish(r#"{"publish_at": "tomorrow"}"#).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body["detail"].as_str().unwrap().starts_with("Json deserialize error"), "{body}");
+
+        let (status, _) = publish(r#"{"publish_at": "#).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn a_missing_body_publishes_right_away() {
+        let (status, _) = publish("").await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+
+        let (status, _) = publish(r#"{"publish_at": "2030-01-01T00:00:00Z"}"#).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    }
+}
@@ -0,0 +1,323 @@
+use actix_web::{HttpResponse, Result, Scope, get, web};
+use tonic::Status;
+use utoipa::OpenApi;
+
+use crate::error::ApiError;
+use crate::dto::search_dto::{CommentSearchHit, PostSearchHit, SearchQuery, SearchResponse, SearchType, UserSearchHit};
+use crate::middleware::auth::OptionalUser;
+use crate::{proto::{comments, posts, users}, state::AppState};
+
+pub fn search_routes() -> Scope {
+    web::scope("/search")
+        .service(search)
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(search))]
+pub struct SearchApi;
+
+#[utoipa::path(
+    tag = "search",
+    summary = "Search posts, comments and users",
+    description = "Full-text search, best matches first. Posts are filtered by visibility like listing posts, comments are only searched under public posts. When some of the searched types fail, the others are still returned and the failed ones are listed in `unavailable`; the search fails only when all of them do or the query is invalid.",
+    params(SearchQuery),
+    responses((status = 200, description = "OK", body = SearchResponse)),
+    security((), ("bearer_token" = [])),
+)]
+#[get("")]
+async fn search(
+    state: web::Data<AppState>,
+    OptionalUser(user): OptionalUser,
+    query: web::Query<SearchQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let query = query.into_inner();
+
+    // Asking for a type the api key can't read is refused, without a type those are left out.
+    if let (Some(user), Some(scope)) = (&user, query.kind.and_then(read_scope)) {
+        user.require_scope(scope)?;
+    }
+
+    let readable = |kind| match (&user, read_scope(kind)) {
+        (Some(user), Some(scope)) => user.has_scope(scope),
+        _ => true,
+    };
+    let wants = |kind| query.kind.is_none_or(|wanted| wanted == kind) && readable(kind);
+
+    let requester_id = user.as_ref().map(|user| user.user_id.to_string()).unwrap_or_default();
+
+    // Searched side by side, a type that wasn't asked for resolves to no hits right away.
+    let (posts, comments, users) = tokio::join!(
+        async {
+            if !wants(SearchType::Posts) {
+                return Ok(vec![]);
+            }
+
+            search_posts(&state, &query, requester_id.clone()).await
+        },
+        async {
+            if !wants(SearchType::Comments) {
+                return Ok(vec![]);
+            }
+
+            search_comments(&state, &query).await
+        },
+        async {
+            if !wants(SearchType::Users) {
+                return Ok(vec![]);
+            }
+
+            search_users(&state, &query).await
+        },
+    );
+
+    let mut sections = Sections::default();
+    let posts = sections.take(SearchType::Posts, wants(SearchType::Posts), posts);
+    let comments = sections.take(SearchType::Comments, wants(SearchType::Comments), comments);
+    let users = sections.take(SearchType::Users, wants(SearchType::Users), users);
+
+    let http_response = SearchResponse { posts, comments, users, unavailable: sections.finish()? };
+
+    Ok(HttpResponse::Ok().json(http_response))
+}
+
+// Collects the per type results, one service being down shouldn't take the whole search with it.
+#[derive(Default)]
+struct Sections {
+    answered: bool,
+    unavailable: Vec<SearchType>,
+    error: Option<ApiError>,
+}
+
+impl Sections {
+    fn take<T>(&mut self, kind: SearchType, searched: bool, result: Result<Vec<T>, ApiError>) -> Vec<T> {
+        match result {
+            Ok(hits) => {
+                self.answered |= searched;
+                hits
+            },
+            Err(error) => {
+                tracing::warn!("Searching {kind:?} failed: {error}");
+                self.unavailable.push(kind);
+
+                // A client error is kept over a server one, it's what the whole search fails with.
+                if self.error.as_ref().is_none_or(|kept| !kept.status.is_client_error()) {
+                    self.error = Some(error);
+                }
+
+                vec![]
+            },
+        }
+    }
+
+    // The types that failed. Every type sees the same query, so a client error from one fails the
+    // whole search, as does nothing answering at all.
+    fn finish(self) -> Result<Vec<SearchType>, ApiError> {
+        match self.error {
+            Some(error) if error.status.is_client_error() || !self.answered => Err(error),
+            _ => Ok(self.unavailable),
+        }
+    }
+}
+
+// Scope an api key needs for hits of this type.
+fn read_scope(kind: SearchType) -> Option<&'static str> {
+    match kind {
+        SearchType::Posts => Some("posts:read"),
+        SearchType::Comments => Some("comments:read"),
+        SearchType::Users => None,
+    }
+}
+
+async fn search_posts(
+    state: &AppState,
+    query: &SearchQuery,
+    requester_id: String,
+) -> Result<Vec<PostSearchHit>, ApiError> {
+    let request = posts::SearchPostsRequest {
+        query: query.q.clone(),
+        requester_id,
+        limit: query.limit.unwrap_or_default(),
+        offset: query.offset.unwrap_or_default(),
+    };
+
+    let response = state.retry
+        .run(|| {
+            let mut client = state.posts_client.clone();
+            let request = request.clone();
+            async move { client.search_posts(tonic::Request::new(request)).await }
+        })
+        .await?
+        .into_inner().hits;
+
+    Ok(response.into_iter().map(|h| h.try_into()).collect::<Result<_, Status>>()?)
+}
+
+async fn search_comments(
+    state: &AppState,
+    query: &SearchQuery,
+) -> Result<Vec<CommentSearchHit>, ApiError> {
+    let request = comments::SearchCommentsRequest {
+        query: query.q.clone(),
+        limit: query.limit.unwrap_or_default(),
+        offset: query.offset.unwrap_or_default(),
+    };
+
+    let response = state.retry
+        .run(|| {
+            let mut client = state.comments_client.clone();
+            let request = request.clone();
+            async move { client.search_comments(tonic::Request::new(request)).await }
+        })
+        .await?
+        .into_inner().hits;
+
+    Ok(response.into_iter().map(|h| h.try_into()).collect::<Result<_, Status>>()?)
+}
+
+async fn search_users(
+    state: &AppState,
+    query: &SearchQuery,
+) -> Result<Vec<UserSearchHit>, ApiError> {
+    let request = users::SearchUsersRequest {
+        query: query.q.clone(),
+        limit: query.limit.unwrap_or_default(),
+        offset: query.offset.unwrap_or_default(),
+    };
+
+    let response = state.retry
+        .run(|| {
+            let mut client = state.users_client.clone();
+            let request = request.clone();
+            async move { client.search_users(tonic::Request::new(request)).await }
+        })
+        .await?
+        .into_inner().hits;
+
+    Ok(response.into_iter().map(|h| h.try_into()).collect::<Result<_, Status>>()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::App;
+    use actix_web::http::StatusCode;
+    use actix_web::test::{TestRequest, call_service, init_service, read_body_json};
+    use tonic::{Request, Response, transport::{Server, server::TcpIncoming}};
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::proto::users::{
+        CreateUserRequest, FollowUserRequest, FollowUserResponse, GetUserByIdRequest, GetUserRequest,
+        IsFollowingRequest, IsFollowingResponse, ListFollowingRequest, ListFollowingResponse,
+        SearchUsersRequest, SearchUsersResponse, UnfollowUserRequest, UnfollowUserResponse, User,
+        users_server::{Users, UsersServer},
+    };
+
+    // Answers user searches and nothing else, checking the query like users-service does.
+    struct FakeUsers;
+
+    #[tonic::async_trait]
+    impl Users for FakeUsers {
+        async fn get_user(&self, _: Request<GetUserRequest>) -> Result<Response<User>, Status> {
+            Err(Status::unimplemented("get_user"))
+        }
+
+        async fn get_user_by_id(&self, _: Request<GetUserByIdRequest>) -> Result<Response<User>, Status> {
+            Err(Status::unimplemented("get_user_by_id"))
+        }
+
+        async fn create_user(&self, _: Request<CreateUserRequest>) -> Result<Response<User>, Status> {
+            Err(Status::unimplemented("create_user"))
+        }
+
+        async fn follow_user(&self, _: Request<FollowUserRequest>) -> Result<Response<FollowUserResponse>, Status> {
+            Err(Status::unimplemented("follow_user"))
+        }
+
+        async fn unfollow_user(&self, _: Request<UnfollowUserRequest>) -> Result<Response<UnfollowUserResponse>, Status> {
+            Err(Status::unimplemented("unfollow_user"))
+        }
+
+        async fn is_following(&self, _: Request<IsFollowingRequest>) -> Result<Response<IsFollowingResponse>, Status> {
+            Err(Status::unimplemented("is_following"))
+        }
+
+        async fn list_following(&self, _: Request<ListFollowingRequest>) -> Result<Response<ListFollowingResponse>, Status> {
+            Err(Status::unimplemented("list_following"))
+        }
+
+        async fn search_users(&self, request: Request<SearchUsersRequest>) -> Result<Response<SearchUsersResponse>, Status> {
+            let username = request.into_inner().query;
+
+            common::search::check_query(&username).map_err(Status::invalid_argument)?;
+
+            let hit = users::UserSearchHit {
+                id: Uuid::new_v4().to_string(),
+                username_highlight: format!("<mark>{username}</mark>"),
+                username,
+                rank: 0.1,
+            };
+
+            Ok(Response::new(SearchUsersResponse { hits: vec![hit] }))
+        }
+    }
+
+    async fn search_at(url: &str, query: &str) -> (StatusCode, serde_json::Value) {
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(AppState::for_tests(url)))
+                .service(search_routes()),
+        )
+        .await;
+
+        let response = call_service(&app, TestRequest::get().uri(&format!("/search?{query}")).to_request()).await;
+
+        (response.status(), read_body_json(response).await)
+    }
+
+    #[actix_web::test]
+    async fn the_types_that_answer_are_returned_when_others_fail() {
+        // Only users are served, posts and comments searches come back unimplemented.
+        let incoming = TcpIncoming::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let url = format!("http://{}", incoming.local_addr().unwrap());
+        tokio::spawn(Server::builder().add_service(UsersServer::new(FakeUsers)).serve_with_incoming(incoming));
+
+        let (status, body) = search_at(&url, "q=zebra").await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["users"][0]["username"], "zebra");
+        assert_eq!(body["posts"], serde_json::json!([]));
+        assert_eq!(body["unavailable"], serde_json::json!(["posts", "comments"]));
+    }
+
+    #[actix_web::test]
+    async fn the_search_fails_when_no_type_answers() {
+        // Nothing listens on port 1.
+        let (status, _) = search_at("http://127.0.0.1:1", "q=zebra").await;
+
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[actix_web::test]
+    async fn the_search_fails_when_the_only_type_asked_for_fails() {
+        let incoming = TcpIncoming::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let url = format!("http://{}", incoming.local_addr().unwrap());
+        tokio::spawn(Server::builder().add_service(UsersServer::new(FakeUsers)).serve_with_incoming(incoming));
+
+        let (status, _) = search_at(&url, "q=zebra&type=posts").await;
+
+        assert_eq!(status, StatusCode::NOT_IMPLEMENTED);
+    }
+
+    #[actix_web::test]
+    async fn a_refused_query_fails_the_whole_search() {
+        // Users refuse the blank query while posts and comments fail as unimplemented.
+        let incoming = TcpIncoming::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let url = format!("http://{}", incoming.local_addr().unwrap());
+        tokio::spawn(Server::builder().add_service(UsersServer::new(FakeUsers)).serve_with_incoming(incoming));
+
+        let (status, body) = search_at(&url, "q=%20").await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["detail"], "Search query must not be empty");
+    }
+}
\ No newline at end of file
@@ -0,0 +1,56 @@
+use actix_web::{HttpResponse, Result, Scope, get, web};
+use tonic::Status;
+use utoipa::OpenApi;
+
+use crate::error::ApiError;
+use crate::domain::time::datetime_to_timestamp;
+use crate::dto::security_events_dto::{ListSecurityEventsQuery, ListSecurityEventsResponse};
+use crate::middleware::auth::AuthenticatedUser;
+use crate::{proto::auth, state::AppState};
+
+pub fn security_events_routes() -> Scope {
+    web::scope("/security-events")
+        .service(list_security_events)
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(list_security_events))]
+pub struct SecurityEventsApi;
+
+#[utoipa::path(
+    tag = "security-events",
+    summary = "List security events of an account, newest first",
+    params(ListSecurityEventsQuery),
+    responses((status = 200, description = "OK", body = ListSecurityEventsResponse)),
+    security(("bearer_token" = [])),
+)]
+#[get("")]
+async fn list_security_events(
+    state: web::Data<AppState>,
+    user: AuthenticatedUser,
+    query: web::Query<ListSecurityEventsQuery>,
+) -> Result<HttpResponse, ApiError> {
+    user.require_session()?;
+
+    let mut client = state.auth_client.clone();
+
+    let query = query.into_inner();
+
+    let request = auth::ListSecurityEventsRequest {
+        user_id: query.user_id.unwrap_or(user.user_id).to_string(),
+        requester_id: user.user_id.to_string(),
+        limit: query.limit.unwrap_or_default(),
+        before: query.before.map(datetime_to_timestamp),
+    };
+
+    let response = client
+        .list_security_events(tonic::Request::new(request))
+        .await?
+        .into_inner().events;
+
+    let http_response = ListSecurityEventsResponse {
+        events: response.into_iter().map(|e| e.try_into()).collect::<Result<_, Status>>()?
+    };
+
+    Ok(HttpResponse::Ok().json(http_response))
+}
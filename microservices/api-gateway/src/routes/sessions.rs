@@ -0,0 +1,91 @@
+use actix_web::{HttpResponse, Result, Scope, delete, get, web};
+use tonic::Status;
+use utoipa::OpenApi;
+
+use crate::error::ApiError;
+use crate::dto::sessions_dto::{ListSessionsResponse, RevokeSessionRequest, RevokeSessionResponse};
+use crate::middleware::auth::AuthenticatedUser;
+use crate::middleware::client_info::ClientInfo;
+use crate::{proto::auth, state::AppState};
+
+pub fn sessions_routes() -> Scope {
+    web::scope("/sessions")
+        .service(list_sessions)
+        .service(revoke_session)
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(list_sessions, revoke_session))]
+pub struct SessionsApi;
+
+#[utoipa::path(
+    tag = "sessions",
+    summary = "List active sessions of the caller",
+    responses((status = 200, description = "OK", body = ListSessionsResponse)),
+    security(("bearer_token" = [])),
+)]
+#[get("")]
+async fn list_sessions(
+    state: web::Data<AppState>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, ApiError> {
+    user.require_session()?;
+
+    let mut client = state.auth_client.clone();
+
+    let request = auth::ListSessionsRequest {
+        user_id: user.user_id.to_string(),
+        current_session_id: user.session_id
+            .map(|id| id.to_string())
+            .unwrap_or_default(),
+    };
+
+    let response = client
+        .list_sessions(tonic::Request::new(request))
+        .await?
+        .into_inner().sessions;
+
+    let http_response = ListSessionsResponse {
+        sessions: response.into_iter().map(|s| s.try_into()).collect::<Result<_, Status>>()?
+    };
+
+    Ok(HttpResponse::Ok().json(http_response))
+}
+
+#[utoipa::path(
+    tag = "sessions",
+    summary = "Revoke a session and its refresh token",
+    params(RevokeSessionRequest),
+    responses((status = 200, description = "OK", body = RevokeSessionResponse)),
+    security(("bearer_token" = [])),
+)]
+#[delete("/{id}")]
+async fn revoke_session(
+    state: web::Data<AppState>,
+    user: AuthenticatedUser,
+    client_info: ClientInfo,
+    id: web::Path<RevokeSessionRequest>,
+) -> Result<HttpResponse, ApiError> {
+    user.require_session()?;
+
+    let mut client = state.auth_client.clone();
+
+    let request = auth::RevokeSessionRequest {
+        id: id.into_inner().id.to_string(),
+        user_id: user.user_id.to_string(),
+    };
+
+    let response = client
+        .revoke_session(client_info.into_request(request))
+        .await?
+        .into_inner();
+
+    let session = response.session
+        .ok_or_else(|| ApiError::missing_field("session"))?;
+
+    let http_response = RevokeSessionResponse {
+        session: session.try_into()?,
+    };
+
+    Ok(HttpResponse::Ok().json(http_response))
+}
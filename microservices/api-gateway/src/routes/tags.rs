@@ -0,0 +1,95 @@
+use actix_web::{HttpResponse, Result, Scope, get, web};
+use tonic::Status;
+use utoipa::OpenApi;
+
+use crate::domain::time::datetime_to_timestamp;
+use crate::error::ApiError;
+use crate::dto::tags_dto::{ListPostsByTagPath, ListPostsByTagQuery, ListPostsByTagResponse, ListTrendingTagsQuery, ListTrendingTagsResponse};
+use crate::middleware::auth::{AuthenticatedUser, OptionalUser};
+use crate::{proto::posts, state::AppState};
+
+pub fn tags_routes() -> Scope {
+    web::scope("/tags")
+        .service(list_trending_tags)
+        .service(list_posts_by_tag)
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(list_trending_tags, list_posts_by_tag))]
+pub struct TagsApi;
+
+#[utoipa::path(
+    tag = "tags",
+    summary = "List the most used tags",
+    description = "Tags ranked by how many public posts using them were published within the last `hours`.",
+    params(ListTrendingTagsQuery),
+    responses((status = 200, description = "OK", body = ListTrendingTagsResponse)),
+)]
+#[get("/trending")]
+async fn list_trending_tags(
+    state: web::Data<AppState>,
+    query: web::Query<ListTrendingTagsQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let query = query.into_inner();
+
+    let request = posts::ListPopularTagsRequest {
+        hours: query.hours.unwrap_or_default(),
+        limit: query.limit.unwrap_or_default(),
+    };
+
+    let response = state.retry
+        .run(|| {
+            let mut client = state.posts_client.clone();
+            async move { client.list_popular_tags(tonic::Request::new(request)).await }
+        })
+        .await?
+        .into_inner().tags;
+
+    let http_response = ListTrendingTagsResponse {
+        tags: response.into_iter().map(Into::into).collect()
+    };
+
+    Ok(HttpResponse::Ok().json(http_response))
+}
+
+#[utoipa::path(
+    tag = "tags",
+    summary = "List posts with a tag, newest first",
+    description = "Only posts the caller may see, as with listing posts.",
+    params(ListPostsByTagPath, ListPostsByTagQuery),
+    responses((status = 200, description = "OK", body = ListPostsByTagResponse)),
+    security((), ("bearer_token" = [])),
+)]
+#[get("/{tag}/posts")]
+async fn list_posts_by_tag(
+    state: web::Data<AppState>,
+    OptionalUser(user): OptionalUser,
+    path: web::Path<ListPostsByTagPath>,
+    query: web::Query<ListPostsByTagQuery>,
+) -> Result<HttpResponse, ApiError> {
+    AuthenticatedUser::require_optional_scope(user.as_ref(), "posts:read")?;
+
+    let query = query.into_inner();
+
+    let request = posts::ListPostsByTagRequest {
+        tag: path.into_inner().tag,
+        requester_id: user.map(|user| user.user_id.to_string()).unwrap_or_default(),
+        limit: query.limit.unwrap_or_default(),
+        before: query.before.map(datetime_to_timestamp),
+    };
+
+    let response = state.retry
+        .run(|| {
+            let mut client = state.posts_client.clone();
+            let request = request.clone();
+            async move { client.list_posts_by_tag(tonic::Request::new(request)).await }
+        })
+        .await?
+        .into_inner().posts;
+
+    let http_response = ListPostsByTagResponse {
+        posts: response.into_iter().map(|p| p.try_into()).collect::<Result<_, Status>>()?
+    };
+
+    Ok(HttpResponse::Ok().json(http_response))
+}
@@ -0,0 +1,76 @@
+use actix_web::{HttpResponse, Result, Scope, delete, post, web};
+use utoipa::OpenApi;
+
+use crate::error::ApiError;
+use crate::dto::users_dto::{FollowUserRequest, UnfollowUserRequest};
+use crate::middleware::auth::AuthenticatedUser;
+use crate::{proto::users, state::AppState};
+
+pub fn users_routes() -> Scope {
+    web::scope("/users")
+        .service(follow_user)
+        .service(unfollow_user)
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(follow_user, unfollow_user))]
+pub struct UsersApi;
+
+#[utoipa::path(
+    tag = "users",
+    summary = "Follow a user",
+    description = "Lets the caller see the user's followers-only posts right away, there is no approval step. Following someone twice is not an error.",
+    params(FollowUserRequest),
+    responses((status = 204, description = "No Content")),
+    security(("bearer_token" = [])),
+)]
+#[post("/{id}/follow")]
+async fn follow_user(
+    state: web::Data<AppState>,
+    user: AuthenticatedUser,
+    id: web::Path<FollowUserRequest>,
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope("follows:write")?;
+
+    let mut client = state.users_client.clone();
+
+    let request = users::FollowUserRequest {
+        follower_id: user.user_id.to_string(),
+        followee_id: id.into_inner().id.to_string(),
+    };
+
+    client
+        .follow_user(tonic::Request::new(request))
+        .await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[utoipa::path(
+    tag = "users",
+    summary = "Unfollow a user",
+    params(UnfollowUserRequest),
+    responses((status = 204, description = "No Content")),
+    security(("bearer_token" = [])),
+)]
+#[delete("/{id}/follow")]
+async fn unfollow_user(
+    state: web::Data<AppState>,
+    user: AuthenticatedUser,
+    id: web::Path<UnfollowUserRequest>,
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope("follows:write")?;
+
+    let mut client = state.users_client.clone();
+
+    let request = users::UnfollowUserRequest {
+        follower_id: user.user_id.to_string(),
+        followee_id: id.into_inner().id.to_string(),
+    };
+
+    client
+        .unfollow_user(tonic::Request::new(request))
+        .await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
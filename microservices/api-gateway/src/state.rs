@@ -1,40 +1,75 @@
-use tonic::transport::Channel;
 use crate::config::Config;
+use crate::grpc::{ResilientChannel, RetryPolicy};
 
 
 // ------------- Including proto -------------
 use crate::proto::auth::auth_client::AuthClient;
-use crate::proto::comments::comments_client::{self, CommentsClient};
+use crate::proto::comments::comments_client::CommentsClient;
 use crate::proto::posts::posts_client::PostsClient;
 use crate::proto::users::users_client::UsersClient;
+use tonic_health::pb::health_client::HealthClient;
 
 
 #[derive(Clone)]
 pub struct AppState {
-    pub auth_client: AuthClient<Channel>,
-    pub users_client: UsersClient<Channel>,
-    pub posts_client: PostsClient<Channel>,
-    pub comments_client: CommentsClient<Channel>,
+    pub auth_client: AuthClient<ResilientChannel>,
+    pub users_client: UsersClient<ResilientChannel>,
+    pub posts_client: PostsClient<ResilientChannel>,
+    pub comments_client: CommentsClient<ResilientChannel>,
+    pub retry: RetryPolicy,
+    // Shares each service's channel, so probes see the same connection and circuit as real traffic.
+    pub health_clients: Vec<(&'static str, HealthClient<ResilientChannel>)>,
 }
 
 impl AppState {
-    pub async fn new(config: &Config) -> Result<Self, Box<dyn std::error::Error>> {
-        let auth_channel = Channel::from_shared(config.auth_service_url.clone())?
-                .connect().await?;
-        let users_channel = Channel::from_shared(config.users_service_url.clone())?
-            .connect().await?;
-        let posts_channel = Channel::from_shared(config.posts_service_url.clone())?
-            .connect().await?;
-        let comments_channel = Channel::from_shared(config.comments_service_url.clone())?
-            .connect().await?;
+    pub fn new(config: &Config) -> Result<Self, tonic::transport::Error> {
+        let grpc = &config.grpc;
+
+        let auth_channel = ResilientChannel::new("auth-service", config.auth_service_url.clone(), grpc)?;
+        let users_channel = ResilientChannel::new("users-service", config.users_service_url.clone(), grpc)?;
+        let posts_channel = ResilientChannel::new("posts-service", config.posts_service_url.clone(), grpc)?;
+        let comments_channel = ResilientChannel::new("comments-service", config.comments_service_url.clone(), grpc)?;
 
         let state = Self {
+            health_clients: vec![
+                ("auth-service", HealthClient::new(auth_channel.clone())),
+                ("users-service", HealthClient::new(users_channel.clone())),
+                ("posts-service", HealthClient::new(posts_channel.clone())),
+                ("comments-service", HealthClient::new(comments_channel.clone())),
+            ],
             auth_client: AuthClient::new(auth_channel),
             users_client: UsersClient::new(users_channel),
             posts_client: PostsClient::new(posts_channel),
             comments_client: CommentsClient::new(comments_channel),
+            retry: RetryPolicy::new(grpc),
         };
 
         Ok(state)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+impl AppState {
+    // Every client talks to `url` with short timeouts and no retries, for tests that stand up one
+    // fake service or none at all.
+    pub fn for_tests(url: &str) -> Self {
+        let config = crate::config::GrpcClientConfig {
+            connect_timeout: std::time::Duration::from_millis(200),
+            request_timeout: std::time::Duration::from_millis(200),
+            retry_attempts: 0,
+            retry_backoff: std::time::Duration::ZERO,
+            failure_threshold: 100,
+            open_duration: std::time::Duration::from_secs(60),
+        };
+        let channel = ResilientChannel::new("test", url.to_string(), &config).unwrap();
+
+        Self {
+            health_clients: vec![("test", HealthClient::new(channel.clone()))],
+            auth_client: AuthClient::new(channel.clone()),
+            users_client: UsersClient::new(channel.clone()),
+            posts_client: PostsClient::new(channel.clone()),
+            comments_client: CommentsClient::new(channel),
+            retry: RetryPolicy::new(&config),
+        }
+    }
+}
@@ -0,0 +1,33 @@
+use std::{io, sync::Arc};
+
+use rustls::ServerConfig;
+use rustls_pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject};
+
+use crate::config::TlsConfig;
+
+fn invalid_data(path: &str, error: impl std::fmt::Display) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("{path}: {error}"))
+}
+
+// Loads a PEM certificate chain and private key, the key may be PKCS#1, PKCS#8 or SEC1.
+// actix adds the h2 and http/1.1 ALPN protocols on bind.
+pub fn load_server_config(tls: &TlsConfig) -> io::Result<ServerConfig> {
+    let certs = CertificateDer::pem_file_iter(&tls.cert_path)
+        .map_err(|err| invalid_data(&tls.cert_path, err))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| invalid_data(&tls.cert_path, err))?;
+
+    if certs.is_empty() {
+        return Err(invalid_data(&tls.cert_path, "no certificates found"));
+    }
+
+    let key = PrivateKeyDer::from_pem_file(&tls.key_path)
+        .map_err(|err| invalid_data(&tls.key_path, err))?;
+
+    ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .map_err(|err| invalid_data(&tls.cert_path, err))?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|err| invalid_data(&tls.key_path, err))
+}
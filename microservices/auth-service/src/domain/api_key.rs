@@ -0,0 +1,61 @@
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+
+use crate::domain::digest::sha256_hex;
+
+// Every key starts with this marker, which lets `authenticate` tell keys from JWTs.
+pub const API_KEY_PREFIX: &str = "snk_";
+
+// Length of the part of the key that is stored in plain text for display.
+const DISPLAY_PREFIX_LEN: usize = API_KEY_PREFIX.len() + 8;
+
+pub struct GeneratedApiKey {
+    pub key: String,
+    pub prefix: String,
+    pub hash: String,
+}
+
+pub fn is_api_key(token: &str) -> bool {
+    token.starts_with(API_KEY_PREFIX)
+}
+
+// Keys carry 256 bits of randomness, so a fast digest is enough to store them.
+pub fn hash_api_key(key: &str) -> String {
+    sha256_hex(key)
+}
+
+pub fn generate_api_key() -> GeneratedApiKey {
+    let mut secret = [0u8; 32];
+    OsRng.fill_bytes(&mut secret);
+
+    let key = format!("{}{}", API_KEY_PREFIX, hex::encode(secret));
+
+    GeneratedApiKey {
+        prefix: key[..DISPLAY_PREFIX_LEN].to_string(),
+        hash: hash_api_key(&key),
+        key,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_keys_are_random_and_stored_only_as_a_hash() {
+        let first = generate_api_key();
+        let second = generate_api_key();
+
+        assert_ne!(first.key, second.key);
+        assert!(is_api_key(&first.key));
+        assert_eq!(first.key.len(), API_KEY_PREFIX.len() + 64);
+        assert!(first.key.starts_with(&first.prefix));
+        assert_eq!(first.prefix.len(), DISPLAY_PREFIX_LEN);
+        assert_eq!(first.hash, hash_api_key(&first.key));
+        assert_ne!(first.hash, first.key);
+    }
+
+    #[test]
+    fn jwts_are_not_api_keys() {
+        assert!(!is_api_key("eyJhbGciOiJIUzI1NiJ9.e30.sig"));
+    }
+}
@@ -0,0 +1,31 @@
+use tonic::metadata::MetadataMap;
+
+// Metadata keys the api gateway uses to forward details about the end client.
+pub const CLIENT_IP_KEY: &str = "x-client-ip";
+pub const CLIENT_USER_AGENT_KEY: &str = "x-client-user-agent";
+pub const CLIENT_COUNTRY_KEY: &str = "x-client-country";
+
+#[derive(Debug, Clone, Default)]
+pub struct ClientInfo {
+    pub ip_address: String,
+    pub user_agent: String,
+    // ISO country code, empty when the edge in front of the gateway does not provide one.
+    pub country: String,
+}
+
+impl ClientInfo {
+    pub fn from_metadata(metadata: &MetadataMap) -> Self {
+        let get = |key: &str| {
+            metadata.get(key)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default()
+                .to_string()
+        };
+
+        Self {
+            ip_address: get(CLIENT_IP_KEY),
+            user_agent: get(CLIENT_USER_AGENT_KEY),
+            country: get(CLIENT_COUNTRY_KEY),
+        }
+    }
+}
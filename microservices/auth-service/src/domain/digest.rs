@@ -0,0 +1,6 @@
+use sha2::{Digest, Sha256};
+
+// Used for high-entropy secrets (api keys, refresh tokens) that are looked up by their hash.
+pub fn sha256_hex(value: &str) -> String {
+    hex::encode(Sha256::digest(value.as_bytes()))
+}
@@ -0,0 +1,59 @@
+use chrono::Duration;
+
+use crate::domain::digest::sha256_hex;
+
+pub const MAGIC_LINK_TTL: Duration = Duration::minutes(15);
+
+// Per `MAGIC_LINK_RATE_WINDOW`, at most `MAGIC_LINK_RATE_LIMIT` requests for one address and
+// `MAGIC_LINK_IP_RATE_LIMIT` from one ip. Requests count whether or not the address has an account.
+pub const MAGIC_LINK_RATE_LIMIT: i64 = 3;
+pub const MAGIC_LINK_IP_RATE_LIMIT: i64 = 10;
+pub const MAGIC_LINK_RATE_WINDOW: Duration = Duration::minutes(15);
+
+// Requests seen in the current window, the one being decided on not included.
+#[derive(Debug, Default)]
+pub struct MagicLinkRequestCounts {
+    pub by_email: i64,
+    pub by_ip: i64,
+}
+
+impl MagicLinkRequestCounts {
+    pub fn exceeded(&self) -> bool {
+        self.by_email >= MAGIC_LINK_RATE_LIMIT || self.by_ip >= MAGIC_LINK_IP_RATE_LIMIT
+    }
+}
+
+// Rate limit key of an address, `Jane@Example.com` and `jane@example.com` share one.
+pub fn email_hash(email: &str) -> String {
+    sha256_hex(&email.trim().to_lowercase())
+}
+
+pub fn magic_link_url(base_url: &str, token: &str) -> String {
+    let separator = if base_url.contains('?') { '&' } else { '?' };
+
+    format!("{base_url}{separator}token={token}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limit_applies_per_address_and_per_ip() {
+        assert!(!MagicLinkRequestCounts { by_email: 2, by_ip: 9 }.exceeded());
+        assert!(MagicLinkRequestCounts { by_email: 3, by_ip: 0 }.exceeded());
+        assert!(MagicLinkRequestCounts { by_email: 0, by_ip: 10 }.exceeded());
+    }
+
+    #[test]
+    fn email_hash_ignores_case_and_whitespace() {
+        assert_eq!(email_hash(" Jane@Example.com"), email_hash("jane@example.com"));
+        assert_ne!(email_hash("jane@example.com"), email_hash("john@example.com"));
+    }
+
+    #[test]
+    fn magic_link_url_appends_the_token() {
+        assert_eq!(magic_link_url("https://app.example.com/magic", "abc"), "https://app.example.com/magic?token=abc");
+        assert_eq!(magic_link_url("https://app.example.com/magic?next=/", "abc"), "https://app.example.com/magic?next=/&token=abc");
+    }
+}
@@ -1,2 +1,9 @@
+pub mod api_key;
+pub mod client;
+pub mod digest;
+pub mod magic_link;
 pub mod password;
+pub mod pkce;
+pub mod security;
+pub mod time;
 pub mod token;
\ No newline at end of file
@@ -1,9 +1,16 @@
+use std::sync::LazyLock;
+
 use argon2::{
     Argon2, password_hash::{
         PasswordHash, PasswordHasher, PasswordVerifier, SaltString, rand_core::OsRng
     }
 };
 
+// Checked against when there's no account to check, see `sign_in`.
+pub static DUMMY_PASSWORD_HASH: LazyLock<String> = LazyLock::new(|| {
+    hash_password("not a password".to_string()).unwrap_or_default()
+});
+
 // Password hashing
 pub fn hash_password(password: String) -> Result<String, String> {
     let salt = SaltString::generate(&mut OsRng);
@@ -24,4 +31,23 @@ pub fn verify_password(password: String, hash: String) -> Result<(), String> {
         Ok(_) => Ok(()),
         Err(_e) => Err("Failed to verify password".to_string()),
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_password_verifies_only_against_its_own_hash() {
+        let hash = hash_password("Passw0rd".to_string()).unwrap();
+
+        assert!(verify_password("Passw0rd".to_string(), hash.clone()).is_ok());
+        assert!(verify_password("passw0rd".to_string(), hash).is_err());
+    }
+
+    #[test]
+    fn the_dummy_hash_is_a_real_hash_that_matches_no_password() {
+        assert!(PasswordHash::new(&DUMMY_PASSWORD_HASH).is_ok());
+        assert!(verify_password("Passw0rd".to_string(), DUMMY_PASSWORD_HASH.clone()).is_err());
+    }
 }
\ No newline at end of file
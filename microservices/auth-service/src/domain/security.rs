@@ -0,0 +1,103 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SecurityEventType {
+    SignUp,
+    SignInSucceeded,
+    SignInFailed,
+    TokenRefreshed,
+    SessionRevoked,
+    ApiKeyCreated,
+    ApiKeyRevoked,
+    SuspiciousSignIn,
+}
+
+impl SecurityEventType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::SignUp => "sign_up",
+            Self::SignInSucceeded => "sign_in_succeeded",
+            Self::SignInFailed => "sign_in_failed",
+            Self::TokenRefreshed => "token_refreshed",
+            Self::SessionRevoked => "session_revoked",
+            Self::ApiKeyCreated => "api_key_created",
+            Self::ApiKeyRevoked => "api_key_revoked",
+            Self::SuspiciousSignIn => "suspicious_sign_in",
+        }
+    }
+}
+
+// Why a successful sign in looks unusual compared to the account's history.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SuspiciousReason {
+    NewDevice,
+    NewCountry,
+}
+
+impl SuspiciousReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::NewDevice => "new_device",
+            Self::NewCountry => "new_country",
+        }
+    }
+
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Self::NewDevice => "a device you have not used before",
+            Self::NewCountry => "a country you have not signed in from before",
+        }
+    }
+}
+
+// Previous successful sign ins of an account, used to spot unusual ones.
+#[derive(Debug, Default)]
+pub struct SignInHistory {
+    pub previous: i64,
+    pub device_seen: bool,
+    pub country_seen: bool,
+}
+
+impl SignInHistory {
+    pub fn suspicious_reasons(&self, country: &str) -> Vec<SuspiciousReason> {
+        let mut reasons = vec![];
+
+        // The very first sign in has nothing to be compared with.
+        if self.previous == 0 {
+            return reasons;
+        }
+
+        if !self.device_seen {
+            reasons.push(SuspiciousReason::NewDevice);
+        }
+        if !country.is_empty() && !self.country_seen {
+            reasons.push(SuspiciousReason::NewCountry);
+        }
+
+        reasons
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_sign_in_is_never_suspicious() {
+        let history = SignInHistory::default();
+
+        assert!(history.suspicious_reasons("NL").is_empty());
+    }
+
+    #[test]
+    fn new_device_and_country_are_suspicious() {
+        let history = SignInHistory { previous: 3, device_seen: false, country_seen: false };
+
+        assert_eq!(history.suspicious_reasons("NL"), vec![SuspiciousReason::NewDevice, SuspiciousReason::NewCountry]);
+    }
+
+    #[test]
+    fn unknown_country_is_not_a_new_one() {
+        let history = SignInHistory { previous: 3, device_seen: true, country_seen: false };
+
+        assert!(history.suspicious_reasons("").is_empty());
+    }
+}
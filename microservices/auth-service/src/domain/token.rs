@@ -2,21 +2,34 @@ use std::env;
 
 use serde::{ Serialize, Deserialize };
 use jsonwebtoken::{ DecodingKey, EncodingKey, Header, Validation, decode, encode, errors::Error };
-use chrono::{ Duration, Utc };
+use chrono::{ DateTime, Duration, Utc };
+use uuid::Uuid;
 
 #[derive(Clone)]
 pub struct Payload {
     pub sub: String,
     pub username: String,
+    pub sid: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TokenClaims {
     pub sub: String,
     pub username: String,
+    // Session id, missing from tokens issued before sessions were tracked.
+    #[serde(default)]
+    pub sid: Option<String>,
+    // Unique per refresh token, two refreshes within one second would otherwise issue the
+    // same token and a rotated token would still match its session.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jti: Option<String>,
     pub exp: i64,
 }
 
+pub fn refresh_token_expiration() -> DateTime<Utc> {
+    Utc::now() + Duration::days(30)
+}
+
 pub fn generate_access_token(claims: Payload) -> Result<String, Error> {
     let access_secret: String = env::var("JWT_ACCESS_TOKEN_SECRET")
         .unwrap_or_else(|_| "H7GF8FGG6D".to_string())
@@ -29,6 +42,8 @@ pub fn generate_access_token(claims: Payload) -> Result<String, Error> {
     let token_claims = TokenClaims {
         sub: claims.sub,
         username: claims.username,
+        sid: Some(claims.sid),
+        jti: None,
         exp: expiration,
     };
 
@@ -44,13 +59,13 @@ pub fn generate_refresh_token(claims: Payload) -> Result<String, Error> {
         .unwrap_or_else(|_| "FGJD86DSGF".to_string())
         .to_string();
 
-    let expiration = (
-        Utc::now() + Duration::days(30)
-    ).timestamp();
+    let expiration = refresh_token_expiration().timestamp();
 
     let token_claims = TokenClaims {
         sub: claims.sub,
         username: claims.username,
+        sid: Some(claims.sid),
+        jti: Some(Uuid::new_v4().to_string()),
         exp: expiration,
     };
 
@@ -92,4 +107,21 @@ pub fn generate_tokens(claims: Payload) -> Result<(String, String), Box<dyn std:
     let refresh_token = generate_refresh_token(claims.clone())?;
 
     Ok((access_token, refresh_token))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refresh_tokens_issued_together_differ() {
+        let payload = Payload { sub: "user".to_string(), username: "alice".to_string(), sid: "session".to_string() };
+
+        let first = generate_refresh_token(payload.clone()).unwrap();
+        let second = generate_refresh_token(payload).unwrap();
+        assert_ne!(first, second);
+
+        let claims = verify_refresh_token(first).unwrap();
+        assert_eq!(claims.sid.as_deref(), Some("session"));
+    }
 }
\ No newline at end of file
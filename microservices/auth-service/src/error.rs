@@ -1,4 +1,7 @@
 use thiserror::Error;
+use tonic::Status;
+
+use crate::oidc::OidcError;
 
 #[derive(Debug, Error)]
 pub enum AuthError {
@@ -13,4 +16,85 @@ pub enum AuthError {
 
     // #[error("Password hashing error")]
     // PasswordHashingError,
+}
+
+#[derive(Debug, Error)]
+pub enum RepositoryError {
+    #[error("database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+
+    #[error("Invalid UUID: {0}")]
+    InvalidUUID(#[from] uuid::Error),
+
+    #[error("Api key not found")]
+    ApiKeyNotFound,
+
+    #[error("Session not found")]
+    SessionNotFound,
+
+    #[error("Sign in state not found or expired")]
+    OidcStateNotFound,
+
+    #[error("Magic link not found, expired or already used")]
+    MagicLinkNotFound,
+}
+
+pub fn map_repo_err(err: RepositoryError) -> Status {
+    match err {
+        RepositoryError::ApiKeyNotFound => {
+            Status::not_found("api key not found")
+        },
+        RepositoryError::SessionNotFound => {
+            Status::not_found("session not found")
+        },
+        RepositoryError::OidcStateNotFound => {
+            Status::invalid_argument("sign in state not found or expired")
+        },
+        RepositoryError::MagicLinkNotFound => {
+            Status::unauthenticated("magic link is invalid or has expired")
+        },
+        RepositoryError::InvalidUUID(_) => {
+            Status::invalid_argument("invalid id")
+        },
+        RepositoryError::DatabaseError(_) => {
+            Status::internal("internal server error")
+        }
+    }
+}
+
+pub fn map_oidc_err(err: OidcError) -> Status {
+    match err {
+        OidcError::UnknownProvider => {
+            Status::not_found("unknown sign in provider")
+        },
+        OidcError::Http(_) | OidcError::InvalidUrl(_) => {
+            Status::unavailable("sign in provider is unavailable")
+        },
+        OidcError::InvalidIdToken(_) => {
+            Status::unauthenticated("sign in with provider failed")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tonic::Code;
+
+    use super::*;
+
+    #[test]
+    fn repository_errors_map_to_codes() {
+        let cases = [
+            (RepositoryError::ApiKeyNotFound, Code::NotFound),
+            (RepositoryError::SessionNotFound, Code::NotFound),
+            (RepositoryError::OidcStateNotFound, Code::InvalidArgument),
+            (RepositoryError::MagicLinkNotFound, Code::Unauthenticated),
+            (RepositoryError::InvalidUUID("x".parse::<uuid::Uuid>().unwrap_err()), Code::InvalidArgument),
+            (RepositoryError::DatabaseError(sqlx::Error::RowNotFound), Code::Internal),
+        ];
+
+        for (err, code) in cases {
+            assert_eq!(map_repo_err(err).code(), code);
+        }
+    }
 }
\ No newline at end of file
@@ -1,6 +1,13 @@
+use std::sync::Arc;
+
+use sqlx::postgres::PgPoolOptions;
 use tonic::{ transport::Server };
 use crate::config::Config;
+use crate::mailer::mailer_from_config;
+use crate::oidc::OidcClient;
 use crate::proto::auth::auth_server::{ AuthServer };
+use crate::repository::AuthRepository;
+use crate::security_hooks::{EmailSecurityHook, SecurityHook, WebhookSecurityHook};
 use crate::service::AuthService;
 
 pub mod service;
@@ -10,22 +17,60 @@ pub mod config;
 pub mod error;
 pub mod validation;
 pub mod model;
+pub mod repository;
+pub mod oidc;
+pub mod mailer;
+pub mod security_hooks;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     dotenvy::dotenv().ok();
 
+    let _tracer_provider = common::telemetry::init("auth-service")?;
+
     let config = Config::from_env();
 
     let addr = config.microservice_url.parse()?;
 
+    common::metrics::install(config.metrics_url.parse()?, "auth-service")?;
+
+    let db = PgPoolOptions::new()
+        .max_connections(5)
+        .connect(&config.database_url)
+        .await?;
+
+    let (health_reporter, health_service) = tonic_health::server::health_reporter();
+    tokio::spawn(common::metrics::watch_pool(db.clone()));
+    tokio::spawn(common::health::watch_database::<AuthServer<AuthService>>(health_reporter, db.clone()));
+
+    let repository = AuthRepository::new(db);
+    let oidc = OidcClient::new(config.oidc_providers);
+    let mailer = mailer_from_config(config.smtp_url.as_deref(), &config.mail_from)?;
+
+    let mut security_hooks: Vec<Arc<dyn SecurityHook>> = vec![
+        Arc::new(EmailSecurityHook::new(mailer.clone())),
+    ];
+    if let Some(url) = config.security_webhook_url {
+        security_hooks.push(Arc::new(WebhookSecurityHook::new(url)));
+    }
+
     let service = AuthService::new(
-        config.users_service_url
+        config.users_service_url,
+        repository,
+        oidc,
+        mailer,
+        config.magic_link_url,
+        security_hooks,
+        config.admin_user_ids,
     ).await?;
 
-    println!("Auth service listening on {}", addr);
+    tracing::info!("Auth service listening on {}", addr);
 
     Server::builder()
+        .trace_fn(common::telemetry::request_span)
+        .layer(common::metrics::GrpcMetricsLayer)
+        .layer(common::telemetry::RequestIdLayer)
+        .add_service(health_service)
         .add_service(AuthServer::new(service))
         .serve(addr)
         .await?;
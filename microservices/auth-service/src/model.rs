@@ -1,4 +1,9 @@
-use crate::proto::{auth::{SignUpRequest}, users::CreateUserRequest};
+use chrono::{DateTime, Utc};
+use sqlx::prelude::FromRow;
+use uuid::Uuid;
+
+use crate::domain::time::datetime_to_timestamp;
+use crate::proto::{auth::{self, ListApiKeysRequest, ListSecurityEventsRequest, ListSessionsRequest, RevokeApiKeyRequest, RevokeSessionRequest, SignUpRequest}, users::CreateUserRequest};
 
 impl From<SignUpRequest> for CreateUserRequest {
     fn from(value: SignUpRequest) -> Self {
@@ -8,4 +13,266 @@ impl From<SignUpRequest> for CreateUserRequest {
             password: value.password,
         }
     }
+}
+
+// --------------------
+
+#[derive(Debug, FromRow)]
+pub struct ApiKey {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub prefix: String,
+    pub scopes: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl From<ApiKey> for auth::ApiKey {
+    fn from(value: ApiKey) -> Self {
+        Self {
+            id: value.id.to_string(),
+            user_id: value.user_id.to_string(),
+            name: value.name,
+            prefix: value.prefix,
+            scopes: value.scopes,
+            created_at: datetime_to_timestamp(value.created_at),
+            last_used_at: value.last_used_at.and_then(datetime_to_timestamp),
+            revoked_at: value.revoked_at.and_then(datetime_to_timestamp),
+        }
+    }
+}
+
+// Owner of an active key, resolved while authenticating a request.
+#[derive(Debug, FromRow)]
+pub struct ApiKeyOwner {
+    pub user_id: Uuid,
+    pub username: String,
+    pub scopes: Vec<String>,
+}
+
+// --------------------
+
+pub struct CreateApiKeyRepo {
+    pub user_id: Uuid,
+    pub name: String,
+    pub prefix: String,
+    pub key_hash: String,
+    pub scopes: Vec<String>,
+}
+
+// --------------------
+
+pub struct ListApiKeysRepo {
+    pub user_id: Uuid,
+}
+
+impl TryFrom<ListApiKeysRequest> for ListApiKeysRepo {
+    type Error = uuid::Error;
+
+    fn try_from(value: ListApiKeysRequest) -> Result<Self, Self::Error> {
+        Ok(Self {
+            user_id: Uuid::parse_str(&value.user_id)?,
+        })
+    }
+}
+
+// --------------------
+
+pub struct RevokeApiKeyRepo {
+    pub id: Uuid,
+    pub user_id: Uuid,
+}
+
+impl TryFrom<RevokeApiKeyRequest> for RevokeApiKeyRepo {
+    type Error = uuid::Error;
+
+    fn try_from(value: RevokeApiKeyRequest) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: Uuid::parse_str(&value.id)?,
+            user_id: Uuid::parse_str(&value.user_id)?,
+        })
+    }
+}
+
+// --------------------
+
+#[derive(Debug, FromRow)]
+pub struct Session {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub user_agent: String,
+    pub ip_address: String,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl From<Session> for auth::Session {
+    fn from(value: Session) -> Self {
+        Self {
+            id: value.id.to_string(),
+            user_id: value.user_id.to_string(),
+            user_agent: value.user_agent,
+            ip_address: value.ip_address,
+            current: false,
+            created_at: datetime_to_timestamp(value.created_at),
+            last_used_at: datetime_to_timestamp(value.last_used_at),
+            expires_at: datetime_to_timestamp(value.expires_at),
+        }
+    }
+}
+
+// --------------------
+
+pub struct CreateSessionRepo {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub refresh_token_hash: String,
+    pub user_agent: String,
+    pub ip_address: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+// --------------------
+
+pub struct RotateSessionRepo {
+    pub id: Uuid,
+    pub refresh_token_hash: String,
+    pub new_refresh_token_hash: String,
+    pub user_agent: String,
+    pub ip_address: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+// --------------------
+
+pub struct ListSessionsRepo {
+    pub user_id: Uuid,
+}
+
+impl TryFrom<&ListSessionsRequest> for ListSessionsRepo {
+    type Error = uuid::Error;
+
+    fn try_from(value: &ListSessionsRequest) -> Result<Self, Self::Error> {
+        Ok(Self {
+            user_id: Uuid::parse_str(&value.user_id)?,
+        })
+    }
+}
+
+// --------------------
+
+pub struct RevokeSessionRepo {
+    pub id: Uuid,
+    pub user_id: Uuid,
+}
+
+impl TryFrom<RevokeSessionRequest> for RevokeSessionRepo {
+    type Error = uuid::Error;
+
+    fn try_from(value: RevokeSessionRequest) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: Uuid::parse_str(&value.id)?,
+            user_id: Uuid::parse_str(&value.user_id)?,
+        })
+    }
+}
+
+// --------------------
+
+#[derive(Debug, FromRow)]
+pub struct OidcState {
+    pub provider: String,
+    pub code_verifier: String,
+    pub nonce: String,
+}
+
+pub struct CreateOidcStateRepo {
+    pub state: String,
+    pub provider: String,
+    pub code_verifier: String,
+    pub nonce: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+// --------------------
+
+pub struct LinkExternalIdentityRepo {
+    pub user_id: Uuid,
+    pub provider: String,
+    pub subject: String,
+    pub email: Option<String>,
+}
+
+// --------------------
+
+pub struct CreateMagicLinkRepo {
+    pub user_id: Uuid,
+    pub email: String,
+    pub token_hash: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+// --------------------
+
+#[derive(Debug, FromRow)]
+pub struct SecurityEvent {
+    pub id: Uuid,
+    pub user_id: Option<Uuid>,
+    pub event_type: String,
+    pub ip_address: String,
+    pub user_agent: String,
+    pub country: String,
+    pub details: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<SecurityEvent> for auth::SecurityEvent {
+    fn from(value: SecurityEvent) -> Self {
+        Self {
+            id: value.id.to_string(),
+            user_id: value.user_id.map(|id| id.to_string()).unwrap_or_default(),
+            event_type: value.event_type,
+            ip_address: value.ip_address,
+            user_agent: value.user_agent,
+            country: value.country,
+            details: value.details.to_string(),
+            created_at: datetime_to_timestamp(value.created_at),
+        }
+    }
+}
+
+pub struct CreateSecurityEventRepo {
+    pub user_id: Option<Uuid>,
+    pub event_type: String,
+    pub ip_address: String,
+    pub user_agent: String,
+    pub country: String,
+    pub details: serde_json::Value,
+}
+
+// --------------------
+
+pub struct ListSecurityEventsRepo {
+    pub user_id: Uuid,
+    pub limit: i64,
+    pub before: Option<DateTime<Utc>>,
+}
+
+impl TryFrom<&ListSecurityEventsRequest> for ListSecurityEventsRepo {
+    type Error = uuid::Error;
+
+    fn try_from(value: &ListSecurityEventsRequest) -> Result<Self, Self::Error> {
+        Ok(Self {
+            user_id: Uuid::parse_str(&value.user_id)?,
+            limit: match value.limit {
+                1..=100 => value.limit as i64,
+                _ => 50,
+            },
+            before: value.before
+                .and_then(|ts| DateTime::from_timestamp(ts.seconds, ts.nanos as u32)),
+        })
+    }
 }
\ No newline at end of file
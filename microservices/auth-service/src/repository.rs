@@ -0,0 +1,767 @@
+use chrono::{DateTime, Utc};
+use sqlx::{Pool, Postgres};
+use uuid::Uuid;
+
+use crate::error::RepositoryError;
+use crate::domain::magic_link::MagicLinkRequestCounts;
+use crate::domain::security::{SecurityEventType, SignInHistory};
+use crate::model::{ApiKey, ApiKeyOwner, CreateApiKeyRepo, CreateMagicLinkRepo, CreateOidcStateRepo, CreateSecurityEventRepo, CreateSessionRepo, LinkExternalIdentityRepo, ListSecurityEventsRepo, OidcState, SecurityEvent, ListApiKeysRepo, ListSessionsRepo, RevokeApiKeyRepo, RevokeSessionRepo, RotateSessionRepo, Session};
+use crate::proto::auth::{ListApiKeysRequest, ListSecurityEventsRequest, ListSessionsRequest, RevokeApiKeyRequest, RevokeSessionRequest};
+
+#[derive(Debug, Clone)]
+pub struct AuthRepository {
+    db: Pool<Postgres>
+}
+
+impl AuthRepository {
+    pub fn new(db: Pool<Postgres>) -> Self {
+        Self { db }
+    }
+
+    pub async fn create_api_key(
+        &self,
+        value: CreateApiKeyRepo,
+    ) -> Result<ApiKey, RepositoryError> {
+        let CreateApiKeyRepo { user_id, name, prefix, key_hash, scopes } = value;
+
+        let api_key = sqlx::query_as!(
+            ApiKey,
+            r#"
+            INSERT INTO api_keys (user_id, name, prefix, key_hash, scopes)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING id, user_id, name, prefix, scopes, created_at, last_used_at, revoked_at
+            "#,
+            user_id,
+            name,
+            prefix,
+            key_hash,
+            &scopes,
+        )
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(api_key)
+    }
+
+    pub async fn list_api_keys(
+        &self,
+        value: ListApiKeysRequest,
+    ) -> Result<Vec<ApiKey>, RepositoryError> {
+        let ListApiKeysRepo { user_id } = value.try_into()?;
+
+        let api_keys = sqlx::query_as!(
+            ApiKey,
+            r#"
+            SELECT id, user_id, name, prefix, scopes, created_at, last_used_at, revoked_at
+            FROM api_keys
+            WHERE user_id = $1
+            ORDER BY created_at DESC
+            "#,
+            user_id
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(api_keys)
+    }
+
+    pub async fn revoke_api_key(
+        &self,
+        value: RevokeApiKeyRequest,
+    ) -> Result<ApiKey, RepositoryError> {
+        let RevokeApiKeyRepo { id, user_id } = value.try_into()?;
+
+        let api_key = sqlx::query_as!(
+            ApiKey,
+            r#"
+            UPDATE api_keys
+            SET revoked_at = COALESCE(revoked_at, now())
+            WHERE id = $1 AND user_id = $2
+            RETURNING id, user_id, name, prefix, scopes, created_at, last_used_at, revoked_at
+            "#,
+            id,
+            user_id
+        )
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or(RepositoryError::ApiKeyNotFound)?;
+
+        Ok(api_key)
+    }
+
+    // Resolves an active key by its hash and records that it was used, at most once a minute
+    // so a busy bot doesn't write on every request.
+    pub async fn use_api_key(
+        &self,
+        key_hash: &str,
+    ) -> Result<ApiKeyOwner, RepositoryError> {
+        let owner = sqlx::query_as!(
+            ApiKeyOwner,
+            r#"
+            WITH key AS (
+                SELECT api_keys.id, api_keys.user_id, users.username, api_keys.scopes
+                FROM api_keys
+                JOIN users ON users.id = api_keys.user_id
+                WHERE api_keys.key_hash = $1 AND api_keys.revoked_at IS NULL
+            ), touched AS (
+                UPDATE api_keys
+                SET last_used_at = now()
+                FROM key
+                WHERE api_keys.id = key.id
+                    AND (api_keys.last_used_at IS NULL OR api_keys.last_used_at < now() - interval '1 minute')
+            )
+            SELECT user_id AS "user_id!", username AS "username!", scopes AS "scopes!"
+            FROM key
+            "#,
+            key_hash
+        )
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or(RepositoryError::ApiKeyNotFound)?;
+
+        Ok(owner)
+    }
+
+    pub async fn create_session(
+        &self,
+        value: CreateSessionRepo,
+    ) -> Result<Session, RepositoryError> {
+        let CreateSessionRepo { id, user_id, refresh_token_hash, user_agent, ip_address, expires_at } = value;
+
+        let session = sqlx::query_as!(
+            Session,
+            r#"
+            INSERT INTO sessions (id, user_id, refresh_token_hash, user_agent, ip_address, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id, user_id, user_agent, ip_address, created_at, last_used_at, expires_at
+            "#,
+            id,
+            user_id,
+            refresh_token_hash,
+            user_agent,
+            ip_address,
+            expires_at,
+        )
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(session)
+    }
+
+    // Swaps the refresh token of an active session, so every refresh token can be used only once.
+    pub async fn rotate_session(
+        &self,
+        value: RotateSessionRepo,
+    ) -> Result<Session, RepositoryError> {
+        let RotateSessionRepo {
+            id,
+            refresh_token_hash,
+            new_refresh_token_hash,
+            user_agent,
+            ip_address,
+            expires_at,
+        } = value;
+
+        let session = sqlx::query_as!(
+            Session,
+            r#"
+            UPDATE sessions
+            SET refresh_token_hash = $3,
+                user_agent = $4,
+                ip_address = $5,
+                expires_at = $6,
+                last_used_at = now()
+            WHERE id = $1
+                AND refresh_token_hash = $2
+                AND revoked_at IS NULL
+                AND expires_at > now()
+            RETURNING id, user_id, user_agent, ip_address, created_at, last_used_at, expires_at
+            "#,
+            id,
+            refresh_token_hash,
+            new_refresh_token_hash,
+            user_agent,
+            ip_address,
+            expires_at,
+        )
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or(RepositoryError::SessionNotFound)?;
+
+        Ok(session)
+    }
+
+    pub async fn is_session_active(
+        &self,
+        id: Uuid,
+    ) -> Result<bool, RepositoryError> {
+        let active = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM sessions
+                WHERE id = $1 AND revoked_at IS NULL AND expires_at > now()
+            ) AS "active!"
+            "#,
+            id
+        )
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(active)
+    }
+
+    pub async fn list_sessions(
+        &self,
+        value: &ListSessionsRequest,
+    ) -> Result<Vec<Session>, RepositoryError> {
+        let ListSessionsRepo { user_id } = value.try_into()?;
+
+        let sessions = sqlx::query_as!(
+            Session,
+            r#"
+            SELECT id, user_id, user_agent, ip_address, created_at, last_used_at, expires_at
+            FROM sessions
+            WHERE user_id = $1 AND revoked_at IS NULL AND expires_at > now()
+            ORDER BY last_used_at DESC
+            "#,
+            user_id
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(sessions)
+    }
+
+    pub async fn revoke_session(
+        &self,
+        value: RevokeSessionRequest,
+    ) -> Result<Session, RepositoryError> {
+        let RevokeSessionRepo { id, user_id } = value.try_into()?;
+
+        let session = sqlx::query_as!(
+            Session,
+            r#"
+            UPDATE sessions
+            SET revoked_at = now()
+            WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL
+            RETURNING id, user_id, user_agent, ip_address, created_at, last_used_at, expires_at
+            "#,
+            id,
+            user_id
+        )
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or(RepositoryError::SessionNotFound)?;
+
+        Ok(session)
+    }
+
+    pub async fn create_oidc_state(
+        &self,
+        value: CreateOidcStateRepo,
+    ) -> Result<(), RepositoryError> {
+        let CreateOidcStateRepo { state, provider, code_verifier, nonce, expires_at } = value;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO oidc_states (state, provider, code_verifier, nonce, expires_at)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+            state,
+            provider,
+            code_verifier,
+            nonce,
+            expires_at,
+        )
+        .execute(&self.db)
+        .await?;
+
+        Ok(())
+    }
+
+    // States are single use: the row is deleted as it is read.
+    pub async fn take_oidc_state(
+        &self,
+        state: &str,
+        provider: &str,
+    ) -> Result<OidcState, RepositoryError> {
+        sqlx::query!(
+            r#"
+            DELETE FROM oidc_states
+            WHERE expires_at <= now()
+            "#
+        )
+        .execute(&self.db)
+        .await?;
+
+        let oidc_state = sqlx::query_as!(
+            OidcState,
+            r#"
+            DELETE FROM oidc_states
+            WHERE state = $1 AND provider = $2
+            RETURNING provider, code_verifier, nonce
+            "#,
+            state,
+            provider,
+        )
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or(RepositoryError::OidcStateNotFound)?;
+
+        Ok(oidc_state)
+    }
+
+    pub async fn find_external_identity(
+        &self,
+        provider: &str,
+        subject: &str,
+    ) -> Result<Option<Uuid>, RepositoryError> {
+        let user_id = sqlx::query_scalar!(
+            r#"
+            SELECT user_id FROM external_identities
+            WHERE provider = $1 AND subject = $2
+            "#,
+            provider,
+            subject,
+        )
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(user_id)
+    }
+
+    pub async fn link_external_identity(
+        &self,
+        value: LinkExternalIdentityRepo,
+    ) -> Result<(), RepositoryError> {
+        let LinkExternalIdentityRepo { user_id, provider, subject, email } = value;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO external_identities (user_id, provider, subject, email)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (provider, subject) DO NOTHING
+            "#,
+            user_id,
+            provider,
+            subject,
+            email,
+        )
+        .execute(&self.db)
+        .await?;
+
+        Ok(())
+    }
+
+    // Requests for the address and from the ip since `since`. Without an ip only the address counts.
+    pub async fn count_magic_link_requests_since(
+        &self,
+        email_hash: &str,
+        ip_address: &str,
+        since: DateTime<Utc>,
+    ) -> Result<MagicLinkRequestCounts, RepositoryError> {
+        let counts = sqlx::query_as!(
+            MagicLinkRequestCounts,
+            r#"
+            SELECT
+                COUNT(*) FILTER (WHERE email_hash = $1) AS "by_email!",
+                COUNT(*) FILTER (WHERE ip_address = $2) AS "by_ip!"
+            FROM magic_link_requests
+            WHERE (email_hash = $1 OR ($2 <> '' AND ip_address = $2)) AND created_at > $3
+            "#,
+            email_hash,
+            ip_address,
+            since,
+        )
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(counts)
+    }
+
+    pub async fn record_magic_link_request(
+        &self,
+        email_hash: &str,
+        ip_address: &str,
+    ) -> Result<(), RepositoryError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO magic_link_requests (email_hash, ip_address)
+            VALUES ($1, $2)
+            "#,
+            email_hash,
+            ip_address,
+        )
+        .execute(&self.db)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn create_magic_link(
+        &self,
+        value: CreateMagicLinkRepo,
+    ) -> Result<(), RepositoryError> {
+        let CreateMagicLinkRepo { user_id, email, token_hash, expires_at } = value;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO magic_links (user_id, email, token_hash, expires_at)
+            VALUES ($1, $2, $3, $4)
+            "#,
+            user_id,
+            email,
+            token_hash,
+            expires_at,
+        )
+        .execute(&self.db)
+        .await?;
+
+        Ok(())
+    }
+
+    // Marks the link as used and returns its owner, a link can be consumed only once.
+    pub async fn consume_magic_link(
+        &self,
+        token_hash: &str,
+    ) -> Result<Uuid, RepositoryError> {
+        let user_id = sqlx::query_scalar!(
+            r#"
+            UPDATE magic_links
+            SET consumed_at = now()
+            WHERE token_hash = $1
+                AND consumed_at IS NULL
+                AND expires_at > now()
+            RETURNING user_id
+            "#,
+            token_hash,
+        )
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or(RepositoryError::MagicLinkNotFound)?;
+
+        Ok(user_id)
+    }
+
+    pub async fn create_security_event(
+        &self,
+        value: CreateSecurityEventRepo,
+    ) -> Result<(), RepositoryError> {
+        let CreateSecurityEventRepo { user_id, event_type, ip_address, user_agent, country, details } = value;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO security_events (user_id, event_type, ip_address, user_agent, country, details)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#,
+            user_id,
+            event_type,
+            ip_address,
+            user_agent,
+            country,
+            details,
+        )
+        .execute(&self.db)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn list_security_events(
+        &self,
+        value: &ListSecurityEventsRequest,
+    ) -> Result<Vec<SecurityEvent>, RepositoryError> {
+        let ListSecurityEventsRepo { user_id, limit, before } = value.try_into()?;
+
+        let events = sqlx::query_as!(
+            SecurityEvent,
+            r#"
+            SELECT id, user_id, event_type, ip_address, user_agent, country, details, created_at
+            FROM security_events
+            WHERE user_id = $1 AND ($2::timestamptz IS NULL OR created_at < $2)
+            ORDER BY created_at DESC
+            LIMIT $3
+            "#,
+            user_id,
+            before,
+            limit,
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(events)
+    }
+
+    pub async fn sign_in_history(
+        &self,
+        user_id: Uuid,
+        user_agent: &str,
+        country: &str,
+    ) -> Result<SignInHistory, RepositoryError> {
+        let history = sqlx::query_as!(
+            SignInHistory,
+            r#"
+            SELECT
+                COUNT(*) AS "previous!",
+                COALESCE(bool_or(user_agent = $3), false) AS "device_seen!",
+                COALESCE(bool_or(country = $4), false) AS "country_seen!"
+            FROM security_events
+            WHERE user_id = $1 AND event_type = $2
+            "#,
+            user_id,
+            SecurityEventType::SignInSucceeded.as_str(),
+            user_agent,
+            country,
+        )
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(history)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+    use crate::domain::api_key::{generate_api_key, hash_api_key};
+
+    async fn insert_user(db: &Pool<Postgres>, name: &str) -> Uuid {
+        sqlx::query_scalar!(
+            "INSERT INTO users (username, email, password) VALUES ($1, $2, 'x') RETURNING id",
+            name,
+            format!("{name}@example.com"),
+        )
+        .fetch_one(db)
+        .await
+        .unwrap()
+    }
+
+    async fn create_key(repository: &AuthRepository, user_id: Uuid) -> (ApiKey, String) {
+        let generated = generate_api_key();
+        let api_key = repository
+            .create_api_key(CreateApiKeyRepo {
+                user_id,
+                name: "bot".to_string(),
+                prefix: generated.prefix,
+                key_hash: generated.hash,
+                scopes: vec!["posts:read".to_string()],
+            })
+            .await
+            .unwrap();
+
+        (api_key, generated.key)
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn an_api_key_resolves_to_its_owner_and_scopes_until_revoked(db: Pool<Postgres>) {
+        let user_id = insert_user(&db, "alice").await;
+        let repository = AuthRepository::new(db);
+        let (api_key, key) = create_key(&repository, user_id).await;
+
+        let owner = repository.use_api_key(&hash_api_key(&key)).await.unwrap();
+        assert_eq!((owner.user_id, owner.username.as_str()), (user_id, "alice"));
+        assert_eq!(owner.scopes, ["posts:read"]);
+
+        let err = repository.use_api_key(&key).await.unwrap_err();
+        assert!(matches!(err, RepositoryError::ApiKeyNotFound), "the key itself is not stored");
+
+        let revoked = repository
+            .revoke_api_key(RevokeApiKeyRequest { id: api_key.id.to_string(), user_id: user_id.to_string() })
+            .await
+            .unwrap();
+        assert!(revoked.revoked_at.is_some());
+
+        let err = repository.use_api_key(&hash_api_key(&key)).await.unwrap_err();
+        assert!(matches!(err, RepositoryError::ApiKeyNotFound));
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn only_the_owner_lists_and_revokes_a_key(db: Pool<Postgres>) {
+        let alice = insert_user(&db, "alice").await;
+        let bob = insert_user(&db, "bob").await;
+        let repository = AuthRepository::new(db);
+        let (api_key, key) = create_key(&repository, alice).await;
+
+        let listed = repository.list_api_keys(ListApiKeysRequest { user_id: bob.to_string() }).await.unwrap();
+        assert!(listed.is_empty());
+
+        let err = repository
+            .revoke_api_key(RevokeApiKeyRequest { id: api_key.id.to_string(), user_id: bob.to_string() })
+            .await
+            .unwrap_err();
+        assert!(matches!(err, RepositoryError::ApiKeyNotFound));
+        assert!(repository.use_api_key(&hash_api_key(&key)).await.is_ok());
+
+        let listed = repository.list_api_keys(ListApiKeysRequest { user_id: alice.to_string() }).await.unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].id, api_key.id);
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn last_use_of_a_key_is_written_at_most_once_a_minute(db: Pool<Postgres>) {
+        let user_id = insert_user(&db, "alice").await;
+        let repository = AuthRepository::new(db.clone());
+        let (api_key, key) = create_key(&repository, user_id).await;
+        let last_used_at = || sqlx::query_scalar!("SELECT last_used_at FROM api_keys WHERE id = $1", api_key.id).fetch_one(&db);
+
+        repository.use_api_key(&hash_api_key(&key)).await.unwrap();
+        let first = last_used_at().await.unwrap().unwrap();
+
+        repository.use_api_key(&hash_api_key(&key)).await.unwrap();
+        assert_eq!(last_used_at().await.unwrap().unwrap(), first);
+
+        sqlx::query!("UPDATE api_keys SET last_used_at = now() - interval '2 minutes' WHERE id = $1", api_key.id)
+            .execute(&db)
+            .await
+            .unwrap();
+        repository.use_api_key(&hash_api_key(&key)).await.unwrap();
+        assert!(last_used_at().await.unwrap().unwrap() >= first);
+    }
+
+    async fn create_session(repository: &AuthRepository, user_id: Uuid, refresh_token_hash: &str) -> Session {
+        repository
+            .create_session(CreateSessionRepo {
+                id: Uuid::new_v4(),
+                user_id,
+                refresh_token_hash: refresh_token_hash.to_string(),
+                user_agent: "phone".to_string(),
+                ip_address: "203.0.113.7".to_string(),
+                expires_at: Utc::now() + Duration::days(30),
+            })
+            .await
+            .unwrap()
+    }
+
+    fn rotation(id: Uuid, refresh_token_hash: &str, new_refresh_token_hash: &str) -> RotateSessionRepo {
+        RotateSessionRepo {
+            id,
+            refresh_token_hash: refresh_token_hash.to_string(),
+            new_refresh_token_hash: new_refresh_token_hash.to_string(),
+            user_agent: "laptop".to_string(),
+            ip_address: "203.0.113.8".to_string(),
+            expires_at: Utc::now() + Duration::days(30),
+        }
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn a_refresh_token_rotates_once_and_its_reuse_is_refused(db: Pool<Postgres>) {
+        let user_id = insert_user(&db, "alice").await;
+        let repository = AuthRepository::new(db);
+        let session = create_session(&repository, user_id, "first").await;
+
+        let rotated = repository.rotate_session(rotation(session.id, "first", "second")).await.unwrap();
+        assert_eq!(rotated.id, session.id);
+        assert_eq!((rotated.user_agent.as_str(), rotated.ip_address.as_str()), ("laptop", "203.0.113.8"));
+
+        let err = repository.rotate_session(rotation(session.id, "first", "third")).await.unwrap_err();
+        assert!(matches!(err, RepositoryError::SessionNotFound));
+
+        // The reused token didn't take the session over, its current token still works.
+        repository.rotate_session(rotation(session.id, "second", "third")).await.unwrap();
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn a_revoked_session_is_no_longer_listed_active_or_refreshable(db: Pool<Postgres>) {
+        let alice = insert_user(&db, "alice").await;
+        let bob = insert_user(&db, "bob").await;
+        let repository = AuthRepository::new(db);
+        let phone = create_session(&repository, alice, "phone").await;
+        let laptop = create_session(&repository, alice, "laptop").await;
+        let list = ListSessionsRequest { user_id: alice.to_string(), current_session_id: String::new() };
+
+        let sessions = repository.list_sessions(&list).await.unwrap();
+        assert_eq!(sessions.len(), 2);
+
+        let err = repository
+            .revoke_session(RevokeSessionRequest { id: phone.id.to_string(), user_id: bob.to_string() })
+            .await
+            .unwrap_err();
+        assert!(matches!(err, RepositoryError::SessionNotFound), "only the owner revokes a session");
+
+        repository
+            .revoke_session(RevokeSessionRequest { id: phone.id.to_string(), user_id: alice.to_string() })
+            .await
+            .unwrap();
+
+        let sessions = repository.list_sessions(&list).await.unwrap();
+        assert_eq!(sessions.iter().map(|session| session.id).collect::<Vec<_>>(), [laptop.id]);
+        assert!(!repository.is_session_active(phone.id).await.unwrap());
+        assert!(repository.is_session_active(laptop.id).await.unwrap());
+
+        let err = repository.rotate_session(rotation(phone.id, "phone", "next")).await.unwrap_err();
+        assert!(matches!(err, RepositoryError::SessionNotFound));
+    }
+
+    async fn counts(repository: &AuthRepository, email_hash: &str, ip_address: &str) -> MagicLinkRequestCounts {
+        repository
+            .count_magic_link_requests_since(email_hash, ip_address, Utc::now() - Duration::minutes(15))
+            .await
+            .unwrap()
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn magic_link_requests_count_per_address_and_ip(db: Pool<Postgres>) {
+        let repository = AuthRepository::new(db);
+
+        // None of these addresses have an account, the requests count all the same.
+        repository.record_magic_link_request("a", "203.0.113.7").await.unwrap();
+        repository.record_magic_link_request("a", "203.0.113.8").await.unwrap();
+        repository.record_magic_link_request("b", "203.0.113.7").await.unwrap();
+
+        let a = counts(&repository, "a", "203.0.113.7").await;
+        assert_eq!((a.by_email, a.by_ip), (2, 2));
+
+        let c = counts(&repository, "c", "203.0.113.8").await;
+        assert_eq!((c.by_email, c.by_ip), (0, 1));
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn requests_without_an_ip_share_no_ip_bucket(db: Pool<Postgres>) {
+        let repository = AuthRepository::new(db);
+
+        repository.record_magic_link_request("a", "").await.unwrap();
+        repository.record_magic_link_request("b", "").await.unwrap();
+
+        let c = counts(&repository, "c", "").await;
+        assert_eq!((c.by_email, c.by_ip), (0, 0));
+    }
+
+    async fn create_state(repository: &AuthRepository, state: &str, expires_at: DateTime<Utc>) {
+        repository
+            .create_oidc_state(CreateOidcStateRepo {
+                state: state.to_string(),
+                provider: "mock".to_string(),
+                code_verifier: "verifier".to_string(),
+                nonce: "nonce".to_string(),
+                expires_at,
+            })
+            .await
+            .unwrap();
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn oidc_state_is_single_use_and_bound_to_its_provider(db: Pool<Postgres>) {
+        let repository = AuthRepository::new(db);
+        create_state(&repository, "state-1", Utc::now() + Duration::minutes(10)).await;
+
+        let err = repository.take_oidc_state("state-1", "other").await.unwrap_err();
+        assert!(matches!(err, RepositoryError::OidcStateNotFound));
+
+        let taken = repository.take_oidc_state("state-1", "mock").await.unwrap();
+        assert_eq!((taken.code_verifier.as_str(), taken.nonce.as_str()), ("verifier", "nonce"));
+
+        let err = repository.take_oidc_state("state-1", "mock").await.unwrap_err();
+        assert!(matches!(err, RepositoryError::OidcStateNotFound));
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn expired_oidc_state_is_refused(db: Pool<Postgres>) {
+        let repository = AuthRepository::new(db);
+        create_state(&repository, "state-1", Utc::now() - Duration::seconds(1)).await;
+
+        let err = repository.take_oidc_state("state-1", "mock").await.unwrap_err();
+        assert!(matches!(err, RepositoryError::OidcStateNotFound));
+    }
+}
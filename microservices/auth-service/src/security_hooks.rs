@@ -0,0 +1,100 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::domain::security::SuspiciousReason;
+use crate::mailer::{Email, Mailer};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SuspiciousActivity {
+    pub user_id: String,
+    pub username: String,
+    pub email: String,
+    #[serde(serialize_with = "serialize_reasons")]
+    pub reasons: Vec<SuspiciousReason>,
+    pub ip_address: String,
+    pub user_agent: String,
+    pub country: String,
+    pub occurred_at: DateTime<Utc>,
+}
+
+fn serialize_reasons<S: serde::Serializer>(reasons: &[SuspiciousReason], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(reasons.iter().map(SuspiciousReason::as_str))
+}
+
+// Called after a suspicious security event was recorded. Hooks run in the background and handle their own errors.
+#[tonic::async_trait]
+pub trait SecurityHook: Send + Sync + std::fmt::Debug {
+    async fn on_suspicious_activity(&self, activity: &SuspiciousActivity);
+}
+
+// Warns the account owner by email.
+#[derive(Debug)]
+pub struct EmailSecurityHook {
+    mailer: Arc<dyn Mailer>,
+}
+
+impl EmailSecurityHook {
+    pub fn new(mailer: Arc<dyn Mailer>) -> Self {
+        Self { mailer }
+    }
+}
+
+#[tonic::async_trait]
+impl SecurityHook for EmailSecurityHook {
+    async fn on_suspicious_activity(&self, activity: &SuspiciousActivity) {
+        let reasons = activity.reasons.iter()
+            .map(|reason| format!("- {}", reason.describe()))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let email = Email {
+            to: activity.email.clone(),
+            subject: "New sign in to your account".to_string(),
+            body: format!(
+                "Hi {},\n\nYour account was signed in to at {} from:\n{}\n\nIP address: {}\nDevice: {}\n\nIf this was not you, revoke the session and change your password.",
+                activity.username,
+                activity.occurred_at.format("%Y-%m-%d %H:%M UTC"),
+                reasons,
+                activity.ip_address,
+                activity.user_agent,
+            ),
+        };
+
+        if let Err(e) = self.mailer.send(email).await {
+            tracing::warn!("Failed to send security notification: {}", e);
+        }
+    }
+}
+
+// Posts the activity as JSON, e.g. to a SIEM or a chat channel.
+#[derive(Debug)]
+pub struct WebhookSecurityHook {
+    http: reqwest::Client,
+    url: String,
+}
+
+impl WebhookSecurityHook {
+    pub fn new(url: String) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            url,
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl SecurityHook for WebhookSecurityHook {
+    async fn on_suspicious_activity(&self, activity: &SuspiciousActivity) {
+        let result = self.http.post(&self.url)
+            .json(activity)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+
+        if let Err(e) = result {
+            tracing::warn!("Failed to call security webhook: {}", e);
+        }
+    }
+}
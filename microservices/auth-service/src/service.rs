@@ -1,26 +1,266 @@
-use tonic::{ Request, Response, Status, transport::Channel};
+use std::sync::Arc;
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use chrono::{Duration, Utc};
+use metrics::counter;
+use serde_json::json;
+use tonic::{ Code, Request, Response, Status, service::interceptor::InterceptedService, transport::Channel};
+use uuid::Uuid;
 
 use crate::proto::auth::SignUpRequest;
 use crate::proto::auth::{ self, auth_server::Auth };
-use crate::domain::token::{Payload, generate_tokens};
-use crate::proto::users::{CreateUserRequest, GetUserRequest};
+use crate::domain::api_key::{generate_api_key, hash_api_key, is_api_key};
+use crate::domain::client::ClientInfo;
+use crate::domain::digest::sha256_hex;
+use crate::domain::magic_link::{MAGIC_LINK_RATE_WINDOW, MAGIC_LINK_TTL, email_hash, magic_link_url};
+use crate::domain::pkce::{code_challenge, random_urlsafe};
+use crate::domain::security::SecurityEventType;
+use crate::domain::token::{Payload, generate_tokens, refresh_token_expiration, verify_access_token, verify_refresh_token};
+use crate::error::{RepositoryError, map_oidc_err, map_repo_err};
+use crate::mailer::{Email, Mailer};
+use crate::model::{CreateApiKeyRepo, CreateMagicLinkRepo, CreateOidcStateRepo, CreateSecurityEventRepo, CreateSessionRepo, LinkExternalIdentityRepo, RotateSessionRepo};
+use crate::oidc::{ExternalIdentity, OidcClient};
+use crate::proto::users::{self, CreateUserRequest, GetUserByIdRequest, GetUserRequest};
 use crate::proto::users::users_client::UsersClient;
-use crate::domain::password::{hash_password, verify_password};
-use crate::validation::{validate_sign_in, validate_sign_up};
+use crate::domain::password::{DUMMY_PASSWORD_HASH, hash_password, verify_password};
+use crate::repository::AuthRepository;
+use crate::security_hooks::{SecurityHook, SuspiciousActivity};
+use common::telemetry::TracePropagation;
+use crate::validation::{check_email, suggest_username, validate_create_api_key, validate_sign_in, validate_sign_up};
 
 #[derive(Debug)]
 pub struct AuthService {
-    users_client: UsersClient<Channel>
+    users_client: UsersClient<InterceptedService<Channel, TracePropagation>>,
+    repository: AuthRepository,
+    oidc: OidcClient,
+    mailer: Arc<dyn Mailer>,
+    magic_link_url: String,
+    security_hooks: Vec<Arc<dyn SecurityHook>>,
+    admin_user_ids: Vec<String>,
 }
 
 impl AuthService {
     pub async fn new(
-        users_service_url: String
+        users_service_url: String,
+        repository: AuthRepository,
+        oidc: OidcClient,
+        mailer: Arc<dyn Mailer>,
+        magic_link_url: String,
+        security_hooks: Vec<Arc<dyn SecurityHook>>,
+        admin_user_ids: Vec<String>,
     ) -> Result<Self, Box<dyn std::error::Error>> {
-        let users_client =
-            UsersClient::connect(users_service_url).await?;
+        let channel = Channel::from_shared(users_service_url)?
+            .connect().await?;
+        let users_client = UsersClient::with_interceptor(channel, TracePropagation);
+
+        Ok(Self { users_client, repository, oidc, mailer, magic_link_url, security_hooks, admin_user_ids })
+    }
+
+    // Security events are an audit trail, failing to write one never fails the request itself.
+    async fn record_event(
+        &self,
+        user_id: Option<&str>,
+        event_type: SecurityEventType,
+        client: &ClientInfo,
+        details: serde_json::Value,
+    ) {
+        let result = self.repository.create_security_event(CreateSecurityEventRepo {
+            user_id: user_id.and_then(|id| Uuid::parse_str(id).ok()),
+            event_type: event_type.as_str().to_string(),
+            ip_address: client.ip_address.clone(),
+            user_agent: client.user_agent.clone(),
+            country: client.country.clone(),
+            details,
+        })
+        .await;
+
+        counter!("auth_security_events_total", "event_type" => event_type.as_str()).increment(1);
+
+        if let Err(e) = result {
+            tracing::error!("Failed to record {} security event: {}", event_type.as_str(), e);
+        }
+    }
+
+    // Records a successful sign in and raises an alert when it comes from an unfamiliar device or country.
+    async fn on_signed_in(
+        &self,
+        user: &users::User,
+        method: &str,
+        client: &ClientInfo,
+    ) {
+        // History is read before the new event is written, so the current sign in does not count as seen.
+        let history = match Uuid::parse_str(&user.id) {
+            Ok(user_id) => self.repository
+                .sign_in_history(user_id, &client.user_agent, &client.country)
+                .await
+                .ok(),
+            Err(_) => None,
+        };
+
+        counter!("auth_sign_ins_total", "method" => method.to_string()).increment(1);
+        self.record_event(Some(&user.id), SecurityEventType::SignInSucceeded, client, json!({ "method": method })).await;
+
+        let reasons = match history {
+            Some(history) => history.suspicious_reasons(&client.country),
+            None => return,
+        };
+
+        if reasons.is_empty() {
+            return;
+        }
+
+        let details = json!({
+            "method": method,
+            "reasons": reasons.iter().map(|reason| reason.as_str()).collect::<Vec<_>>(),
+        });
+        self.record_event(Some(&user.id), SecurityEventType::SuspiciousSignIn, client, details).await;
+
+        let activity = Arc::new(SuspiciousActivity {
+            user_id: user.id.clone(),
+            username: user.username.clone(),
+            email: user.email.clone(),
+            reasons,
+            ip_address: client.ip_address.clone(),
+            user_agent: client.user_agent.clone(),
+            country: client.country.clone(),
+            occurred_at: Utc::now(),
+        });
+
+        for hook in &self.security_hooks {
+            let hook = hook.clone();
+            let activity = activity.clone();
+
+            tokio::spawn(async move {
+                hook.on_suspicious_activity(&activity).await;
+            });
+        }
+    }
+
+    // Finds the local user behind an external identity, linking or creating one on first sign in.
+    async fn resolve_external_user(
+        &self,
+        provider: &str,
+        identity: ExternalIdentity,
+    ) -> Result<(users::User, bool), Status> {
+        let mut users_service = self.users_client.clone();
+
+        let linked_user_id = self.repository
+            .find_external_identity(provider, &identity.subject)
+            .await
+            .map_err(map_repo_err)?;
+
+        if let Some(user_id) = linked_user_id {
+            let user = users_service.get_user_by_id(GetUserByIdRequest {
+                id: user_id.to_string(),
+            })
+            .await?
+            .into_inner();
+
+            return Ok((user, false));
+        }
+
+        let email = identity.email.clone()
+            .ok_or_else(|| Status::failed_precondition("Sign in provider did not share an email address"))?;
+
+        let (user, created) = match users_service.get_user(GetUserRequest { email: email.clone() }).await {
+            Ok(response) => {
+                // Only a verified email proves that the external account owns the local one.
+                if !identity.email_verified {
+                    return Err(Status::already_exists("An account with this email already exists"));
+                }
 
-        Ok(Self { users_client })
+                (response.into_inner(), false)
+            },
+            Err(status) if status.code() == Code::NotFound => {
+                (self.create_external_user(&identity, &email).await?, true)
+            },
+            Err(status) => return Err(status),
+        };
+
+        self.repository.link_external_identity(LinkExternalIdentityRepo {
+            user_id: Uuid::parse_str(&user.id)
+                .map_err(|_| Status::internal("Invalid user id"))?,
+            provider: provider.to_string(),
+            subject: identity.subject,
+            email: Some(email),
+        })
+        .await
+        .map_err(map_repo_err)?;
+
+        Ok((user, created))
+    }
+
+    async fn create_external_user(
+        &self,
+        identity: &ExternalIdentity,
+        email: &str,
+    ) -> Result<users::User, Status> {
+        let mut users_service = self.users_client.clone();
+
+        let base = suggest_username(
+            identity.preferred_username.as_deref()
+                .unwrap_or_else(|| email.split('@').next().unwrap_or_default())
+        );
+
+        // Nobody knows this password, so the account is only reachable through the provider.
+        let password = hash_password(random_urlsafe())
+            .map_err(|_| Status::internal("error on password hasing"))?;
+
+        for attempt in 0..3 {
+            let username = match attempt {
+                0 => base.clone(),
+                _ => format!("{}{:04}", base, OsRng.next_u32() % 10_000),
+            };
+
+            let result = users_service.create_user(CreateUserRequest {
+                username,
+                email: email.to_string(),
+                password: password.clone(),
+            })
+            .await;
+
+            match result {
+                Ok(response) => return Ok(response.into_inner()),
+                Err(status) if status.code() == Code::AlreadyExists => continue,
+                Err(status) => return Err(status),
+            }
+        }
+
+        Err(Status::already_exists("Could not find a free username"))
+    }
+
+    // Opens a new session for the user and issues the tokens bound to it.
+    async fn start_session(
+        &self,
+        user_id: &str,
+        username: &str,
+        client: &ClientInfo,
+    ) -> Result<(String, String), Status> {
+        let session_id = Uuid::new_v4();
+
+        let claims = Payload {
+            sub: user_id.to_string(),
+            username: username.to_string(),
+            sid: session_id.to_string(),
+        };
+
+        // ---------- JWT Tokens ----------
+        let (access_token, refresh_token) = generate_tokens(claims)
+            .map_err(|_| Status::internal("Error on generating tokens"))?;
+
+        self.repository.create_session(CreateSessionRepo {
+            id: session_id,
+            user_id: Uuid::parse_str(user_id)
+                .map_err(|_| Status::internal("Invalid user id"))?,
+            refresh_token_hash: sha256_hex(&refresh_token),
+            user_agent: client.user_agent.clone(),
+            ip_address: client.ip_address.clone(),
+            expires_at: refresh_token_expiration(),
+        })
+        .await
+        .map_err(map_repo_err)?;
+
+        Ok((access_token, refresh_token))
     }
 }
 
@@ -30,10 +270,11 @@ impl Auth for AuthService {
         &self,
         request: Request<auth::SignUpRequest>,
     ) -> Result<Response<auth::SignUpResponse>, Status> {
+        let client = ClientInfo::from_metadata(request.metadata());
         let request = request.into_inner();
 
         let input = validate_sign_up(request)
-            .map_err(|e| Status::invalid_argument(e))?;
+            .map_err(Status::invalid_argument)?;
 
         let password_hash = hash_password(input.password)
             .map_err(|_| Status::internal("error on password hasing"))?;
@@ -49,14 +290,11 @@ impl Auth for AuthService {
         .await?
         .into_inner();
 
-        let claims = Payload {
-            sub: response.id.clone(),
-            username: response.username.clone(),
-        };
+        self.record_event(Some(&response.id), SecurityEventType::SignUp, &client, json!({ "method": "password" })).await;
 
-        // ---------- JWT Tokens ----------
-        let (access_token, _refresh_token) = generate_tokens(claims)
-            .map_err(|_| Status::internal("Error on generating tokens"))?;
+        let (access_token, refresh_token) = self
+            .start_session(&response.id, &response.username, &client)
+            .await?;
 
         let response = auth::SignUpResponse {
                 user: Some(auth::User {
@@ -65,6 +303,7 @@ impl Auth for AuthService {
                     email: response.email,
                 }),
                 access_token,
+                refresh_token,
         };
 
         Ok(Response::new(response))
@@ -74,31 +313,46 @@ impl Auth for AuthService {
         &self,
         request: Request<auth::SignInRequest>,
     ) -> Result<Response<auth::SignInResponse>, Status> {
-
+        let client = ClientInfo::from_metadata(request.metadata());
         let request = request.into_inner();
 
         let input = validate_sign_in(request)
-            .map_err(|e| Status::invalid_argument(e))?;
+            .map_err(Status::invalid_argument)?;
 
         let mut users_service = self.users_client.clone();
         let response = users_service.get_user(
             GetUserRequest {
-                email: input.email,
+                email: input.email.clone(),
         }).await;
 
-        let user = response?.into_inner();
+        let user = match response {
+            Ok(response) => response.into_inner(),
+            Err(status) if status.code() == Code::NotFound => {
+                // Spends the time a wrong password would, so the answer doesn't tell whether the account exists.
+                let _ = verify_password(input.password, DUMMY_PASSWORD_HASH.clone());
 
-        verify_password(input.password, user.password)
-            .map_err(|_| Status::unauthenticated("Wrong password."))?;
+                counter!("auth_sign_in_failures_total", "reason" => "unknown_email").increment(1);
+                let details = json!({ "email": input.email, "reason": "unknown_email" });
+                self.record_event(None, SecurityEventType::SignInFailed, &client, details).await;
 
-        let claims = Payload {
-            sub: user.id.clone(),
-            username: user.username.clone(),
+                return Err(Status::unauthenticated("Wrong email or password."));
+            },
+            Err(status) => return Err(status),
         };
 
-        // ---------- JWT tokens ----------
-        let (access_token, _refresh_token) = generate_tokens(claims)
-            .map_err(|_| Status::internal("Error on generating tokens"))?;
+        if verify_password(input.password, user.password.clone()).is_err() {
+            counter!("auth_sign_in_failures_total", "reason" => "wrong_password").increment(1);
+            let details = json!({ "email": input.email, "reason": "wrong_password" });
+            self.record_event(Some(&user.id), SecurityEventType::SignInFailed, &client, details).await;
+
+            return Err(Status::unauthenticated("Wrong email or password."));
+        }
+
+        self.on_signed_in(&user, "password", &client).await;
+
+        let (access_token, refresh_token) = self
+            .start_session(&user.id, &user.username, &client)
+            .await?;
 
         let response = auth::SignInResponse {
                 user: Some(auth::User {
@@ -107,6 +361,437 @@ impl Auth for AuthService {
                     email: user.email,
                 }),
                 access_token,
+                refresh_token,
+        };
+
+        Ok(Response::new(response))
+    }
+
+    async fn authenticate(
+        &self,
+        request: Request<auth::AuthenticateRequest>,
+    ) -> Result<Response<auth::AuthenticateResponse>, Status> {
+        let request = request.into_inner();
+
+        if is_api_key(&request.token) {
+            let owner = self.repository.use_api_key(&hash_api_key(&request.token))
+                .await
+                .map_err(|e| match e {
+                    RepositoryError::ApiKeyNotFound => Status::unauthenticated("Invalid api key"),
+                    e => map_repo_err(e),
+                })?;
+
+            let response = auth::AuthenticateResponse {
+                user_id: owner.user_id.to_string(),
+                username: owner.username,
+                scopes: owner.scopes,
+                is_api_key: true,
+                session_id: String::new(),
+            };
+
+            return Ok(Response::new(response));
+        }
+
+        let claims = verify_access_token(request.token)
+            .map_err(|_| Status::unauthenticated("Invalid access token"))?;
+
+        if let Some(sid) = &claims.sid {
+            let session_id = Uuid::parse_str(sid)
+                .map_err(|_| Status::unauthenticated("Invalid access token"))?;
+
+            let active = self.repository.is_session_active(session_id)
+                .await
+                .map_err(map_repo_err)?;
+
+            if !active {
+                return Err(Status::unauthenticated("Session has been revoked"));
+            }
+        }
+
+        let response = auth::AuthenticateResponse {
+            user_id: claims.sub,
+            username: claims.username,
+            scopes: vec![],
+            is_api_key: false,
+            session_id: claims.sid.unwrap_or_default(),
+        };
+
+        Ok(Response::new(response))
+    }
+
+    async fn refresh(
+        &self,
+        request: Request<auth::RefreshRequest>,
+    ) -> Result<Response<auth::RefreshResponse>, Status> {
+        let client = ClientInfo::from_metadata(request.metadata());
+        let request = request.into_inner();
+
+        let claims = verify_refresh_token(request.refresh_token.clone())
+            .map_err(|_| Status::unauthenticated("Invalid refresh token"))?;
+
+        let session_id = claims.sid.as_deref()
+            .and_then(|sid| Uuid::parse_str(sid).ok())
+            .ok_or_else(|| Status::unauthenticated("Invalid refresh token"))?;
+
+        let payload = Payload {
+            sub: claims.sub.clone(),
+            username: claims.username,
+            sid: session_id.to_string(),
+        };
+
+        // ---------- JWT Tokens ----------
+        let (access_token, refresh_token) = generate_tokens(payload)
+            .map_err(|_| Status::internal("Error on generating tokens"))?;
+
+        self.repository.rotate_session(RotateSessionRepo {
+            id: session_id,
+            refresh_token_hash: sha256_hex(&request.refresh_token),
+            new_refresh_token_hash: sha256_hex(&refresh_token),
+            user_agent: client.user_agent.clone(),
+            ip_address: client.ip_address.clone(),
+            expires_at: refresh_token_expiration(),
+        })
+        .await
+        .map_err(|e| match e {
+            RepositoryError::SessionNotFound => Status::unauthenticated("Session has expired or was revoked"),
+            e => map_repo_err(e),
+        })?;
+
+        let details = json!({ "session_id": session_id });
+        self.record_event(Some(&claims.sub), SecurityEventType::TokenRefreshed, &client, details).await;
+
+        let response = auth::RefreshResponse {
+            access_token,
+            refresh_token,
+        };
+
+        Ok(Response::new(response))
+    }
+
+    async fn list_sessions(
+        &self,
+        request: Request<auth::ListSessionsRequest>,
+    ) -> Result<Response<auth::ListSessionsResponse>, Status> {
+        let request = request.into_inner();
+
+        let sessions = self.repository.list_sessions(&request)
+            .await
+            .map_err(map_repo_err)?;
+
+        let response = auth::ListSessionsResponse {
+            sessions: sessions.into_iter()
+                .map(|session| {
+                    let mut session = auth::Session::from(session);
+                    session.current = session.id == request.current_session_id;
+                    session
+                })
+                .collect(),
+        };
+
+        Ok(Response::new(response))
+    }
+
+    async fn revoke_session(
+        &self,
+        request: Request<auth::RevokeSessionRequest>,
+    ) -> Result<Response<auth::RevokeSessionResponse>, Status> {
+        let client = ClientInfo::from_metadata(request.metadata());
+        let request = request.into_inner();
+        let user_id = request.user_id.clone();
+
+        let session = self.repository.revoke_session(request)
+            .await
+            .map_err(map_repo_err)?;
+
+        let details = json!({ "session_id": session.id });
+        self.record_event(Some(&user_id), SecurityEventType::SessionRevoked, &client, details).await;
+
+        let response = auth::RevokeSessionResponse {
+            session: Some(session.into()),
+        };
+
+        Ok(Response::new(response))
+    }
+
+    async fn create_api_key(
+        &self,
+        request: Request<auth::CreateApiKeyRequest>,
+    ) -> Result<Response<auth::CreateApiKeyResponse>, Status> {
+        let client = ClientInfo::from_metadata(request.metadata());
+        let request = request.into_inner();
+
+        let input = validate_create_api_key(request)
+            .map_err(Status::invalid_argument)?;
+
+        let user_id = Uuid::parse_str(&input.user_id)
+            .map_err(|_| Status::invalid_argument("invalid user id"))?;
+
+        let generated = generate_api_key();
+
+        let api_key = self.repository.create_api_key(CreateApiKeyRepo {
+            user_id,
+            name: input.name,
+            prefix: generated.prefix,
+            key_hash: generated.hash,
+            scopes: input.scopes,
+        })
+        .await
+        .map_err(map_repo_err)?;
+
+        let details = json!({ "api_key_id": api_key.id, "name": api_key.name, "scopes": api_key.scopes });
+        self.record_event(Some(&input.user_id), SecurityEventType::ApiKeyCreated, &client, details).await;
+
+        let response = auth::CreateApiKeyResponse {
+            api_key: Some(api_key.into()),
+            key: generated.key,
+        };
+
+        Ok(Response::new(response))
+    }
+
+    async fn list_api_keys(
+        &self,
+        request: Request<auth::ListApiKeysRequest>,
+    ) -> Result<Response<auth::ListApiKeysResponse>, Status> {
+        let request = request.into_inner();
+
+        let api_keys = self.repository.list_api_keys(request)
+            .await
+            .map_err(map_repo_err)?;
+
+        let response = auth::ListApiKeysResponse {
+            api_keys: api_keys.into_iter().map(Into::into).collect(),
+        };
+
+        Ok(Response::new(response))
+    }
+
+    async fn revoke_api_key(
+        &self,
+        request: Request<auth::RevokeApiKeyRequest>,
+    ) -> Result<Response<auth::RevokeApiKeyResponse>, Status> {
+        let client = ClientInfo::from_metadata(request.metadata());
+        let request = request.into_inner();
+        let user_id = request.user_id.clone();
+
+        let api_key = self.repository.revoke_api_key(request)
+            .await
+            .map_err(map_repo_err)?;
+
+        let details = json!({ "api_key_id": api_key.id, "name": api_key.name });
+        self.record_event(Some(&user_id), SecurityEventType::ApiKeyRevoked, &client, details).await;
+
+        let response = auth::RevokeApiKeyResponse {
+            api_key: Some(api_key.into()),
+        };
+
+        Ok(Response::new(response))
+    }
+
+    async fn start_oidc_sign_in(
+        &self,
+        request: Request<auth::StartOidcSignInRequest>,
+    ) -> Result<Response<auth::StartOidcSignInResponse>, Status> {
+        let request = request.into_inner();
+
+        if !self.oidc.has_provider(&request.provider) {
+            return Err(Status::not_found("Unknown sign in provider"));
+        }
+
+        let state = random_urlsafe();
+        let nonce = random_urlsafe();
+        let code_verifier = random_urlsafe();
+
+        let authorization_url = self.oidc
+            .authorization_url(&request.provider, &state, &nonce, &code_challenge(&code_verifier))
+            .await
+            .map_err(map_oidc_err)?;
+
+        self.repository.create_oidc_state(CreateOidcStateRepo {
+            state: state.clone(),
+            provider: request.provider,
+            code_verifier,
+            nonce,
+            expires_at: Utc::now() + Duration::minutes(10),
+        })
+        .await
+        .map_err(map_repo_err)?;
+
+        let response = auth::StartOidcSignInResponse {
+            authorization_url,
+            state,
+        };
+
+        Ok(Response::new(response))
+    }
+
+    async fn finish_oidc_sign_in(
+        &self,
+        request: Request<auth::FinishOidcSignInRequest>,
+    ) -> Result<Response<auth::FinishOidcSignInResponse>, Status> {
+        let client = ClientInfo::from_metadata(request.metadata());
+        let request = request.into_inner();
+
+        let oidc_state = self.repository
+            .take_oidc_state(&request.state, &request.provider)
+            .await
+            .map_err(map_repo_err)?;
+
+        let identity = self.oidc
+            .exchange_code(&oidc_state.provider, &request.code, &oidc_state.code_verifier, &oidc_state.nonce)
+            .await
+            .map_err(map_oidc_err)?;
+
+        let (user, created) = self.resolve_external_user(&oidc_state.provider, identity).await?;
+
+        let method = format!("oidc:{}", oidc_state.provider);
+        if created {
+            self.record_event(Some(&user.id), SecurityEventType::SignUp, &client, json!({ "method": method })).await;
+        }
+        self.on_signed_in(&user, &method, &client).await;
+
+        let (access_token, refresh_token) = self
+            .start_session(&user.id, &user.username, &client)
+            .await?;
+
+        let response = auth::FinishOidcSignInResponse {
+                user: Some(auth::User {
+                    id: user.id,
+                    username: user.username,
+                    email: user.email,
+                }),
+                access_token,
+                refresh_token,
+                created,
+        };
+
+        Ok(Response::new(response))
+    }
+
+    async fn request_magic_link(
+        &self,
+        request: Request<auth::RequestMagicLinkRequest>,
+    ) -> Result<Response<auth::RequestMagicLinkResponse>, Status> {
+        let client = ClientInfo::from_metadata(request.metadata());
+        let request = request.into_inner();
+
+        check_email(&request.email)
+            .map_err(Status::invalid_argument)?;
+
+        // Counted before looking the address up, so known and unknown addresses hit the limit alike.
+        let email_hash = email_hash(&request.email);
+
+        let requested_recently = self.repository
+            .count_magic_link_requests_since(&email_hash, &client.ip_address, Utc::now() - MAGIC_LINK_RATE_WINDOW)
+            .await
+            .map_err(map_repo_err)?;
+
+        if requested_recently.exceeded() {
+            return Err(Status::resource_exhausted("Too many sign in links requested, try again later"));
+        }
+
+        self.repository
+            .record_magic_link_request(&email_hash, &client.ip_address)
+            .await
+            .map_err(map_repo_err)?;
+
+        let mut users_service = self.users_client.clone();
+        let user = match users_service.get_user(GetUserRequest { email: request.email.clone() }).await {
+            Ok(response) => response.into_inner(),
+            // Unknown addresses get the same answer, so the endpoint cannot be used to probe for accounts.
+            Err(status) if status.code() == Code::NotFound => {
+                return Ok(Response::new(auth::RequestMagicLinkResponse {}));
+            },
+            Err(status) => return Err(status),
+        };
+
+        let token = random_urlsafe();
+
+        self.repository.create_magic_link(CreateMagicLinkRepo {
+            user_id: Uuid::parse_str(&user.id)
+                .map_err(|_| Status::internal("Invalid user id"))?,
+            email: user.email.clone(),
+            token_hash: sha256_hex(&token),
+            expires_at: Utc::now() + MAGIC_LINK_TTL,
+        })
+        .await
+        .map_err(map_repo_err)?;
+
+        let email = Email {
+            to: user.email,
+            subject: "Your sign in link".to_string(),
+            body: format!(
+                "Hi {},\n\nUse this link to sign in, it works once and expires in {} minutes:\n{}\n\nIf you did not ask for it, ignore this email.",
+                user.username,
+                MAGIC_LINK_TTL.num_minutes(),
+                magic_link_url(&self.magic_link_url, &token),
+            ),
+        };
+
+        self.mailer.send(email)
+            .await
+            .map_err(|_| Status::unavailable("Failed to send sign in link"))?;
+
+        Ok(Response::new(auth::RequestMagicLinkResponse {}))
+    }
+
+    async fn consume_magic_link(
+        &self,
+        request: Request<auth::ConsumeMagicLinkRequest>,
+    ) -> Result<Response<auth::ConsumeMagicLinkResponse>, Status> {
+        let client = ClientInfo::from_metadata(request.metadata());
+        let request = request.into_inner();
+
+        let user_id = self.repository
+            .consume_magic_link(&sha256_hex(&request.token))
+            .await
+            .map_err(map_repo_err)?;
+
+        let mut users_service = self.users_client.clone();
+        let user = users_service.get_user_by_id(GetUserByIdRequest {
+            id: user_id.to_string(),
+        })
+        .await?
+        .into_inner();
+
+        self.on_signed_in(&user, "magic_link", &client).await;
+
+        let (access_token, refresh_token) = self
+            .start_session(&user.id, &user.username, &client)
+            .await?;
+
+        let response = auth::ConsumeMagicLinkResponse {
+                user: Some(auth::User {
+                    id: user.id,
+                    username: user.username,
+                    email: user.email,
+                }),
+                access_token,
+                refresh_token,
+        };
+
+        Ok(Response::new(response))
+    }
+
+    async fn list_security_events(
+        &self,
+        request: Request<auth::ListSecurityEventsRequest>,
+    ) -> Result<Response<auth::ListSecurityEventsResponse>, Status> {
+        let request = request.into_inner();
+
+        let is_owner = request.requester_id == request.user_id;
+        let is_admin = self.admin_user_ids.contains(&request.requester_id);
+
+        if !is_owner && !is_admin {
+            return Err(Status::permission_denied("Not allowed to view security events of this user"));
+        }
+
+        let events = self.repository.list_security_events(&request)
+            .await
+            .map_err(map_repo_err)?;
+
+        let response = auth::ListSecurityEventsResponse {
+            events: events.into_iter().map(Into::into).collect(),
         };
 
         Ok(Response::new(response))
@@ -1,12 +1,20 @@
 use regex::Regex;
 use std::sync::LazyLock;
 
-use crate::proto::auth::{SignInRequest, SignUpRequest};
+use crate::proto::auth::{CreateApiKeyRequest, SignInRequest, SignUpRequest};
 
 static EMAIL_REGEX: LazyLock<Regex> = LazyLock::new(|| {
     Regex::new(r"^[a-zA-Z0-9._%+-]+@[a-zA-Z0-9.-]+\.[a-zA-Z]{2,}$").unwrap()
 });
 
+pub const API_KEY_SCOPES: [&str; 5] = [
+    "posts:read",
+    "posts:write",
+    "comments:read",
+    "comments:write",
+    "follows:write",
+];
+
 pub fn check_password(password: &str) -> Result<(), &'static str> {
     let length = password.len();
 
@@ -63,6 +71,40 @@ pub fn check_email(email: &str) -> Result<(), &'static str> {
     Ok(())
 }
 
+pub fn check_api_key_name(name: &str) -> Result<(), &'static str> {
+    if !(1..=50).contains(&name.trim().len()) {
+        return Err("Api key name must be 1-50 characters long");
+    }
+
+    Ok(())
+}
+
+pub fn check_api_key_scopes(scopes: &[String]) -> Result<(), &'static str> {
+    if scopes.is_empty() {
+        return Err("Api key must have at least one scope");
+    }
+
+    if !scopes.iter().all(|scope| API_KEY_SCOPES.contains(&scope.as_str())) {
+        return Err("Unknown api key scope");
+    }
+
+    Ok(())
+}
+
+// Builds a valid username out of what an external provider knows about the user.
+pub fn suggest_username(source: &str) -> String {
+    let mut username: String = source.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .take(28)
+        .collect();
+
+    if username.len() < 3 {
+        username.insert_str(0, "user");
+    }
+
+    username
+}
+
 // ---------- Validating Specific Procedures ----------
 
 pub fn validate_sign_up(input: SignUpRequest) -> Result<SignUpRequest, &'static str> {
@@ -78,4 +120,43 @@ pub fn validate_sign_in(input: SignInRequest) -> Result<SignInRequest, &'static
     check_password(&input.password)?;
 
     Ok(input)
+}
+
+pub fn validate_create_api_key(mut input: CreateApiKeyRequest) -> Result<CreateApiKeyRequest, &'static str> {
+    check_api_key_name(&input.name)?;
+    check_api_key_scopes(&input.scopes)?;
+
+    input.name = input.name.trim().to_string();
+    input.scopes.sort();
+    input.scopes.dedup();
+
+    Ok(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(name: &str, scopes: &[&str]) -> CreateApiKeyRequest {
+        CreateApiKeyRequest {
+            name: name.to_string(),
+            scopes: scopes.iter().map(|scope| scope.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn api_key_scopes_are_deduplicated_and_sorted() {
+        let input = validate_create_api_key(request(" bot ", &["posts:write", "posts:read", "posts:write"])).unwrap();
+
+        assert_eq!(input.name, "bot");
+        assert_eq!(input.scopes, ["posts:read", "posts:write"]);
+    }
+
+    #[test]
+    fn api_keys_need_a_name_and_known_scopes() {
+        assert!(validate_create_api_key(request("  ", &["posts:read"])).is_err());
+        assert!(validate_create_api_key(request("bot", &[])).is_err());
+        assert!(validate_create_api_key(request("bot", &["admin"])).is_err());
+    }
 }
\ No newline at end of file
@@ -4,6 +4,9 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     tonic_prost_build::configure().compile_protos(
         &[
             format!("{proto_dir}/comments.proto"),
+            format!("{proto_dir}/posts.proto"),
+            format!("{proto_dir}/google/rpc/status.proto"),
+            format!("{proto_dir}/google/rpc/error_details.proto"),
         ],
         &[proto_dir],
     )?;
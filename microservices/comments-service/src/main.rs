@@ -1,7 +1,7 @@
 use std::net::SocketAddr;
 
 use sqlx::postgres::PgPoolOptions;
-use tonic::transport::Server;
+use tonic::transport::{Channel, Server};
 
 use crate::{config::Config, proto::proto::comments::comments_server::CommentsServer, repository::CommentsRepository, service::CommentsService};
 
@@ -12,26 +12,48 @@ pub mod repository;
 pub mod model;
 pub mod domain;
 pub mod error;
+pub mod validation;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     dotenvy::dotenv().ok();
 
-    let config = Config::from_env();
+    let _tracer_provider = common::telemetry::init("comments-service")?;
+
+    let config = Config::from_env()?;
 
     let addr: SocketAddr = config.microservice_url.parse()?;
 
+    common::metrics::install(config.metrics_url.parse()?, "comments-service")?;
+
     let db = PgPoolOptions::new()
         .max_connections(5)
         .connect(&config.database_url)
         .await?;
 
-    let repository = CommentsRepository::new(db);
-    let service = CommentsService::new(repository);
+    let (health_reporter, health_service) = tonic_health::server::health_reporter();
+    tokio::spawn(common::metrics::watch_pool(db.clone()));
+    tokio::spawn(common::health::watch_database::<CommentsServer<CommentsService>>(health_reporter, db.clone()));
+
+    let repository = CommentsRepository::new(db, config.retention);
+    let purging = repository.clone();
+    tokio::spawn(common::purge::run("comments", config.purge_interval, move || {
+        let repository = purging.clone();
+        async move { repository.purge_deleted().await }
+    }));
+
+    // Connected on first use so the service can start before posts-service is up.
+    let posts_channel = Channel::from_shared(config.posts_service_url)?.connect_lazy();
+
+    let service = CommentsService::new(repository, posts_channel, config.moderator_user_ids);
 
-    println!("Comments service listening on {}", addr);
+    tracing::info!("Comments service listening on {}", addr);
 
     Server::builder()
+        .trace_fn(common::telemetry::request_span)
+        .layer(common::metrics::GrpcMetricsLayer)
+        .layer(common::telemetry::RequestIdLayer)
+        .add_service(health_service)
         .add_service(CommentsServer::new(service))
         .serve(addr)
         .await?;
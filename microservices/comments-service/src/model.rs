@@ -1,14 +1,9 @@
 use chrono::{DateTime, Utc};
 use sqlx::prelude::FromRow;
-use sqlx::types::{Uuid, uuid};
+use sqlx::types::Uuid;
 use crate::domain::time::{datetime_to_timestamp};
-use crate::proto::proto::comments::{AddCommentRequest, Comment as ProtoComment, DeleteCommentRequest, GetCommentRequest, GetCommentsRequest, UpdateCommentRequest};
-
-#[derive(Debug, thiserror::Error)]
-pub enum CommentConvertError {
-    #[error("invalid uuid: {0}")]
-    InvalidUuid(#[from] uuid::Error),
-}
+use crate::error::RepositoryError;
+use crate::proto::proto::comments::{AddCommentRequest, Comment as ProtoComment, CommentRevision as ProtoCommentRevision, CommentSearchHit as ProtoCommentSearchHit, DeleteCommentRequest, GetCommentRequest, GetCommentsRequest, ListCommentRevisionsRequest, RestoreCommentRequest, SearchCommentsRequest, UpdateCommentRequest};
 
 #[derive(Debug, FromRow)]
 pub struct Comment {
@@ -18,6 +13,10 @@ pub struct Comment {
     pub post_id: Uuid,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub edited_at: Option<DateTime<Utc>>,
+    pub edit_count: i32,
+    pub version: i64,
 }
 
 impl From<Comment> for ProtoComment {
@@ -29,9 +28,55 @@ impl From<Comment> for ProtoComment {
             post_id: value.post_id.to_string(),
             created_at: Some(datetime_to_timestamp(value.created_at)),
             updated_at: Some(datetime_to_timestamp(value.updated_at)),
+            deleted_at: value.deleted_at.map(datetime_to_timestamp),
+            edited_at: value.edited_at.map(datetime_to_timestamp),
+            edit_count: value.edit_count,
+            version: value.version,
+        }
+    }
+}
+
+#[derive(Debug, FromRow)]
+pub struct CommentRevision {
+    pub comment_id: Uuid,
+    pub revision: i32,
+    pub content: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<CommentRevision> for ProtoCommentRevision {
+    fn from(value: CommentRevision) -> ProtoCommentRevision {
+        ProtoCommentRevision {
+            comment_id: value.comment_id.to_string(),
+            revision: value.revision,
+            content: value.content,
+            created_at: Some(datetime_to_timestamp(value.created_at)),
         }
     }
 }
+
+#[derive(Debug)]
+pub struct CommentSearchHit {
+    pub comment: Comment,
+    pub rank: f32,
+    pub content_highlight: String,
+}
+
+impl From<CommentSearchHit> for ProtoCommentSearchHit {
+    fn from(value: CommentSearchHit) -> ProtoCommentSearchHit {
+        ProtoCommentSearchHit {
+            comment: Some(value.comment.into()),
+            rank: value.rank,
+            content_highlight: value.content_highlight,
+        }
+    }
+}
+
+pub fn parse_uuid(value: &str, field: &'static str) -> Result<Uuid, RepositoryError> {
+    Uuid::parse_str(value)
+        .map_err(|source| RepositoryError::InvalidUUID { field, source })
+}
+
 // -----------------------------
 
 pub struct GetCommentRepo {
@@ -39,11 +84,11 @@ pub struct GetCommentRepo {
 }
 
 impl TryFrom<&GetCommentRequest> for GetCommentRepo {
-    type Error = CommentConvertError;
+    type Error = RepositoryError;
 
     fn try_from(value: &GetCommentRequest) -> Result<Self, Self::Error> {
         Ok(Self {
-            id: Uuid::parse_str(&value.id)?
+            id: parse_uuid(&value.id, "id")?
         })
     }
 }
@@ -55,11 +100,11 @@ pub struct  GetCommentsRepo {
 }
 
 impl TryFrom<&GetCommentsRequest> for GetCommentsRepo {
-    type Error = CommentConvertError;
+    type Error = RepositoryError;
 
     fn try_from(value: &GetCommentsRequest) -> Result<Self, Self::Error> {
         Ok(Self {
-            post_id: Uuid::parse_str(&value.post_id)?
+            post_id: parse_uuid(&value.post_id, "post_id")?
         })
     }
 }
@@ -73,13 +118,13 @@ pub struct AddCommentRepo {
 }
 
 impl TryFrom<&AddCommentRequest> for AddCommentRepo {
-    type Error = CommentConvertError;
+    type Error = RepositoryError;
 
     fn try_from(value: &AddCommentRequest) -> Result<Self, Self::Error> {
         Ok(Self {
             content: value.content.to_string(),
-            post_id: Uuid::parse_str(&value.post_id)?,
-            user_id: Uuid::parse_str(&value.user_id)?,
+            post_id: parse_uuid(&value.post_id, "post_id")?,
+            user_id: parse_uuid(&value.user_id, "user_id")?,
         })
     }
 }
@@ -91,17 +136,19 @@ pub struct UpdateCommentRepo {
     pub content: String,
     pub post_id: Uuid,
     pub user_id: Uuid,
+    pub expected_versions: Vec<i64>,
 }
 
 impl TryFrom<&UpdateCommentRequest> for UpdateCommentRepo {
-    type Error = CommentConvertError;
+    type Error = RepositoryError;
 
     fn try_from(value: &UpdateCommentRequest) -> Result<Self, Self::Error> {
         Ok(UpdateCommentRepo {
-            id: Uuid::parse_str(&value.id)?,
+            id: parse_uuid(&value.id, "id")?,
             content: value.content.to_string(),
-            post_id: Uuid::parse_str(&value.post_id)?,
-            user_id: Uuid::parse_str(&value.user_id)?,
+            post_id: parse_uuid(&value.post_id, "post_id")?,
+            user_id: parse_uuid(&value.user_id, "user_id")?,
+            expected_versions: value.expected_versions.clone(),
         })
     }
 }
@@ -111,14 +158,71 @@ impl TryFrom<&UpdateCommentRequest> for UpdateCommentRepo {
 
 pub struct DeleteCommentRepo {
     pub id: Uuid,
+    pub requester_id: Uuid,
 }
 
 impl TryFrom<&DeleteCommentRequest> for DeleteCommentRepo {
-    type Error = CommentConvertError;
+    type Error = RepositoryError;
 
     fn try_from(value: &DeleteCommentRequest) -> Result<Self, Self::Error> {
         Ok(DeleteCommentRepo {
-            id: Uuid::parse_str(&value.id)?,
+            id: parse_uuid(&value.id, "id")?,
+            requester_id: parse_uuid(&value.requester_id, "requester_id")?,
+        })
+    }
+}
+
+// ---------------------------
+
+pub struct RestoreCommentRepo {
+    pub id: Uuid,
+    pub requester_id: Uuid,
+}
+
+impl TryFrom<&RestoreCommentRequest> for RestoreCommentRepo {
+    type Error = RepositoryError;
+
+    fn try_from(value: &RestoreCommentRequest) -> Result<Self, Self::Error> {
+        Ok(RestoreCommentRepo {
+            id: parse_uuid(&value.id, "id")?,
+            requester_id: parse_uuid(&value.requester_id, "requester_id")?,
         })
     }
+}
+
+// ---------------------------
+
+pub struct ListCommentRevisionsRepo {
+    pub id: Uuid,
+}
+
+impl TryFrom<&ListCommentRevisionsRequest> for ListCommentRevisionsRepo {
+    type Error = RepositoryError;
+
+    fn try_from(value: &ListCommentRevisionsRequest) -> Result<Self, Self::Error> {
+        Ok(ListCommentRevisionsRepo {
+            id: parse_uuid(&value.id, "id")?,
+        })
+    }
+}
+
+// -----------------------------
+
+pub struct SearchCommentsRepo {
+    pub query: String,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+impl From<&SearchCommentsRequest> for SearchCommentsRepo {
+    fn from(value: &SearchCommentsRequest) -> Self {
+        Self {
+            query: value.query.clone(),
+            limit: match value.limit {
+                1..=100 => value.limit as i64,
+                _ => 20,
+            },
+            offset: value.offset.max(0) as i64,
+        }
+    }
 }
\ No newline at end of file
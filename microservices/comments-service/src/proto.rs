@@ -1,7 +1,16 @@
 extern crate prost_types;
 
+#[allow(clippy::module_inception)]
 pub mod proto {
     pub mod comments {
         tonic::include_proto!("comments");
     }
+    pub mod posts {
+        tonic::include_proto!("posts");
+    }
+    pub mod google {
+        pub mod rpc {
+            tonic::include_proto!("google.rpc");
+        }
+    }
 }
\ No newline at end of file
@@ -1,15 +1,45 @@
-use sqlx::{Pool, Postgres};
+use std::time::Duration;
 
-use crate::{error::RepositoryError, model::{AddCommentRepo, Comment, DeleteCommentRepo, GetCommentRepo, GetCommentsRepo, UpdateCommentRepo}, proto::proto::comments::{AddCommentRequest, DeleteCommentRequest, GetCommentRequest, GetCommentsRequest, UpdateCommentRequest}};
+use chrono::{DateTime, Utc};
+use sqlx::{Pool, Postgres, Transaction};
+
+use crate::{error::RepositoryError, model::{AddCommentRepo, Comment, CommentRevision, DeleteCommentRepo, GetCommentRepo, CommentSearchHit, GetCommentsRepo, ListCommentRevisionsRepo, RestoreCommentRepo, SearchCommentsRepo, UpdateCommentRepo}, proto::proto::comments::{AddCommentRequest, DeleteCommentRequest, GetCommentRequest, GetCommentsRequest, ListCommentRevisionsRequest, RestoreCommentRequest, SearchCommentsRequest, UpdateCommentRequest}};
+
+// Rows removed per statement by the purge job, keeps each delete short.
+const PURGE_BATCH_SIZE: i64 = 1000;
+
+// Turns constraint violations of comment writes into domain errors.
+fn map_write_err(err: sqlx::Error) -> RepositoryError {
+    if let Some(db_err) = err.as_database_error() {
+        if db_err.is_foreign_key_violation() {
+            return match db_err.constraint() {
+                Some("comments_posts_fkey") => RepositoryError::PostNotFound,
+                _ => RepositoryError::UserNotFound,
+            };
+        }
+
+        if db_err.is_check_violation() {
+            return RepositoryError::InvalidContent;
+        }
+    }
+
+    RepositoryError::DatabaseError(err)
+}
 
 #[derive(Debug, Clone)]
 pub struct CommentsRepository {
-    pub db: Pool<Postgres>
+    pub db: Pool<Postgres>,
+    pub retention: Duration,
 }
 
 impl CommentsRepository {
-    pub fn new(db: Pool<Postgres>) -> Self {
-        Self { db }
+    pub fn new(db: Pool<Postgres>, retention: Duration) -> Self {
+        Self { db, retention }
+    }
+
+    // Deleted comments older than this are past restoring and up for purging.
+    fn retention_cutoff(&self) -> DateTime<Utc> {
+        Utc::now() - self.retention
     }
 
     pub async fn get_comment(
@@ -21,14 +51,15 @@ impl CommentsRepository {
         let comment = sqlx::query_as!(
             Comment,
             r#"
-            SELECT id, content, post_id, user_id, created_at, updated_at
+            SELECT id, content, post_id, user_id, created_at, updated_at, deleted_at, edited_at, edit_count, version
             FROM comments
-            WHERE id = $1
+            WHERE id = $1 AND deleted_at IS NULL
             "#,
             id,
         )
-        .fetch_one(&self.db)
-        .await?;
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or(RepositoryError::CommentNotFound)?;
 
         Ok(comment)
     }
@@ -42,9 +73,9 @@ impl CommentsRepository {
         let comment = sqlx::query_as!(
             Comment,
             r#"
-            SELECT id, content, post_id, user_id, created_at, updated_at
+            SELECT id, content, post_id, user_id, created_at, updated_at, deleted_at, edited_at, edit_count, version
             FROM comments
-            WHERE post_id = $1
+            WHERE post_id = $1 AND deleted_at IS NULL
             "#,
             post_id,
         )
@@ -54,70 +85,580 @@ impl CommentsRepository {
         Ok(comment)
     }
 
+    // Best match first. Comments under posts that aren't public are left out, a hit would otherwise
+    // reveal what those posts are about to anyone.
+    pub async fn search_comments(
+        &self,
+        value: &SearchCommentsRequest,
+    ) -> Result<Vec<CommentSearchHit>, RepositoryError> {
+        let SearchCommentsRepo { query, limit, offset } = value.into();
+
+        let rows = sqlx::query!(
+            r#"
+            SELECT comments.id, content, post_id, comments.user_id, comments.created_at, comments.updated_at, comments.deleted_at, comments.edited_at, edit_count, comments.version,
+                ts_rank_cd(comments.search_vector, query) AS "rank!",
+                ts_headline('english', html_escape(content), query, 'StartSel=<mark>, StopSel=</mark>, MaxFragments=2, MinWords=5, MaxWords=20') AS "content_highlight!"
+            FROM comments
+            JOIN posts ON posts.id = comments.post_id,
+            websearch_to_tsquery('english', $1) AS query
+            WHERE comments.search_vector @@ query AND comments.deleted_at IS NULL
+            AND posts.deleted_at IS NULL AND posts.status = 'published' AND posts.visibility = 'public'
+            ORDER BY "rank!" DESC, comments.created_at DESC
+            LIMIT $2 OFFSET $3
+            "#,
+            query,
+            limit,
+            offset,
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        let hits = rows.into_iter()
+            .map(|row| CommentSearchHit {
+                comment: Comment {
+                    id: row.id,
+                    content: row.content,
+                    user_id: row.user_id,
+                    post_id: row.post_id,
+                    created_at: row.created_at,
+                    updated_at: row.updated_at,
+                    deleted_at: row.deleted_at,
+                    edited_at: row.edited_at,
+                    edit_count: row.edit_count,
+                    version: row.version,
+                },
+                rank: row.rank,
+                content_highlight: row.content_highlight,
+            })
+            .collect();
+
+        Ok(hits)
+    }
+
     pub async fn add_comment(
         &self,
         value: &AddCommentRequest,
     ) -> Result<Comment, RepositoryError> {
         let AddCommentRepo {content, post_id, user_id} = value.try_into()?;
 
+        let mut tx = self.db.begin().await?;
+
+        // Locking the post row until commit keeps it from being locked or deleted in between.
+        // Drafts and scheduled posts can't be commented on yet, to everyone else they don't exist.
+        let post = sqlx::query!(
+            r#"
+            SELECT locked_at
+            FROM posts
+            WHERE id = $1 AND deleted_at IS NULL AND status = 'published'
+            FOR SHARE
+            "#,
+            post_id,
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(RepositoryError::PostNotFound)?;
+
+        if post.locked_at.is_some() {
+            return Err(RepositoryError::PostLocked);
+        }
+
         let comment = sqlx::query_as!(
             Comment,
             r#"
             INSERT INTO comments (content, user_id, post_id)
             VALUES ($1, $2, $3)
-            RETURNING id, content, user_id, post_id, created_at, updated_at
+            RETURNING id, content, user_id, post_id, created_at, updated_at, deleted_at, edited_at, edit_count, version
             "#,
             content,
             user_id,
             post_id
         )
-        .fetch_one(&self.db)
-        .await?;
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(map_write_err)?;
+
+        Self::insert_revision(&mut tx, &comment).await?;
+
+        tx.commit().await?;
 
         Ok(comment)
     }
 
+    // Records the edit as a new revision. Saving unchanged content is not an edit and leaves the comment as is.
+    // With `expected_versions` set, a comment written to since the caller read it is left alone.
+    // Someone else's comment is not found, so its existence isn't confirmed either.
     pub async fn update_comment(
         &self,
         value: &UpdateCommentRequest,
     ) -> Result<Comment, RepositoryError> {
-        let UpdateCommentRepo { id, content, ..  } = value.try_into()?;
+        let UpdateCommentRepo { id, content, user_id, expected_versions, ..  } = value.try_into()?;
+
+        let mut tx = self.db.begin().await?;
+
+        let current = sqlx::query_as!(
+            Comment,
+            r#"
+            SELECT id, content, post_id, user_id, created_at, updated_at, deleted_at, edited_at, edit_count, version
+            FROM comments
+            WHERE id = $1 AND user_id = $2 AND deleted_at IS NULL
+            FOR UPDATE
+            "#,
+            id,
+            user_id,
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(RepositoryError::CommentNotFound)?;
+
+        if !expected_versions.is_empty() && !expected_versions.contains(&current.version) {
+            return Err(RepositoryError::VersionMismatch);
+        }
+
+        if current.content == content {
+            return Ok(current);
+        }
 
         let comment = sqlx::query_as!(
             Comment,
             r#"
             UPDATE comments
-            SET content = $1
+            SET content = $1, edited_at = now(), edit_count = edit_count + 1
             WHERE id = $2
-            RETURNING id, content, user_id, post_id, created_at, updated_at
+            RETURNING id, content, user_id, post_id, created_at, updated_at, deleted_at, edited_at, edit_count, version
             "#,
             content,
             id,
         )
-        .fetch_one(&self.db)
-        .await?;
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(map_write_err)?;
+
+        Self::insert_revision(&mut tx, &comment).await?;
+
+        tx.commit().await?;
 
         Ok(comment)
     }
 
+    // Revision numbers start at 1 for the comment as posted, so each edit adds the next one.
+    async fn insert_revision(
+        tx: &mut Transaction<'_, Postgres>,
+        comment: &Comment,
+    ) -> Result<(), RepositoryError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO comment_revisions (comment_id, revision, content, created_at)
+            VALUES ($1, $2, $3, $4)
+            "#,
+            comment.id,
+            comment.edit_count + 1,
+            comment.content,
+            comment.edited_at.unwrap_or(comment.created_at),
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    // Newest revision first. Revisions of a deleted comment stay hidden unless `include_deleted` is set.
+    pub async fn list_comment_revisions(
+        &self,
+        value: &ListCommentRevisionsRequest,
+        include_deleted: bool,
+    ) -> Result<Vec<CommentRevision>, RepositoryError> {
+        let ListCommentRevisionsRepo { id } = value.try_into()?;
+
+        let comment = sqlx::query!(
+            r#"
+            SELECT deleted_at
+            FROM comments
+            WHERE id = $1
+            "#,
+            id,
+        )
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or(RepositoryError::CommentNotFound)?;
+
+        if comment.deleted_at.is_some() && !include_deleted {
+            return Err(RepositoryError::CommentNotFound);
+        }
+
+        let revisions = sqlx::query_as!(
+            CommentRevision,
+            r#"
+            SELECT comment_id, revision, content, created_at
+            FROM comment_revisions
+            WHERE comment_id = $1
+            ORDER BY revision DESC
+            "#,
+            id,
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(revisions)
+    }
+
+    // Only the author's own comments are found.
     pub async fn delete_comment(
         &self,
         value: &DeleteCommentRequest,
     ) -> Result<Comment, RepositoryError> {
-        let DeleteCommentRepo { id } = value.try_into()?;
+        let DeleteCommentRepo { id, requester_id } = value.try_into()?;
 
         let comment = sqlx::query_as!(
             Comment,
             r#"
-            DELETE FROM comments
+            UPDATE comments
+            SET deleted_at = now()
+            WHERE id = $1 AND user_id = $2 AND deleted_at IS NULL
+            RETURNING id, content, user_id, post_id, created_at, updated_at, deleted_at, edited_at, edit_count, version
+            "#,
+            id,
+            requester_id,
+        )
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or(RepositoryError::CommentNotFound)?;
+
+        Ok(comment)
+    }
+
+    // Comments of a deleted post come back with the post, restoring one alone would leave it orphaned.
+    // Only the author's own comments are found.
+    pub async fn restore_comment(
+        &self,
+        value: &RestoreCommentRequest,
+    ) -> Result<Comment, RepositoryError> {
+        let RestoreCommentRepo { id, requester_id } = value.try_into()?;
+
+        let mut tx = self.db.begin().await?;
+
+        // Locking the post row too keeps it from being deleted while the comment comes back.
+        let deleted = sqlx::query!(
+            r#"
+            SELECT comments.deleted_at, posts.deleted_at AS post_deleted_at
+            FROM comments
+            JOIN posts ON posts.id = comments.post_id
+            WHERE comments.id = $1 AND comments.user_id = $2
+            FOR UPDATE OF comments
+            FOR SHARE OF posts
+            "#,
+            id,
+            requester_id,
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(RepositoryError::CommentNotFound)?;
+
+        let deleted_at = deleted.deleted_at.ok_or(RepositoryError::CommentNotDeleted)?;
+
+        if deleted.post_deleted_at.is_some() {
+            return Err(RepositoryError::PostDeleted);
+        }
+
+        if deleted_at < self.retention_cutoff() {
+            return Err(RepositoryError::RetentionExpired);
+        }
+
+        let comment = sqlx::query_as!(
+            Comment,
+            r#"
+            UPDATE comments
+            SET deleted_at = NULL
             WHERE id = $1
-            RETURNING id, content, user_id, post_id, created_at, updated_at
+            RETURNING id, content, user_id, post_id, created_at, updated_at, deleted_at, edited_at, edit_count, version
             "#,
             id,
         )
-        .fetch_one(&self.db)
+        .fetch_one(&mut *tx)
         .await?;
 
+        tx.commit().await?;
+
         Ok(comment)
     }
-}
\ No newline at end of file
+
+    // Permanently removes one batch of comments deleted before the retention window.
+    // Returns how many were removed.
+    pub async fn purge_deleted(&self) -> Result<u64, RepositoryError> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM comments
+            WHERE id IN (
+                SELECT id FROM comments
+                WHERE deleted_at < $1
+                LIMIT $2
+            )
+            "#,
+            self.retention_cutoff(),
+            PURGE_BATCH_SIZE,
+        )
+        .execute(&self.db)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::types::Uuid;
+
+    use super::*;
+
+    async fn insert_user(db: &Pool<Postgres>, name: &str) -> Uuid {
+        sqlx::query_scalar!(
+            "INSERT INTO users (username, email, password) VALUES ($1, $2, 'x') RETURNING id",
+            name,
+            format!("{name}@example.com"),
+        )
+        .fetch_one(db)
+        .await
+        .unwrap()
+    }
+
+    async fn insert_post(db: &Pool<Postgres>, user_id: Uuid) -> Uuid {
+        sqlx::query_scalar!(
+            "INSERT INTO posts (title, description, user_id) VALUES ('Title', 'Description', $1) RETURNING id",
+            user_id,
+        )
+        .fetch_one(db)
+        .await
+        .unwrap()
+    }
+
+    async fn fixture(db: Pool<Postgres>) -> (CommentsRepository, Uuid, Uuid, Comment) {
+        let repository = CommentsRepository::new(db.clone(), Duration::from_secs(3600));
+        let author = insert_user(&db, "author").await;
+        let other = insert_user(&db, "other").await;
+        let post_id = insert_post(&db, author).await;
+
+        let comment = repository
+            .add_comment(&AddCommentRequest {
+                user_id: author.to_string(),
+                post_id: post_id.to_string(),
+                content: "First".to_string(),
+            })
+            .await
+            .unwrap();
+
+        (repository, author, other, comment)
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn only_the_author_updates_a_comment(db: Pool<Postgres>) {
+        let (repository, author, other, comment) = fixture(db).await;
+
+        let update = |user_id: Uuid| UpdateCommentRequest {
+            id: comment.id.to_string(),
+            content: "Edited".to_string(),
+            user_id: user_id.to_string(),
+            post_id: comment.post_id.to_string(),
+            expected_versions: vec![],
+        };
+
+        let err = repository.update_comment(&update(other)).await.unwrap_err();
+        assert!(matches!(err, RepositoryError::CommentNotFound));
+
+        let updated = repository.update_comment(&update(author)).await.unwrap();
+        assert_eq!(updated.content, "Edited");
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn only_the_author_deletes_and_restores_a_comment(db: Pool<Postgres>) {
+        let (repository, author, other, comment) = fixture(db).await;
+
+        let delete = |requester_id: Uuid| DeleteCommentRequest {
+            id: comment.id.to_string(),
+            requester_id: requester_id.to_string(),
+        };
+        let restore = |requester_id: Uuid| RestoreCommentRequest {
+            id: comment.id.to_string(),
+            requester_id: requester_id.to_string(),
+        };
+
+        let err = repository.delete_comment(&delete(other)).await.unwrap_err();
+        assert!(matches!(err, RepositoryError::CommentNotFound));
+
+        repository.delete_comment(&delete(author)).await.unwrap();
+
+        let err = repository.restore_comment(&restore(other)).await.unwrap_err();
+        assert!(matches!(err, RepositoryError::CommentNotFound));
+
+        let restored = repository.restore_comment(&restore(author)).await.unwrap();
+        assert!(restored.deleted_at.is_none());
+    }
+
+    fn restore(comment: &Comment, requester_id: Uuid) -> RestoreCommentRequest {
+        RestoreCommentRequest {
+            id: comment.id.to_string(),
+            requester_id: requester_id.to_string(),
+        }
+    }
+
+    async fn set_deleted_at(db: &Pool<Postgres>, table: &str, id: Uuid, deleted_at: DateTime<Utc>) {
+        sqlx::query(&format!("UPDATE {table} SET deleted_at = $2 WHERE id = $1"))
+            .bind(id)
+            .bind(deleted_at)
+            .execute(db)
+            .await
+            .unwrap();
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn a_comment_of_a_deleted_post_only_comes_back_with_the_post(db: Pool<Postgres>) {
+        let (repository, author, _, comment) = fixture(db.clone()).await;
+
+        let now = Utc::now();
+        set_deleted_at(&db, "comments", comment.id, now).await;
+        set_deleted_at(&db, "posts", comment.post_id, now).await;
+
+        let err = repository.restore_comment(&restore(&comment, author)).await.unwrap_err();
+        assert!(matches!(err, RepositoryError::PostDeleted));
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn a_comment_past_the_retention_window_stays_deleted(db: Pool<Postgres>) {
+        let (repository, author, _, comment) = fixture(db.clone()).await;
+
+        let err = repository.restore_comment(&restore(&comment, author)).await.unwrap_err();
+        assert!(matches!(err, RepositoryError::CommentNotDeleted));
+
+        set_deleted_at(&db, "comments", comment.id, Utc::now() - Duration::from_secs(2 * 3600)).await;
+
+        let err = repository.restore_comment(&restore(&comment, author)).await.unwrap_err();
+        assert!(matches!(err, RepositoryError::RetentionExpired));
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn purging_removes_only_expired_comments(db: Pool<Postgres>) {
+        let (repository, author, _, expired) = fixture(db.clone()).await;
+
+        let add = || AddCommentRequest {
+            user_id: author.to_string(),
+            post_id: expired.post_id.to_string(),
+            content: "Another".to_string(),
+        };
+        let live = repository.add_comment(&add()).await.unwrap();
+        let recent = repository.add_comment(&add()).await.unwrap();
+
+        set_deleted_at(&db, "comments", recent.id, Utc::now()).await;
+        set_deleted_at(&db, "comments", expired.id, Utc::now() - Duration::from_secs(2 * 3600)).await;
+
+        assert_eq!(repository.purge_deleted().await.unwrap(), 1);
+        assert_eq!(repository.purge_deleted().await.unwrap(), 0);
+
+        let mut remaining: Vec<Uuid> = sqlx::query_scalar!("SELECT id FROM comments")
+            .fetch_all(&db)
+            .await
+            .unwrap();
+        let mut expected = vec![live.id, recent.id];
+        remaining.sort();
+        expected.sort();
+        assert_eq!(remaining, expected);
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn an_edit_is_recorded_as_a_revision(db: Pool<Postgres>) {
+        let (repository, author, _, comment) = fixture(db).await;
+        assert_eq!(comment.edit_count, 0);
+        assert!(comment.edited_at.is_none());
+
+        let update = |content: &str| UpdateCommentRequest {
+            id: comment.id.to_string(),
+            content: content.to_string(),
+            user_id: author.to_string(),
+            post_id: comment.post_id.to_string(),
+            expected_versions: vec![],
+        };
+        let revisions = ListCommentRevisionsRequest { id: comment.id.to_string(), requester_id: String::new() };
+
+        let edited = repository.update_comment(&update("Edited")).await.unwrap();
+        assert_eq!(edited.edit_count, 1);
+        assert!(edited.edited_at.is_some());
+
+        let unchanged = repository.update_comment(&update("Edited")).await.unwrap();
+        assert_eq!(unchanged.edit_count, 1);
+
+        let history = repository.list_comment_revisions(&revisions, false).await.unwrap();
+        let contents: Vec<(i32, &str)> = history.iter().map(|r| (r.revision, r.content.as_str())).collect();
+        assert_eq!(contents, vec![(2, "Edited"), (1, "First")]);
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn revisions_of_a_deleted_comment_are_only_listed_when_asked_for(db: Pool<Postgres>) {
+        let (repository, author, _, comment) = fixture(db).await;
+        let revisions = ListCommentRevisionsRequest { id: comment.id.to_string(), requester_id: String::new() };
+
+        repository.delete_comment(&DeleteCommentRequest {
+            id: comment.id.to_string(),
+            requester_id: author.to_string(),
+        }).await.unwrap();
+
+        let err = repository.list_comment_revisions(&revisions, false).await.unwrap_err();
+        assert!(matches!(err, RepositoryError::CommentNotFound));
+
+        let history = repository.list_comment_revisions(&revisions, true).await.unwrap();
+        assert_eq!(history.len(), 1);
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn only_comments_under_public_posts_are_searched(db: Pool<Postgres>) {
+        let repository = CommentsRepository::new(db.clone(), Duration::from_secs(3600));
+        let author = insert_user(&db, "author").await;
+
+        let mut found = vec![];
+        for visibility in ["public", "followers", "private"] {
+            let post_id: Uuid = sqlx::query_scalar!(
+                "INSERT INTO posts (title, description, user_id, visibility) VALUES ('Title', 'Description', $1, $2) RETURNING id",
+                author,
+                visibility,
+            )
+            .fetch_one(&db)
+            .await
+            .unwrap();
+
+            let comment = repository
+                .add_comment(&AddCommentRequest {
+                    user_id: author.to_string(),
+                    post_id: post_id.to_string(),
+                    content: format!("A zebra under a {visibility} post"),
+                })
+                .await
+                .unwrap();
+            found.push(comment.id);
+        }
+
+        let hits = repository
+            .search_comments(&SearchCommentsRequest { query: "zebra".to_string(), limit: 0, offset: 0 })
+            .await
+            .unwrap();
+        let ids: Vec<Uuid> = hits.iter().map(|hit| hit.comment.id).collect();
+
+        assert_eq!(ids, vec![found[0]]);
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn highlights_escape_the_text_around_the_matches(db: Pool<Postgres>) {
+        let (repository, author, _, comment) = fixture(db).await;
+
+        repository
+            .add_comment(&AddCommentRequest {
+                user_id: author.to_string(),
+                post_id: comment.post_id.to_string(),
+                content: "Zebra <script>alert('x')</script> stripes".to_string(),
+            })
+            .await
+            .unwrap();
+
+        let hits = repository
+            .search_comments(&SearchCommentsRequest { query: "zebra".to_string(), limit: 0, offset: 0 })
+            .await
+            .unwrap();
+
+        assert_eq!(hits[0].content_highlight, "<mark>Zebra</mark> &lt;script&gt;alert(&#39;x&#39;)&lt;/script&gt; stripes");
+    }
+}
@@ -1,15 +1,46 @@
-use tonic::{Request, Response, Status};
+use tonic::service::interceptor::InterceptedService;
+use tonic::transport::Channel;
+use tonic::{Code, Request, Response, Status};
 
-use crate::{proto::proto::comments::{AddCommentRequest, AddCommentResponse, DeleteCommentRequest, DeleteCommentResponse, GetCommentRequest, GetCommentResponse, GetCommentsRequest, GetCommentsResponse, UpdateCommentRequest, UpdateCommentResponse, comments_server::Comments}, repository::CommentsRepository};
+use crate::error::{RepositoryError, map_posts_err, map_repo_err, map_validation_err};
+use crate::model::parse_uuid;
+use crate::proto::proto::posts::{GetPostRequest, posts_client::PostsClient};
+use common::telemetry::TracePropagation;
+use crate::validation::{check_search_query, validate_comment};
+use crate::{proto::proto::comments::{AddCommentRequest, AddCommentResponse, DeleteCommentRequest, DeleteCommentResponse, GetCommentRequest, GetCommentResponse, GetCommentsRequest, GetCommentsResponse, ListCommentRevisionsRequest, ListCommentRevisionsResponse, RestoreCommentRequest, RestoreCommentResponse, SearchCommentsRequest, SearchCommentsResponse, UpdateCommentRequest, UpdateCommentResponse, comments_server::Comments}, repository::CommentsRepository};
 
 #[derive(Debug)]
 pub struct CommentsService {
-    repository: CommentsRepository
+    repository: CommentsRepository,
+    posts_client: PostsClient<InterceptedService<Channel, TracePropagation>>,
+    moderator_user_ids: Vec<String>,
 }
 
 impl CommentsService {
-    pub fn new(repository: CommentsRepository) -> Self {
-        Self { repository }
+    pub fn new(repository: CommentsRepository, posts_channel: Channel, moderator_user_ids: Vec<String>) -> Self {
+        let posts_client = PostsClient::with_interceptor(posts_channel, TracePropagation);
+
+        Self { repository, posts_client, moderator_user_ids }
+    }
+
+    // Posts-service owns the visibility rules, so it's asked whether the requester can see the post.
+    // A post they can't see fails with `not_found`, the same as one that doesn't exist.
+    async fn check_post_visible(
+        &self,
+        post_id: &str,
+        requester_id: &str,
+        not_found: RepositoryError,
+    ) -> Result<(), Status> {
+        let request = GetPostRequest {
+            id: post_id.to_string(),
+            requester_id: requester_id.to_string(),
+        };
+
+        match self.posts_client.clone().get_post(request).await {
+            Ok(_) => Ok(()),
+            Err(status) if status.code() == Code::NotFound => Err(map_repo_err(not_found)),
+            Err(status) => Err(map_posts_err(status)),
+        }
     }
 }
 
@@ -22,7 +53,10 @@ impl Comments for CommentsService {
         let request = request.into_inner();
 
         let comment = self.repository.get_comment(&request)
-            .await.map_err(|_| Status::internal("DB: Error on getting comment"))?;
+            .await.map_err(map_repo_err)?;
+
+        self.check_post_visible(&comment.post_id.to_string(), &request.requester_id, RepositoryError::CommentNotFound)
+            .await?;
 
         let response = GetCommentResponse {
             comment: Some(comment.into())
@@ -36,8 +70,13 @@ impl Comments for CommentsService {
     ) -> Result<Response<GetCommentsResponse>, Status> {
         let request = request.into_inner();
 
+        parse_uuid(&request.post_id, "post_id").map_err(map_repo_err)?;
+
+        self.check_post_visible(&request.post_id, &request.requester_id, RepositoryError::PostNotFound)
+            .await?;
+
         let comment = self.repository.get_comments(&request)
-            .await.map_err(|_| Status::internal("DB: Error on getting comment"))?;
+            .await.map_err(map_repo_err)?;
 
         let response = GetCommentsResponse {
             comments: comment.into_iter().map(|c| c.into()).collect()
@@ -52,8 +91,16 @@ impl Comments for CommentsService {
     ) -> Result<Response<AddCommentResponse>, Status> {
         let request = request.into_inner();
 
+        validate_comment(&request.content)
+            .map_err(map_validation_err)?;
+
+        parse_uuid(&request.post_id, "post_id").map_err(map_repo_err)?;
+
+        self.check_post_visible(&request.post_id, &request.user_id, RepositoryError::PostNotFound)
+            .await?;
+
         let comment = self.repository.add_comment(&request)
-            .await.map_err(|_| Status::internal("DB: Error on adding comment"))?;
+            .await.map_err(map_repo_err)?;
 
         let response = AddCommentResponse {
             comment: Some(comment.into())
@@ -68,8 +115,11 @@ impl Comments for CommentsService {
     ) -> Result<Response<UpdateCommentResponse>, Status> {
         let request = request.into_inner();
 
+        validate_comment(&request.content)
+            .map_err(map_validation_err)?;
+
         let comment = self.repository.update_comment(&request)
-            .await.map_err(|_| Status::internal("DB: Error on updating comment"))?;
+            .await.map_err(map_repo_err)?;
 
         let response = UpdateCommentResponse {
             comment: Some(comment.into())
@@ -85,7 +135,7 @@ impl Comments for CommentsService {
         let request = request.into_inner();
 
         let comment = self.repository.delete_comment(&request)
-            .await.map_err(|_| Status::internal("DB: Error on deleting comment"))?;
+            .await.map_err(map_repo_err)?;
 
         let response = DeleteCommentResponse {
             comment: Some(comment.into())
@@ -93,4 +143,286 @@ impl Comments for CommentsService {
 
         Ok(Response::new(response))
     }
+
+    async fn restore_comment(
+        &self,
+        request: Request<RestoreCommentRequest>
+    ) -> Result<Response<RestoreCommentResponse>, Status> {
+        let request = request.into_inner();
+
+        let comment = self.repository.restore_comment(&request)
+            .await.map_err(map_repo_err)?;
+
+        let response = RestoreCommentResponse {
+            comment: Some(comment.into())
+        };
+
+        Ok(Response::new(response))
+    }
+
+    async fn list_comment_revisions(
+        &self,
+        request: Request<ListCommentRevisionsRequest>
+    ) -> Result<Response<ListCommentRevisionsResponse>, Status> {
+        let request = request.into_inner();
+
+        let is_moderator = self.moderator_user_ids.contains(&request.requester_id);
+
+        // Moderators see every comment, anyone else only those under posts they can see.
+        if !is_moderator {
+            let comment = self.repository.get_comment(&GetCommentRequest {
+                id: request.id.clone(),
+                requester_id: request.requester_id.clone(),
+            })
+            .await.map_err(map_repo_err)?;
+
+            self.check_post_visible(&comment.post_id.to_string(), &request.requester_id, RepositoryError::CommentNotFound)
+                .await?;
+        }
+
+        let revisions = self.repository.list_comment_revisions(&request, is_moderator)
+            .await.map_err(map_repo_err)?;
+
+        let response = ListCommentRevisionsResponse {
+            revisions: revisions.into_iter().map(|r| r.into()).collect()
+        };
+
+        Ok(Response::new(response))
+    }
+
+    async fn search_comments(
+        &self,
+        request: Request<SearchCommentsRequest>
+    ) -> Result<Response<SearchCommentsResponse>, Status> {
+        let request = request.into_inner();
+
+        check_search_query(&request.query)
+            .map_err(map_validation_err)?;
+
+        let hits = self.repository.search_comments(&request)
+            .await.map_err(map_repo_err)?;
+
+        let response = SearchCommentsResponse {
+            hits: hits.into_iter().map(Into::into).collect()
+        };
+
+        Ok(Response::new(response))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use sqlx::{Pool, Postgres, types::Uuid};
+    use tonic::transport::{Server, server::TcpIncoming};
+
+    use super::*;
+    use crate::proto::proto::posts::{self, posts_server::{Posts, PostsServer}};
+
+    async fn insert_user(db: &Pool<Postgres>, name: &str) -> Uuid {
+        sqlx::query_scalar!(
+            "INSERT INTO users (username, email, password) VALUES ($1, $2, 'x') RETURNING id",
+            name,
+            format!("{name}@example.com"),
+        )
+        .fetch_one(db)
+        .await
+        .unwrap()
+    }
+
+    // Revisions of a deleted comment are read without asking posts-service, so it is never reached here.
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn only_moderators_list_revisions_of_a_deleted_comment(db: Pool<Postgres>) {
+        let author = insert_user(&db, "author").await;
+        let moderator = insert_user(&db, "moderator").await;
+        let post_id: Uuid = sqlx::query_scalar!(
+            "INSERT INTO posts (title, description, user_id) VALUES ('Title', 'Description', $1) RETURNING id",
+            author,
+        )
+        .fetch_one(&db)
+        .await
+        .unwrap();
+        let comment_id: Uuid = sqlx::query_scalar!(
+            "INSERT INTO comments (content, post_id, user_id, deleted_at) VALUES ('Gone', $1, $2, now()) RETURNING id",
+            post_id,
+            author,
+        )
+        .fetch_one(&db)
+        .await
+        .unwrap();
+        sqlx::query!(
+            "INSERT INTO comment_revisions (comment_id, revision, content, created_at) VALUES ($1, 1, 'Gone', now())",
+            comment_id,
+        )
+        .execute(&db)
+        .await
+        .unwrap();
+
+        let posts_channel = Channel::from_static("http://127.0.0.1:1").connect_lazy();
+        let service = CommentsService::new(
+            CommentsRepository::new(db, std::time::Duration::from_secs(3600)),
+            posts_channel,
+            vec![moderator.to_string()],
+        );
+
+        let list = |requester_id: Uuid| Request::new(ListCommentRevisionsRequest {
+            id: comment_id.to_string(),
+            requester_id: requester_id.to_string(),
+        });
+
+        // Not even the author, the comment is gone for everyone but moderators.
+        let status = service.list_comment_revisions(list(author)).await.unwrap_err();
+        assert_eq!(status.code(), Code::NotFound);
+
+        let revisions = service.list_comment_revisions(list(moderator)).await.unwrap().into_inner().revisions;
+        assert_eq!(revisions.len(), 1);
+        assert_eq!(revisions[0].content, "Gone");
+    }
+
+    // Posts-service as far as comments-service sees it: who may see each post.
+    struct FakePosts {
+        readers: HashMap<String, Vec<String>>,
+    }
+
+    // Only `get_post` is asked, every other call is refused.
+    macro_rules! fake_posts {
+        ($($name:ident($request:ident) -> $response:ident;)*) => {
+            #[tonic::async_trait]
+            impl Posts for FakePosts {
+                async fn get_post(&self, request: Request<posts::GetPostRequest>) -> Result<Response<posts::GetPostResponse>, Status> {
+                    let request = request.into_inner();
+                    let readers = self.readers.get(&request.id).ok_or_else(|| Status::not_found("Post not found"))?;
+
+                    if !readers.contains(&request.requester_id) {
+                        return Err(Status::not_found("Post not found"));
+                    }
+
+                    let post = posts::Post { id: request.id, ..Default::default() };
+                    Ok(Response::new(posts::GetPostResponse { post: Some(post) }))
+                }
+
+                $(
+                    async fn $name(&self, _request: Request<posts::$request>) -> Result<Response<posts::$response>, Status> {
+                        Err(Status::unimplemented(stringify!($name)))
+                    }
+                )*
+            }
+        };
+    }
+
+    fake_posts! {
+        get_posts(GetPostsRequest) -> GetPostsResponse;
+        create_post(CreatePostRequest) -> CreatePostResponse;
+        update_post(UpdatePostRequest) -> UpdatePostResponse;
+        delete_post(DeletePostRequest) -> DeletePostResponse;
+        restore_post(RestorePostRequest) -> RestorePostResponse;
+        lock_post(LockPostRequest) -> LockPostResponse;
+        unlock_post(UnlockPostRequest) -> UnlockPostResponse;
+        list_post_revisions(ListPostRevisionsRequest) -> ListPostRevisionsResponse;
+        get_post_revision(GetPostRevisionRequest) -> GetPostRevisionResponse;
+        list_drafts(ListDraftsRequest) -> ListDraftsResponse;
+        publish_post(PublishPostRequest) -> PublishPostResponse;
+        list_posts_by_tag(ListPostsByTagRequest) -> ListPostsByTagResponse;
+        list_popular_tags(ListPopularTagsRequest) -> ListPopularTagsResponse;
+        search_posts(SearchPostsRequest) -> SearchPostsResponse;
+    }
+
+    // Comments under a post are read, listed, added to and looked back on by exactly the callers
+    // posts-service lets see the post.
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn comments_are_only_reached_through_a_visible_post(db: Pool<Postgres>) {
+        let author = insert_user(&db, "author").await;
+        let follower = insert_user(&db, "follower").await;
+        let stranger = insert_user(&db, "stranger").await;
+        let anonymous = String::new();
+
+        let mut readers = HashMap::new();
+        let mut comments = vec![];
+
+        for visibility in ["public", "followers", "private"] {
+            let post_id: Uuid = sqlx::query_scalar!(
+                "INSERT INTO posts (title, description, user_id, visibility) VALUES ('Title', 'Description', $1, $2) RETURNING id",
+                author,
+                visibility,
+            )
+            .fetch_one(&db)
+            .await
+            .unwrap();
+            let comment_id: Uuid = sqlx::query_scalar!(
+                "INSERT INTO comments (content, post_id, user_id) VALUES ('Nice', $1, $2) RETURNING id",
+                post_id,
+                author,
+            )
+            .fetch_one(&db)
+            .await
+            .unwrap();
+
+            let allowed: Vec<String> = match visibility {
+                "public" => vec![author.to_string(), follower.to_string(), stranger.to_string(), anonymous.clone()],
+                "followers" => vec![author.to_string(), follower.to_string()],
+                _ => vec![author.to_string()],
+            };
+            readers.insert(post_id.to_string(), allowed);
+            comments.push((visibility, post_id.to_string(), comment_id.to_string()));
+        }
+
+        let incoming = TcpIncoming::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let posts_url = format!("http://{}", incoming.local_addr().unwrap());
+        let allowed = readers.clone();
+        tokio::spawn(Server::builder().add_service(PostsServer::new(FakePosts { readers })).serve_with_incoming(incoming));
+
+        let service = CommentsService::new(
+            CommentsRepository::new(db, std::time::Duration::from_secs(3600)),
+            Channel::from_shared(posts_url).unwrap().connect_lazy(),
+            vec![],
+        );
+
+        let callers = [
+            ("anonymous", anonymous),
+            ("stranger", stranger.to_string()),
+            ("follower", follower.to_string()),
+            ("author", author.to_string()),
+        ];
+
+        for (name, requester_id) in &callers {
+            for (visibility, post_id, comment_id) in &comments {
+                let visible = allowed[post_id].contains(requester_id);
+                let check = |result: Result<(), Status>, what: &str| match result {
+                    Ok(()) => assert!(visible, "{name} {what} under a {visibility} post"),
+                    Err(status) => {
+                        assert!(!visible, "{name} {what} under a {visibility} post: {status:?}");
+                        assert_eq!(status.code(), Code::NotFound, "{name} {what} under a {visibility} post");
+                    },
+                };
+
+                let read = service.get_comment(Request::new(GetCommentRequest {
+                    id: comment_id.clone(),
+                    requester_id: requester_id.clone(),
+                })).await;
+                check(read.map(|_| ()), "reading a comment");
+
+                let listed = service.get_comments(Request::new(GetCommentsRequest {
+                    post_id: post_id.clone(),
+                    requester_id: requester_id.clone(),
+                })).await;
+                check(listed.map(|_| ()), "listing comments");
+
+                let revisions = service.list_comment_revisions(Request::new(ListCommentRevisionsRequest {
+                    id: comment_id.clone(),
+                    requester_id: requester_id.clone(),
+                })).await;
+                check(revisions.map(|_| ()), "listing revisions");
+
+                if !requester_id.is_empty() {
+                    let added = service.add_comment(Request::new(AddCommentRequest {
+                        user_id: requester_id.clone(),
+                        post_id: post_id.clone(),
+                        content: "Me too".to_string(),
+                    })).await;
+                    check(added.map(|_| ()), "commenting");
+                }
+            }
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,71 @@
+// Kept in sync with the `comments_content_check` constraint.
+pub const MAX_CONTENT_CHARS: usize = 2000;
+
+// A request field that failed validation.
+#[derive(Debug)]
+pub struct ValidationError {
+    pub field: &'static str,
+    pub message: &'static str,
+}
+
+pub fn check_content(content: &str) -> Result<(), ValidationError> {
+    if content.trim().is_empty() {
+        return Err(ValidationError { field: "content", message: "Comment must not be empty" });
+    }
+
+    if content.chars().count() > MAX_CONTENT_CHARS {
+        return Err(ValidationError { field: "content", message: "Comment must be at most 2000 characters long" });
+    }
+
+    // Line breaks and tabs are fine, other control characters only break rendering.
+    if content.chars().any(|c| c.is_control() && !matches!(c, '\n' | '\r' | '\t')) {
+        return Err(ValidationError { field: "content", message: "Comment contains invalid characters" });
+    }
+
+    Ok(())
+}
+
+pub fn validate_comment(content: &str) -> Result<(), ValidationError> {
+    check_content(content)?;
+
+    Ok(())
+}
+
+// Checked the same way by every service that searches.
+pub fn check_search_query(query: &str) -> Result<(), ValidationError> {
+    common::search::check_query(query).map_err(|message| ValidationError { field: "query", message })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_or_blank_comments_are_refused() {
+        assert!(validate_comment("").is_err());
+        assert!(validate_comment(" \n\t ").is_err());
+        assert!(validate_comment("Nice post").is_ok());
+    }
+
+    #[test]
+    fn a_comment_is_at_most_2000_characters() {
+        assert!(validate_comment(&"a".repeat(MAX_CONTENT_CHARS)).is_ok());
+        assert!(validate_comment(&"ä".repeat(MAX_CONTENT_CHARS)).is_ok(), "characters are counted, not bytes");
+
+        let err = validate_comment(&"a".repeat(MAX_CONTENT_CHARS + 1)).unwrap_err();
+        assert_eq!(err.field, "content");
+    }
+
+    #[test]
+    fn only_line_breaks_and_tabs_are_allowed_control_characters() {
+        assert!(validate_comment("first line\r\nsecond\tline").is_ok());
+        assert!(validate_comment("bell\u{7}").is_err());
+        assert!(validate_comment("null\0").is_err());
+    }
+
+    #[test]
+    fn a_bad_search_query_is_reported_on_the_query_field() {
+        assert!(check_search_query("rust").is_ok());
+        assert_eq!(check_search_query(" ").unwrap_err().field, "query");
+    }
+}
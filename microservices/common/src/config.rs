@@ -0,0 +1,93 @@
+use std::{env, time::Duration};
+
+use thiserror::Error;
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+// A century, far past any sensible window and well inside what a timestamp can be moved back by.
+const MAX_RETENTION_DAYS: u64 = 36_500;
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("{0} must be at least one second")]
+    ZeroInterval(&'static str),
+    #[error("{0} must be at most {MAX_RETENTION_DAYS} days")]
+    RetentionTooLong(&'static str),
+}
+
+pub fn interval_var(name: &'static str, default: u64) -> Result<Duration, ConfigError> {
+    parse_interval(name, env::var(name).ok(), default)
+}
+
+// Unset or unparsable falls back to the default. Zero is refused, `tokio::time::interval` panics on it.
+pub fn parse_interval(name: &'static str, value: Option<String>, default: u64) -> Result<Duration, ConfigError> {
+    let secs = value
+        .and_then(|secs| secs.parse().ok())
+        .unwrap_or(default);
+
+    if secs == 0 {
+        return Err(ConfigError::ZeroInterval(name));
+    }
+
+    Ok(Duration::from_secs(secs))
+}
+
+pub fn retention_var(name: &'static str, default_days: u64) -> Result<Duration, ConfigError> {
+    parse_retention(name, env::var(name).ok(), default_days)
+}
+
+// How long soft deleted rows can be restored, given in days. Unset or unparsable falls back to the
+// default, zero purges on the next run.
+pub fn parse_retention(name: &'static str, value: Option<String>, default_days: u64) -> Result<Duration, ConfigError> {
+    let days = value
+        .and_then(|days| days.parse().ok())
+        .unwrap_or(default_days);
+
+    let secs = days
+        .checked_mul(SECS_PER_DAY)
+        .filter(|_| days <= MAX_RETENTION_DAYS)
+        .ok_or(ConfigError::RetentionTooLong(name))?;
+
+    Ok(Duration::from_secs(secs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interval_defaults_when_unset_or_invalid() {
+        assert_eq!(parse_interval("X", None, 15).unwrap(), Duration::from_secs(15));
+        assert_eq!(parse_interval("X", Some("soon".to_string()), 15).unwrap(), Duration::from_secs(15));
+        assert_eq!(parse_interval("X", Some("-1".to_string()), 3600).unwrap(), Duration::from_secs(3600));
+        assert_eq!(parse_interval("X", Some("30".to_string()), 15).unwrap(), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn zero_interval_is_rejected() {
+        let err = parse_interval("SCHEDULER_INTERVAL_SECS", Some("0".to_string()), 15).unwrap_err();
+        assert!(matches!(err, ConfigError::ZeroInterval("SCHEDULER_INTERVAL_SECS")));
+
+        let err = parse_interval("PURGE_INTERVAL_SECS", Some("0".to_string()), 3600).unwrap_err();
+        assert!(matches!(err, ConfigError::ZeroInterval("PURGE_INTERVAL_SECS")));
+    }
+
+    #[test]
+    fn retention_is_given_in_days() {
+        assert_eq!(parse_retention("X", None, 30).unwrap(), Duration::from_secs(30 * SECS_PER_DAY));
+        assert_eq!(parse_retention("X", Some("a month".to_string()), 30).unwrap(), Duration::from_secs(30 * SECS_PER_DAY));
+        assert_eq!(parse_retention("X", Some("7".to_string()), 30).unwrap(), Duration::from_secs(7 * SECS_PER_DAY));
+        assert_eq!(parse_retention("X", Some("0".to_string()), 30).unwrap(), Duration::ZERO);
+    }
+
+    #[test]
+    fn retention_too_long_to_subtract_from_now_is_rejected() {
+        let max = MAX_RETENTION_DAYS.to_string();
+        assert!(parse_retention("X", Some(max), 30).is_ok());
+
+        for days in [MAX_RETENTION_DAYS + 1, u64::MAX / SECS_PER_DAY + 1, u64::MAX] {
+            let err = parse_retention("SOFT_DELETE_RETENTION_DAYS", Some(days.to_string()), 30).unwrap_err();
+            assert!(matches!(err, ConfigError::RetentionTooLong("SOFT_DELETE_RETENTION_DAYS")));
+        }
+    }
+}
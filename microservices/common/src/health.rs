@@ -0,0 +1,101 @@
+use std::time::Duration;
+
+use sqlx::PgPool;
+use tonic::server::NamedService;
+use tonic_health::{ServingStatus, server::HealthReporter};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(5);
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+// Keeps `grpc.health.v1` in step with the database pool, the service can't answer anything without it.
+// Both the overall ("") and the named service status are reported.
+pub async fn watch_database<S: NamedService>(reporter: HealthReporter, db: PgPool) {
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+    let mut last_status = None;
+
+    loop {
+        interval.tick().await;
+
+        let status = check_database(&db).await;
+        report::<S>(&reporter, &mut last_status, status).await;
+    }
+}
+
+async fn check_database(db: &PgPool) -> ServingStatus {
+    match tokio::time::timeout(CHECK_TIMEOUT, sqlx::query("SELECT 1").execute(db)).await {
+        Ok(Ok(_)) => ServingStatus::Serving,
+        Ok(Err(err)) => {
+            tracing::warn!("Database health check failed: {err}");
+            ServingStatus::NotServing
+        },
+        Err(_) => {
+            tracing::warn!("Database health check timed out");
+            ServingStatus::NotServing
+        },
+    }
+}
+
+// Only changes are reported, watchers of the health service get one update per transition.
+async fn report<S: NamedService>(reporter: &HealthReporter, last_status: &mut Option<ServingStatus>, status: ServingStatus) {
+    if *last_status != Some(status) {
+        reporter.set_service_status("", status).await;
+        reporter.set_service_status(S::NAME, status).await;
+        *last_status = Some(status);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::postgres::PgPoolOptions;
+    use tonic::Request;
+    use tonic_health::pb::{HealthCheckRequest, health_check_response, health_server::Health};
+    use tonic_health::server::HealthService;
+
+    use super::*;
+
+    struct Posts;
+
+    impl NamedService for Posts {
+        const NAME: &'static str = "posts.Posts";
+    }
+
+    async fn status_of(health: &HealthService, service: &str) -> health_check_response::ServingStatus {
+        let request = Request::new(HealthCheckRequest { service: service.to_string() });
+
+        health.check(request).await.unwrap().into_inner().status()
+    }
+
+    #[sqlx::test]
+    async fn a_reachable_database_is_serving(db: PgPool) {
+        assert_eq!(check_database(&db).await, ServingStatus::Serving);
+
+        db.close().await;
+        assert_eq!(check_database(&db).await, ServingStatus::NotServing);
+    }
+
+    #[tokio::test]
+    async fn an_unreachable_database_is_not_serving() {
+        // Nothing listens on port 1.
+        let db = PgPoolOptions::new().connect_lazy("postgres://postgres@127.0.0.1:1/social").unwrap();
+
+        assert_eq!(check_database(&db).await, ServingStatus::NotServing);
+    }
+
+    #[tokio::test]
+    async fn the_overall_and_the_named_status_follow_the_database() {
+        let reporter = HealthReporter::new();
+        let health = HealthService::from_health_reporter(reporter.clone());
+        let mut last_status = None;
+
+        for status in [ServingStatus::Serving, ServingStatus::NotServing, ServingStatus::Serving] {
+            report::<Posts>(&reporter, &mut last_status, status).await;
+
+            let expected = match status {
+                ServingStatus::Serving => health_check_response::ServingStatus::Serving,
+                _ => health_check_response::ServingStatus::NotServing,
+            };
+            assert_eq!(status_of(&health, "").await, expected);
+            assert_eq!(status_of(&health, Posts::NAME).await, expected);
+        }
+    }
+}
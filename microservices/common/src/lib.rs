@@ -0,0 +1,8 @@
+// Pieces every service shares: tracing, Prometheus metrics, gRPC health, search query checks, and
+// the settings and purge job of soft deleted rows.
+pub mod config;
+pub mod health;
+pub mod metrics;
+pub mod purge;
+pub mod search;
+pub mod telemetry;
@@ -0,0 +1,147 @@
+use std::{future::Future, net::SocketAddr, pin::Pin, task::{Context, Poll}, time::{Duration, Instant}};
+
+use metrics::{counter, gauge, histogram};
+use metrics_exporter_prometheus::{BuildError, PrometheusBuilder};
+use sqlx::PgPool;
+use tonic::Code;
+use tower::{Layer, Service};
+
+const LATENCY_BUCKETS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0];
+const POOL_SAMPLE_INTERVAL: Duration = Duration::from_secs(5);
+
+// Prometheus scrapes `/metrics` on its own port, so the gRPC listener only speaks gRPC.
+pub fn install(addr: SocketAddr, service: &'static str) -> Result<(), BuildError> {
+    PrometheusBuilder::new()
+        .with_http_listener(addr)
+        .add_global_label("service", service)
+        .set_buckets(LATENCY_BUCKETS)?
+        .install()
+}
+
+pub async fn watch_pool(db: PgPool) {
+    let mut interval = tokio::time::interval(POOL_SAMPLE_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let size = db.size() as usize;
+        let idle = db.num_idle();
+
+        gauge!("db_pool_connections", "state" => "idle").set(idle as f64);
+        gauge!("db_pool_connections", "state" => "active").set(size.saturating_sub(idle) as f64);
+        gauge!("db_pool_max_connections").set(db.options().get_max_connections() as f64);
+    }
+}
+
+// Counts and times every RPC by its path, e.g. `/posts.Posts/GetPost`.
+#[derive(Clone)]
+pub struct GrpcMetricsLayer;
+
+impl<S> Layer<S> for GrpcMetricsLayer {
+    type Service = GrpcMetrics<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        GrpcMetrics { inner }
+    }
+}
+
+#[derive(Clone)]
+pub struct GrpcMetrics<S> {
+    inner: S,
+}
+
+impl<S, ReqBody, ResBody> Service<http::Request<ReqBody>> for GrpcMetrics<S>
+where
+    S: Service<http::Request<ReqBody>, Response = http::Response<ResBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<ReqBody>) -> Self::Future {
+        let rpc = request.uri().path().to_string();
+        let started = Instant::now();
+        let future = self.inner.call(request);
+
+        Box::pin(async move {
+            let result = future.await;
+
+            // Errors are sent trailers only, so their status is in the headers. A successful
+            // unary call carries its status in the trailers, which are not read here.
+            let code = match &result {
+                Ok(response) => response.headers().get("grpc-status")
+                    .and_then(|code| code.to_str().ok()?.parse::<i32>().ok())
+                    .map(Code::from_i32)
+                    .unwrap_or(Code::Ok),
+                Err(_) => Code::Unknown,
+            };
+
+            counter!("grpc_server_requests_total", "rpc" => rpc.clone(), "code" => format!("{code:?}")).increment(1);
+            histogram!("grpc_server_request_duration_seconds", "rpc" => rpc).record(started.elapsed().as_secs_f64());
+
+            result
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::future::{Ready, ready};
+
+    use metrics_exporter_prometheus::PrometheusBuilder;
+
+    use super::*;
+
+    // Answers every call like a service whose RPC failed with `code`, trailers only.
+    #[derive(Clone)]
+    struct Answer(Option<Code>);
+
+    impl Service<http::Request<()>> for Answer {
+        type Response = http::Response<()>;
+        type Error = std::convert::Infallible;
+        type Future = Ready<Result<Self::Response, Self::Error>>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, _request: http::Request<()>) -> Self::Future {
+            let mut response = http::Response::new(());
+
+            if let Some(code) = self.0 {
+                response.headers_mut().insert("grpc-status", (code as i32).into());
+            }
+
+            ready(Ok(response))
+        }
+    }
+
+    #[test]
+    fn every_rpc_is_counted_by_path_and_code() {
+        let recorder = PrometheusBuilder::new().build_recorder();
+        let handle = recorder.handle();
+
+        metrics::with_local_recorder(&recorder, || {
+            let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+
+            runtime.block_on(async {
+                for (path, code) in [("/posts.Posts/GetPost", None), ("/posts.Posts/GetPost", None), ("/posts.Posts/GetPost", Some(Code::NotFound))] {
+                    let mut service = GrpcMetricsLayer.layer(Answer(code));
+                    service.call(http::Request::get(path).body(()).unwrap()).await.unwrap();
+                }
+            });
+        });
+
+        let rendered = handle.render();
+
+        assert!(rendered.contains(r#"grpc_server_requests_total{rpc="/posts.Posts/GetPost",code="Ok"} 2"#), "{rendered}");
+        assert!(rendered.contains(r#"grpc_server_requests_total{rpc="/posts.Posts/GetPost",code="NotFound"} 1"#), "{rendered}");
+        assert!(rendered.contains(r#"grpc_server_request_duration_seconds_count{rpc="/posts.Posts/GetPost"} 3"#), "{rendered}");
+    }
+}
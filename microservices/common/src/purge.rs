@@ -0,0 +1,70 @@
+use std::{fmt::Display, future::Future, time::Duration};
+
+use metrics::counter;
+
+// Hard deletes soft deleted rows of `table` once they can no longer be restored. Every tick
+// `purge_batch` is called until it removes nothing, so a backlog doesn't hold one long transaction.
+pub async fn run<F, Fut, E>(table: &'static str, every: Duration, mut purge_batch: F)
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<u64, E>>,
+    E: Display,
+{
+    let mut interval = tokio::time::interval(every);
+
+    loop {
+        interval.tick().await;
+        purge(table, &mut purge_batch).await;
+    }
+}
+
+// One run of the job, returns how many rows were removed.
+async fn purge<F, Fut, E>(table: &'static str, purge_batch: &mut F) -> u64
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<u64, E>>,
+    E: Display,
+{
+    let mut purged = 0;
+
+    loop {
+        match purge_batch().await {
+            Ok(0) => break,
+            Ok(removed) => purged += removed,
+            Err(err) => {
+                tracing::warn!("Purging deleted {table} failed: {err}");
+                break;
+            },
+        }
+    }
+
+    if purged > 0 {
+        counter!("soft_delete_purged_total", "table" => table).increment(purged);
+        tracing::info!("Purged {purged} deleted {table}");
+    }
+
+    purged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn batches_run_until_nothing_is_left() {
+        let mut batches = vec![Ok(1000), Ok(1000), Ok(12), Ok(0), Ok(5)].into_iter();
+        let purged = purge("posts", &mut || std::future::ready(batches.next().unwrap_or(Ok::<_, String>(0)))).await;
+
+        assert_eq!(purged, 2012);
+        assert_eq!(batches.next(), Some(Ok(5)));
+    }
+
+    #[tokio::test]
+    async fn a_failing_batch_ends_the_run() {
+        let mut batches = vec![Ok(3), Err("connection reset".to_string()), Ok(7)].into_iter();
+        let purged = purge("comments", &mut || std::future::ready(batches.next().unwrap_or(Ok(0)))).await;
+
+        assert_eq!(purged, 3);
+        assert_eq!(batches.next(), Some(Ok(7)));
+    }
+}
@@ -0,0 +1,38 @@
+// Longer queries only make the search slower without finding more.
+pub const MAX_QUERY_CHARS: usize = 200;
+
+// Checks a full text search query, the error is the message to show the user.
+pub fn check_query(query: &str) -> Result<(), &'static str> {
+    if query.trim().is_empty() {
+        return Err("Search query must not be empty");
+    }
+
+    if query.chars().count() > MAX_QUERY_CHARS {
+        return Err("Search query must be at most 200 characters long");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_blank_search_query_is_refused() {
+        assert!(check_query("").is_err());
+        assert!(check_query("  \t ").is_err());
+    }
+
+    #[test]
+    fn a_search_query_is_at_most_200_characters() {
+        assert!(check_query("rust").is_ok());
+        assert!(check_query(&"a".repeat(MAX_QUERY_CHARS)).is_ok());
+        assert!(check_query(&"a".repeat(MAX_QUERY_CHARS + 1)).is_err());
+    }
+
+    #[test]
+    fn the_search_query_limit_counts_characters_not_bytes() {
+        assert!(check_query(&"ä".repeat(MAX_QUERY_CHARS)).is_ok());
+    }
+}
@@ -0,0 +1,321 @@
+use std::{env, future::Future, pin::Pin, task::{Context, Poll}};
+
+use opentelemetry::{global, propagation::Injector, trace::TracerProvider as _};
+use opentelemetry_http::HeaderExtractor;
+use opentelemetry_otlp::SpanExporter;
+use opentelemetry_sdk::{Resource, propagation::TraceContextPropagator, trace::SdkTracerProvider};
+use tonic::metadata::{MetadataKey, MetadataMap, MetadataValue};
+use tonic::{Status, service::Interceptor};
+use tower::{Layer, Service};
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{EnvFilter, layer::SubscriberExt, util::SubscriberInitExt};
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+// Id of the request being handled, as the caller sent it.
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(Clone::clone).ok().filter(|id| !id.is_empty())
+}
+
+// Logs go to stdout filtered by `RUST_LOG`, `log` records such as actix's included. Spans always
+// carry W3C trace context so it can be passed on, they are only exported when
+// `OTEL_EXPORTER_OTLP_ENDPOINT` points at a collector.
+pub fn init(service: &'static str) -> Result<SdkTracerProvider, Box<dyn std::error::Error>> {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    let exporter = match env::var("OTEL_EXPORTER_OTLP_ENDPOINT") {
+        Ok(_) => Some(SpanExporter::builder().with_http().build()?),
+        Err(_) => None,
+    };
+    let provider = tracer_provider(service, exporter);
+
+    tracing_subscriber::registry()
+        .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")))
+        .with(tracing_subscriber::fmt::layer())
+        .with(tracing_opentelemetry::layer().with_tracer(provider.tracer(service)))
+        .try_init()?;
+
+    Ok(provider)
+}
+
+fn tracer_provider(service: &'static str, exporter: Option<SpanExporter>) -> SdkTracerProvider {
+    let mut provider = SdkTracerProvider::builder()
+        .with_resource(Resource::builder().with_service_name(service).build());
+
+    if let Some(exporter) = exporter {
+        provider = provider.with_batch_exporter(exporter);
+    }
+
+    provider.build()
+}
+
+// One span per RPC, continuing the caller's trace and tagged with the gateway's request id.
+pub fn request_span(request: &http::Request<()>) -> Span {
+    let request_id = request.headers().get(REQUEST_ID_HEADER)
+        .and_then(|id| id.to_str().ok())
+        .unwrap_or_default();
+
+    let span = tracing::info_span!("grpc_request", rpc = %request.uri().path(), request_id = %request_id);
+
+    let parent = global::get_text_map_propagator(|propagator| propagator.extract(&HeaderExtractor(request.headers())));
+    let _ = span.set_parent(parent);
+
+    span
+}
+
+// Keeps the caller's request id around while an RPC is handled, so `TracePropagation` can pass it on.
+#[derive(Debug, Clone, Copy)]
+pub struct RequestIdLayer;
+
+impl<S> Layer<S> for RequestIdLayer {
+    type Service = RequestId<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequestId { inner }
+    }
+}
+
+#[derive(Clone)]
+pub struct RequestId<S> {
+    inner: S,
+}
+
+impl<S, ReqBody> Service<http::Request<ReqBody>> for RequestId<S>
+where
+    S: Service<http::Request<ReqBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<ReqBody>) -> Self::Future {
+        let request_id = request.headers().get(REQUEST_ID_HEADER)
+            .and_then(|id| id.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+
+        Box::pin(REQUEST_ID.scope(request_id, self.inner.call(request)))
+    }
+}
+
+struct MetadataInjector<'a>(&'a mut MetadataMap);
+
+impl Injector for MetadataInjector<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        if let (Ok(key), Ok(value)) = (MetadataKey::from_bytes(key.as_bytes()), MetadataValue::try_from(value)) {
+            self.0.insert(key, value);
+        }
+    }
+}
+
+// Continues the current trace on calls to other services and passes the request id on.
+#[derive(Debug, Clone, Copy)]
+pub struct TracePropagation;
+
+impl Interceptor for TracePropagation {
+    fn call(&mut self, mut request: tonic::Request<()>) -> Result<tonic::Request<()>, Status> {
+        let context = Span::current().context();
+        global::get_text_map_propagator(|propagator| {
+            propagator.inject_context(&context, &mut MetadataInjector(request.metadata_mut()))
+        });
+
+        if let Some(request_id) = current_request_id() {
+            MetadataInjector(request.metadata_mut()).set(REQUEST_ID_HEADER, request_id);
+        }
+
+        Ok(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex, mpsc};
+    use std::thread;
+    use std::time::Duration;
+
+    use opentelemetry::trace::{TraceContextExt, TraceId};
+    use opentelemetry_otlp::WithExportConfig;
+    use tonic::transport::{Endpoint, Server, server::TcpIncoming};
+    use tonic_health::pb::{HealthCheckRequest, HealthCheckResponse, health_client::HealthClient, health_server::{Health, HealthServer}};
+    use tracing_subscriber::Registry;
+
+    use super::*;
+
+    // Accepts OTLP/HTTP exports and hands over the path and protobuf body of each one.
+    fn otlp_receiver() -> (String, mpsc::Receiver<(String, Vec<u8>)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}/v1/traces", listener.local_addr().unwrap());
+        let (sender, receiver) = mpsc::channel();
+
+        thread::spawn(move || {
+            for mut stream in listener.incoming().map_while(Result::ok) {
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':')
+                        && name.eq_ignore_ascii_case("content-length")
+                    {
+                        length = value.trim().parse().unwrap();
+                    }
+                }
+
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n").unwrap();
+
+                let path = request_line.split_whitespace().nth(1).unwrap_or_default().to_string();
+                let _ = sender.send((path, body));
+            }
+        });
+
+        (endpoint, receiver)
+    }
+
+    fn subscriber(provider: &SdkTracerProvider) -> impl tracing::Subscriber + Send + Sync {
+        Registry::default().with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")))
+    }
+
+    fn contains(haystack: &[u8], needle: &str) -> bool {
+        haystack.windows(needle.len()).any(|window| window == needle.as_bytes())
+    }
+
+    #[test]
+    fn spans_are_exported_over_otlp() {
+        let (endpoint, exports) = otlp_receiver();
+        let exporter = SpanExporter::builder().with_http().with_endpoint(endpoint).build().unwrap();
+        let provider = tracer_provider("test-service", Some(exporter));
+
+        tracing::subscriber::with_default(subscriber(&provider), || {
+            let _span = tracing::info_span!("exported_span").entered();
+        });
+        provider.force_flush().unwrap();
+
+        let (path, body) = exports.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(path, "/v1/traces");
+        assert!(contains(&body, "exported_span"));
+        assert!(contains(&body, "test-service"));
+    }
+
+    type Seen = Arc<Mutex<Option<(String, String)>>>;
+
+    // The last hop, remembers the request id and traceparent it was called with.
+    struct Users(Seen);
+
+    // The middle hop, calls users while handling the request.
+    struct Posts(String);
+
+    fn header(request: &tonic::Request<HealthCheckRequest>, key: &str) -> String {
+        request.metadata().get(key).and_then(|value| value.to_str().ok()).unwrap_or_default().to_string()
+    }
+
+    #[tonic::async_trait]
+    impl Health for Users {
+        type WatchStream = tonic::codegen::BoxStream<HealthCheckResponse>;
+
+        async fn check(&self, request: tonic::Request<HealthCheckRequest>) -> Result<tonic::Response<HealthCheckResponse>, Status> {
+            *self.0.lock().unwrap() = Some((header(&request, REQUEST_ID_HEADER), header(&request, "traceparent")));
+
+            Ok(tonic::Response::new(HealthCheckResponse::default()))
+        }
+
+        async fn watch(&self, _request: tonic::Request<HealthCheckRequest>) -> Result<tonic::Response<Self::WatchStream>, Status> {
+            Err(Status::unimplemented("watch"))
+        }
+    }
+
+    #[tonic::async_trait]
+    impl Health for Posts {
+        type WatchStream = tonic::codegen::BoxStream<HealthCheckResponse>;
+
+        async fn check(&self, _request: tonic::Request<HealthCheckRequest>) -> Result<tonic::Response<HealthCheckResponse>, Status> {
+            let channel = Endpoint::from_shared(self.0.clone()).unwrap().connect_lazy();
+            HealthClient::with_interceptor(channel, TracePropagation).check(HealthCheckRequest::default()).await
+        }
+
+        async fn watch(&self, _request: tonic::Request<HealthCheckRequest>) -> Result<tonic::Response<Self::WatchStream>, Status> {
+            Err(Status::unimplemented("watch"))
+        }
+    }
+
+    fn serve(service: impl Health) -> String {
+        let incoming = TcpIncoming::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let url = format!("http://{}", incoming.local_addr().unwrap());
+
+        tokio::spawn(
+            Server::builder()
+                .trace_fn(request_span)
+                .layer(RequestIdLayer)
+                .add_service(HealthServer::new(service))
+                .serve_with_incoming(incoming),
+        );
+
+        url
+    }
+
+    // Spawned servers run on the test's thread, so they see its subscriber.
+    #[tokio::test]
+    async fn request_id_and_trace_reach_the_second_hop() {
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        let provider = tracer_provider("test-service", None);
+        let _subscriber = tracing::subscriber::set_default(subscriber(&provider));
+
+        let seen = Seen::default();
+        let users = serve(Users(seen.clone()));
+        let posts = serve(Posts(users));
+
+        let trace_id = "4bf92f3577b34da6a3ce929d0e0e4736";
+        let mut request = tonic::Request::new(HealthCheckRequest::default());
+        request.metadata_mut().insert(REQUEST_ID_HEADER, "request-1".parse().unwrap());
+        request.metadata_mut().insert("traceparent", format!("00-{trace_id}-00f067aa0ba902b7-01").parse().unwrap());
+
+        let channel = Endpoint::from_shared(posts).unwrap().connect_lazy();
+        HealthClient::new(channel).check(request).await.unwrap();
+
+        let (request_id, traceparent) = seen.lock().unwrap().clone().unwrap();
+        assert_eq!(request_id, "request-1");
+        assert!(traceparent.contains(trace_id), "{traceparent}");
+    }
+
+    #[test]
+    fn called_service_continues_the_callers_trace() {
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        let provider = tracer_provider("test-service", None);
+
+        tracing::subscriber::with_default(subscriber(&provider), || {
+            let caller = tracing::info_span!("caller");
+            let request = caller.in_scope(|| TracePropagation.call(tonic::Request::new(()))).unwrap();
+
+            let headers = request.metadata().clone().into_headers();
+            let mut request = http::Request::builder().uri("/posts.Posts/GetPost").body(()).unwrap();
+            *request.headers_mut() = headers;
+
+            let caller_trace = caller.context().span().span_context().trace_id();
+            let called_trace = request_span(&request).context().span().span_context().trace_id();
+
+            assert!(request.headers().contains_key("traceparent"));
+            assert_ne!(caller_trace, TraceId::INVALID);
+            assert_eq!(called_trace, caller_trace);
+        });
+    }
+}
@@ -0,0 +1,82 @@
+use std::collections::BTreeSet;
+
+// Longer runs after a '#' are not taken as tags.
+const MAX_TAG_LENGTH: usize = 50;
+
+fn is_tag_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+// Lowercases a tag body, `None` when it is too long or has no letter (so "#1" isn't a tag).
+pub fn normalize_tag(body: &str) -> Option<String> {
+    let tag = body.to_lowercase();
+
+    if tag.chars().count() > MAX_TAG_LENGTH || !tag.chars().any(char::is_alphabetic) {
+        return None;
+    }
+
+    Some(tag)
+}
+
+// Hashtags in the texts, deduplicated and sorted. A tag is a '#' not preceded by a word character
+// ("a#b" has none), followed by letters, digits or underscores. The hashtags migration backfills
+// existing posts with the same rules.
+pub fn extract_hashtags(texts: &[&str]) -> Vec<String> {
+    let mut tags = BTreeSet::new();
+
+    for text in texts {
+        let chars: Vec<char> = text.chars().collect();
+        let mut i = 0;
+
+        while i < chars.len() {
+            if chars[i] != '#' || (i > 0 && is_tag_char(chars[i - 1])) {
+                i += 1;
+                continue;
+            }
+
+            let body: String = chars[i + 1..].iter().take_while(|c| is_tag_char(**c)).collect();
+            i += 1 + body.chars().count();
+
+            if let Some(tag) = normalize_tag(&body) {
+                tags.insert(tag);
+            }
+        }
+    }
+
+    tags.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tags_are_lowercased_deduplicated_and_sorted() {
+        let tags = extract_hashtags(&["Learning #Rust and #sql", "more #rust, #SQL and #async_await!"]);
+
+        assert_eq!(tags, ["async_await", "rust", "sql"]);
+    }
+
+    #[test]
+    fn a_tag_starts_at_a_hash_that_does_not_follow_a_word() {
+        assert_eq!(extract_hashtags(&["a#b c_#d"]), Vec::<String>::new());
+        assert_eq!(extract_hashtags(&["(#one) ##two end#"]), ["one", "two"]);
+    }
+
+    #[test]
+    fn a_tag_needs_a_letter_and_at_most_fifty_characters() {
+        let longest = "a".repeat(MAX_TAG_LENGTH);
+        let too_long = "a".repeat(MAX_TAG_LENGTH + 1);
+
+        assert_eq!(normalize_tag("2024"), None);
+        assert_eq!(normalize_tag("2024q1"), Some("2024q1".to_string()));
+        assert_eq!(normalize_tag(&longest), Some(longest.clone()));
+        assert_eq!(normalize_tag(&too_long), None);
+        assert_eq!(extract_hashtags(&[&format!("#{too_long} #1")]), Vec::<String>::new());
+    }
+
+    #[test]
+    fn letters_outside_ascii_are_part_of_a_tag() {
+        assert_eq!(extract_hashtags(&["#Café #日本"]), ["café", "日本"]);
+    }
+}
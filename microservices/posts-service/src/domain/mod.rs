@@ -1 +1,4 @@
-pub mod time;
\ No newline at end of file
+pub mod time;
+pub mod status;
+pub mod visibility;
+pub mod hashtags;
\ No newline at end of file
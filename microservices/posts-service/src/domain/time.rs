@@ -6,4 +6,8 @@ pub fn datetime_to_timestamp(dt: DateTime<Utc>) -> Option<Timestamp> {
         seconds: dt.timestamp(),
         nanos: dt.timestamp_subsec_nanos() as i32,
     })
+}
+
+pub fn optional_timestamp_to_datetime(ts: Option<Timestamp>) -> Option<DateTime<Utc>> {
+    ts.and_then(|t| DateTime::from_timestamp(t.seconds, t.nanos as u32))
 }
\ No newline at end of file
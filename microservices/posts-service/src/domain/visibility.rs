@@ -0,0 +1,36 @@
+use uuid::Uuid;
+
+// Who can see a published post, stored as text in `posts.visibility`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PostVisibility {
+    Public,
+    // Anyone following the author, following needs no approval so this is not a privacy setting.
+    Followers,
+    Private,
+}
+
+impl PostVisibility {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Public => "public",
+            Self::Followers => "followers",
+            Self::Private => "private",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "public" => Some(Self::Public),
+            "followers" => Some(Self::Followers),
+            "private" => Some(Self::Private),
+            _ => None,
+        }
+    }
+}
+
+// The caller of a read, `following` are the users whose followers-only posts they may see.
+#[derive(Debug, Default)]
+pub struct Viewer {
+    pub id: Option<Uuid>,
+    pub following: Vec<Uuid>,
+}
@@ -1,15 +1,228 @@
+use prost::Message;
+use prost_types::Any;
 use thiserror::Error;
+use tonic::{Code, Status};
+
+use crate::proto::proto::google::rpc::{self, BadRequest, ErrorInfo, PreconditionFailure, ResourceInfo, bad_request::FieldViolation, precondition_failure::Violation};
+use crate::validation::ValidationError;
+
+// Identifies this service in `google.rpc.ErrorInfo`, reasons are only unique within it.
+const ERROR_DOMAIN: &str = "posts-service";
 
 #[derive(Debug, Error)]
 pub enum RepositoryError {
     #[error("database error: {0}")]
     DatabaseError(#[from] sqlx::Error),
 
-    #[error("Invalid UUID: {0}")]
-    InvalidUUID(#[from] uuid::Error),
+    #[error("Invalid {field}: {source}")]
+    InvalidUUID {
+        field: &'static str,
+        source: uuid::Error,
+    },
+
+    #[error("Invalid {}: {}", .0.field, .0.message)]
+    InvalidField(ValidationError),
 
     #[error("Post with this id not found")]
     PostNotFound,
 
+    #[error("User with this id not found")]
+    UserNotFound,
+
+    #[error("Post revision not found")]
+    RevisionNotFound,
+
+    #[error("Post was changed since it was read")]
+    VersionMismatch,
+
+    #[error("Post is already published")]
+    PostAlreadyPublished,
+
+    #[error("Post is not deleted")]
+    PostNotDeleted,
+
+    #[error("Post was deleted before the retention window")]
+    RetentionExpired,
+}
+
+// Lets request conversions in model.rs use `?` on validation checks.
+impl From<ValidationError> for RepositoryError {
+    fn from(err: ValidationError) -> Self {
+        RepositoryError::InvalidField(err)
+    }
+}
+
+fn pack<M: Message>(type_name: &str, message: &M) -> Any {
+    Any {
+        type_url: format!("type.googleapis.com/google.rpc.{type_name}"),
+        value: message.encode_to_vec(),
+    }
+}
+
+// Builds a status whose `grpc-status-details-bin` carries an `ErrorInfo` with `reason` plus the given details.
+fn status_with_details(code: Code, message: &str, reason: &str, details: Vec<Any>) -> Status {
+    let error_info = ErrorInfo {
+        reason: reason.to_string(),
+        domain: ERROR_DOMAIN.to_string(),
+        metadata: Default::default(),
+    };
+
+    let status = rpc::Status {
+        code: code as i32,
+        message: message.to_string(),
+        details: std::iter::once(pack("ErrorInfo", &error_info)).chain(details).collect(),
+    };
+
+    Status::with_details(code, message, status.encode_to_vec().into())
+}
+
+pub fn invalid_field(field: &str, description: &str, reason: &str) -> Status {
+    let bad_request = BadRequest {
+        field_violations: vec![FieldViolation {
+            field: field.to_string(),
+            description: description.to_string(),
+        }],
+    };
+
+    status_with_details(Code::InvalidArgument, description, reason, vec![pack("BadRequest", &bad_request)])
+}
+
+pub fn resource_not_found(resource_type: &str, description: &str, reason: &str) -> Status {
+    let resource_info = ResourceInfo {
+        resource_type: resource_type.to_string(),
+        description: description.to_string(),
+        ..Default::default()
+    };
+
+    status_with_details(Code::NotFound, description, reason, vec![pack("ResourceInfo", &resource_info)])
+}
+
+pub fn precondition_failed(subject: &str, description: &str, reason: &str) -> Status {
+    let precondition_failure = PreconditionFailure {
+        violations: vec![Violation {
+            r#type: reason.to_string(),
+            subject: subject.to_string(),
+            description: description.to_string(),
+        }],
+    };
+
+    status_with_details(Code::FailedPrecondition, description, reason, vec![pack("PreconditionFailure", &precondition_failure)])
+}
+
+// The follow graph lives in users-service, reads that need it can't be answered without it.
+pub fn map_users_err(status: Status) -> Status {
+    tracing::error!("Users service error: {}", status);
+
+    status_with_details(Code::Unavailable, "users service is unavailable", "USERS_SERVICE_UNAVAILABLE", vec![])
+}
+
+pub fn map_validation_err(err: ValidationError) -> Status {
+    invalid_field(err.field, err.message, "VALIDATION_FAILED")
+}
+
+pub fn map_repo_err(err: RepositoryError) -> Status {
+    match err {
+        RepositoryError::PostNotFound => {
+            resource_not_found("post", "post with this id not found", "POST_NOT_FOUND")
+        },
+        RepositoryError::UserNotFound => {
+            resource_not_found("user", "user with this id not found", "USER_NOT_FOUND")
+        },
+        RepositoryError::RevisionNotFound => {
+            resource_not_found("post_revision", "post revision not found", "REVISION_NOT_FOUND")
+        },
+        RepositoryError::VersionMismatch => {
+            precondition_failed("post", "post was changed since it was read", "VERSION_MISMATCH")
+        },
+        RepositoryError::PostAlreadyPublished => {
+            precondition_failed("post", "post is already published", "POST_ALREADY_PUBLISHED")
+        },
+        RepositoryError::PostNotDeleted => {
+            precondition_failed("post", "post is not deleted", "POST_NOT_DELETED")
+        },
+        RepositoryError::RetentionExpired => {
+            precondition_failed("post", "post can no longer be restored", "RETENTION_EXPIRED")
+        },
+        RepositoryError::InvalidField(err) => map_validation_err(err),
+        RepositoryError::InvalidUUID { field, .. } => {
+            invalid_field(field, &format!("{field} is not a valid uuid"), "INVALID_ID")
+        },
+        RepositoryError::DatabaseError(err) => {
+            tracing::error!("Database error: {}", err);
+
+            match err {
+                sqlx::Error::PoolTimedOut | sqlx::Error::PoolClosed | sqlx::Error::Io(_) => {
+                    status_with_details(Code::Unavailable, "database is unavailable", "DATABASE_UNAVAILABLE", vec![])
+                },
+                _ => status_with_details(Code::Internal, "internal server error", "INTERNAL", vec![]),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The `ErrorInfo` reason the gateway reads from the status details.
+    fn reason(status: &Status) -> String {
+        let details = rpc::Status::decode(status.details()).unwrap();
+
+        details.details.iter()
+            .find(|any| any.type_url.ends_with("google.rpc.ErrorInfo"))
+            .map(|any| ErrorInfo::decode(any.value.as_slice()).unwrap().reason)
+            .unwrap()
+    }
+
+    #[test]
+    fn repository_errors_map_to_codes_and_reasons() {
+        let cases = [
+            (RepositoryError::PostNotFound, Code::NotFound, "POST_NOT_FOUND"),
+            (RepositoryError::UserNotFound, Code::NotFound, "USER_NOT_FOUND"),
+            (RepositoryError::RevisionNotFound, Code::NotFound, "REVISION_NOT_FOUND"),
+            (RepositoryError::VersionMismatch, Code::FailedPrecondition, "VERSION_MISMATCH"),
+            (RepositoryError::PostAlreadyPublished, Code::FailedPrecondition, "POST_ALREADY_PUBLISHED"),
+            (RepositoryError::PostNotDeleted, Code::FailedPrecondition, "POST_NOT_DELETED"),
+            (RepositoryError::RetentionExpired, Code::FailedPrecondition, "RETENTION_EXPIRED"),
+            (
+                RepositoryError::InvalidField(ValidationError { field: "title", message: "Title must be 1-50 characters long" }),
+                Code::InvalidArgument,
+                "VALIDATION_FAILED",
+            ),
+            (
+                RepositoryError::InvalidUUID { field: "id", source: "x".parse::<uuid::Uuid>().unwrap_err() },
+                Code::InvalidArgument,
+                "INVALID_ID",
+            ),
+            (RepositoryError::DatabaseError(sqlx::Error::PoolTimedOut), Code::Unavailable, "DATABASE_UNAVAILABLE"),
+            (RepositoryError::DatabaseError(sqlx::Error::RowNotFound), Code::Internal, "INTERNAL"),
+        ];
+
+        for (err, code, expected) in cases {
+            let status = map_repo_err(err);
+
+            assert_eq!((status.code(), reason(&status).as_str()), (code, expected));
+        }
+    }
+
+    #[test]
+    fn invalid_fields_name_the_field() {
+        let status = map_repo_err(RepositoryError::InvalidField(ValidationError { field: "title", message: "Title must be 1-50 characters long" }));
+        let details = rpc::Status::decode(status.details()).unwrap();
+
+        let bad_request = details.details.iter()
+            .find(|any| any.type_url.ends_with("google.rpc.BadRequest"))
+            .map(|any| BadRequest::decode(any.value.as_slice()).unwrap())
+            .unwrap();
+
+        assert_eq!(bad_request.field_violations[0].field, "title");
+        assert_eq!(status.message(), "Title must be 1-50 characters long");
+    }
+
+    #[test]
+    fn an_unreachable_users_service_makes_the_read_unavailable() {
+        let status = map_users_err(Status::unavailable("connection refused"));
 
-}
\ No newline at end of file
+        assert_eq!((status.code(), reason(&status).as_str()), (Code::Unavailable, "USERS_SERVICE_UNAVAILABLE"));
+    }
+}
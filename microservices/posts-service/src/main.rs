@@ -1,7 +1,7 @@
 use std::net::SocketAddr;
 
 use sqlx::postgres::PgPoolOptions;
-use tonic::transport::Server;
+use tonic::transport::{Channel, Server};
 
 use crate::config::Config;
 use crate::proto::proto::posts::posts_server::{ PostsServer };
@@ -16,26 +16,49 @@ pub mod error;
 pub mod model;
 pub mod domain;
 pub mod validation;
+pub mod scheduler;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     dotenvy::dotenv().ok();
 
-    let config = Config::from_env();
+    let _tracer_provider = common::telemetry::init("posts-service")?;
+
+    let config = Config::from_env()?;
 
     let addr: SocketAddr = config.microservice_url.parse()?;
 
+    common::metrics::install(config.metrics_url.parse()?, "posts-service")?;
+
     let db = PgPoolOptions::new()
         .max_connections(5)
         .connect(&config.database_url)
         .await?;
 
-    let repository = PostsRepository::new(db);
-    let service = PostsService::new(repository);
+    let (health_reporter, health_service) = tonic_health::server::health_reporter();
+    tokio::spawn(common::metrics::watch_pool(db.clone()));
+    tokio::spawn(common::health::watch_database::<PostsServer<PostsService>>(health_reporter, db.clone()));
+
+    let repository = PostsRepository::new(db, config.retention);
+    let purging = repository.clone();
+    tokio::spawn(common::purge::run("posts", config.purge_interval, move || {
+        let repository = purging.clone();
+        async move { repository.purge_deleted().await }
+    }));
+    tokio::spawn(scheduler::run(repository.clone(), config.scheduler_interval));
+
+    // Connected on first use so the service can start before users-service is up.
+    let users_channel = Channel::from_shared(config.users_service_url)?.connect_lazy();
+
+    let service = PostsService::new(repository, users_channel, config.moderator_user_ids);
 
-    println!("Posts service listening on {}", addr);
+    tracing::info!("Posts service listening on {}", addr);
 
     Server::builder()
+        .trace_fn(common::telemetry::request_span)
+        .layer(common::metrics::GrpcMetricsLayer)
+        .layer(common::telemetry::RequestIdLayer)
+        .add_service(health_service)
         .add_service(PostsServer::new(service))
         .serve(addr)
         .await?;
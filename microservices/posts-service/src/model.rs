@@ -1,8 +1,12 @@
 use chrono::{DateTime, Utc};
 use sqlx::prelude::FromRow;
 use uuid::Uuid;
-use crate::proto::proto::posts::{CreatePostRequest, DeletePostRequest, GetPostRequest, Post as ProtoPost, UpdatePostRequest};
-use crate::{domain::time::datetime_to_timestamp};
+use crate::proto::proto::posts::{CreatePostRequest, DeletePostRequest, GetPostRequest, GetPostRevisionRequest, ListDraftsRequest, ListPopularTagsRequest, ListPostRevisionsRequest, ListPostsByTagRequest, LockPostRequest, PopularTag as ProtoPopularTag, Post as ProtoPost, PostSearchHit as ProtoPostSearchHit, PostRevision as ProtoPostRevision, PublishPostRequest, RestorePostRequest, SearchPostsRequest, UnlockPostRequest, UpdatePostRequest};
+use crate::domain::status::PostStatus;
+use crate::domain::visibility::PostVisibility;
+use crate::domain::time::{datetime_to_timestamp, optional_timestamp_to_datetime};
+use crate::error::RepositoryError;
+use crate::validation::{check_publish_at, parse_status, parse_tag, parse_visibility};
 
 #[derive(Debug, FromRow)]
 pub struct Post {
@@ -12,6 +16,15 @@ pub struct Post {
     pub user_id: Uuid,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub locked_at: Option<DateTime<Utc>>,
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub edited_at: Option<DateTime<Utc>>,
+    pub revision: i32,
+    pub version: i64,
+    pub status: String,
+    pub publish_at: Option<DateTime<Utc>>,
+    pub published_at: Option<DateTime<Utc>>,
+    pub visibility: String,
 }
 
 impl From<Post> for ProtoPost {
@@ -23,22 +36,101 @@ impl From<Post> for ProtoPost {
             user_id: post.user_id.to_string(),
             created_at: datetime_to_timestamp(post.created_at),
             updated_at: datetime_to_timestamp(post.updated_at),
+            locked_at: post.locked_at.and_then(datetime_to_timestamp),
+            deleted_at: post.deleted_at.and_then(datetime_to_timestamp),
+            edited_at: post.edited_at.and_then(datetime_to_timestamp),
+            revision: post.revision,
+            version: post.version,
+            status: post.status,
+            publish_at: post.publish_at.and_then(datetime_to_timestamp),
+            published_at: post.published_at.and_then(datetime_to_timestamp),
+            visibility: post.visibility,
         }
     }
 }
 
+#[derive(Debug, FromRow)]
+pub struct PostRevision {
+    pub post_id: Uuid,
+    pub revision: i32,
+    pub title: String,
+    pub description: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<PostRevision> for ProtoPostRevision {
+    fn from(revision: PostRevision) -> ProtoPostRevision {
+        ProtoPostRevision {
+            post_id: revision.post_id.to_string(),
+            revision: revision.revision,
+            title: revision.title,
+            description: revision.description,
+            created_at: datetime_to_timestamp(revision.created_at),
+        }
+    }
+}
+
+#[derive(Debug, FromRow)]
+pub struct PopularTag {
+    pub tag: String,
+    pub post_count: i64,
+}
+
+impl From<PopularTag> for ProtoPopularTag {
+    fn from(tag: PopularTag) -> ProtoPopularTag {
+        ProtoPopularTag {
+            tag: tag.tag,
+            post_count: tag.post_count,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct PostSearchHit {
+    pub post: Post,
+    pub rank: f32,
+    pub title_highlight: String,
+    pub description_highlight: String,
+}
+
+impl From<PostSearchHit> for ProtoPostSearchHit {
+    fn from(hit: PostSearchHit) -> ProtoPostSearchHit {
+        ProtoPostSearchHit {
+            post: Some(hit.post.into()),
+            rank: hit.rank,
+            title_highlight: hit.title_highlight,
+            description_highlight: hit.description_highlight,
+        }
+    }
+}
+
+pub fn parse_uuid(value: &str, field: &'static str) -> Result<Uuid, RepositoryError> {
+    Uuid::parse_str(value)
+        .map_err(|source| RepositoryError::InvalidUUID { field, source })
+}
+
+// Empty means the field was left out, e.g. an anonymous requester.
+pub fn parse_optional_uuid(value: &str, field: &'static str) -> Result<Option<Uuid>, RepositoryError> {
+    if value.is_empty() {
+        return Ok(None);
+    }
+
+    parse_uuid(value, field).map(Some)
+}
+
 // ----------------------------
 
 pub struct GetPostRepo {
     pub id: Uuid,
 }
 
+// The requester is resolved into a `Viewer` by the service.
 impl TryFrom<GetPostRequest> for GetPostRepo {
-    type Error = uuid::Error;
+    type Error = RepositoryError;
 
     fn try_from(value: GetPostRequest) -> Result<Self, Self::Error> {
         Ok(Self {
-            id: Uuid::parse_str(&value.id)?
+            id: parse_uuid(&value.id, "id")?,
         })
     }
 }
@@ -49,16 +141,26 @@ pub struct CreatePostRepo {
     pub user_id: Uuid,
     pub title: String,
     pub description: String,
+    pub status: PostStatus,
+    pub publish_at: Option<DateTime<Utc>>,
+    pub visibility: PostVisibility,
 }
 
 impl TryFrom<CreatePostRequest> for CreatePostRepo {
-    type Error = uuid::Error;
+    type Error = RepositoryError;
 
     fn try_from(value: CreatePostRequest) -> Result<Self, Self::Error> {
+        let status = parse_status(&value.status)?;
+        let publish_at = optional_timestamp_to_datetime(value.publish_at);
+        check_publish_at(status, publish_at)?;
+
         Ok(Self {
             title: value.title,
             description: value.description,
-            user_id: Uuid::parse_str(&value.user_id)?,
+            user_id: parse_uuid(&value.user_id, "user_id")?,
+            status,
+            publish_at,
+            visibility: parse_visibility(&value.visibility)?,
         })
     }
 }
@@ -67,18 +169,24 @@ impl TryFrom<CreatePostRequest> for CreatePostRepo {
 
 pub struct UpdatePostRepo {
     pub id: Uuid,
-    pub title: String,
-    pub description: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub expected_versions: Vec<i64>,
+    pub visibility: Option<PostVisibility>,
+    pub requester_id: Uuid,
 }
 
 impl TryFrom<UpdatePostRequest> for UpdatePostRepo {
-    type Error = uuid::Error;
+    type Error = RepositoryError;
 
     fn try_from(value: UpdatePostRequest) -> Result<Self, Self::Error> {
         Ok(Self {
-            id: Uuid::parse_str(&value.id)?,
+            id: parse_uuid(&value.id, "id")?,
             title: value.title,
             description: value.description,
+            expected_versions: value.expected_versions,
+            visibility: value.visibility.as_deref().map(parse_visibility).transpose()?,
+            requester_id: parse_uuid(&value.requester_id, "requester_id")?,
         })
     }
 }
@@ -86,15 +194,204 @@ impl TryFrom<UpdatePostRequest> for UpdatePostRepo {
 // -----------------------------
 
 pub struct DeletePostRepo {
-    pub id: Uuid
+    pub id: Uuid,
+    pub requester_id: Uuid,
 }
 
 impl TryFrom<DeletePostRequest> for DeletePostRepo {
-    type Error = uuid::Error;
+    type Error = RepositoryError;
 
     fn try_from(value: DeletePostRequest) -> Result<Self, Self::Error> {
         Ok(Self {
-            id: Uuid::parse_str(&value.id)?,
+            id: parse_uuid(&value.id, "id")?,
+            requester_id: parse_uuid(&value.requester_id, "requester_id")?,
+        })
+    }
+}
+
+// -----------------------------
+
+pub struct RestorePostRepo {
+    pub id: Uuid,
+    pub requester_id: Uuid,
+}
+
+impl TryFrom<RestorePostRequest> for RestorePostRepo {
+    type Error = RepositoryError;
+
+    fn try_from(value: RestorePostRequest) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: parse_uuid(&value.id, "id")?,
+            requester_id: parse_uuid(&value.requester_id, "requester_id")?,
+        })
+    }
+}
+
+// -----------------------------
+
+pub struct LockPostRepo {
+    pub id: Uuid,
+    pub requester_id: Uuid,
+}
+
+impl TryFrom<LockPostRequest> for LockPostRepo {
+    type Error = RepositoryError;
+
+    fn try_from(value: LockPostRequest) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: parse_uuid(&value.id, "id")?,
+            requester_id: parse_uuid(&value.requester_id, "requester_id")?,
+        })
+    }
+}
+
+impl TryFrom<UnlockPostRequest> for LockPostRepo {
+    type Error = RepositoryError;
+
+    fn try_from(value: UnlockPostRequest) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: parse_uuid(&value.id, "id")?,
+            requester_id: parse_uuid(&value.requester_id, "requester_id")?,
+        })
+    }
+}
+
+// -----------------------------
+
+pub struct ListPostRevisionsRepo {
+    pub post_id: Uuid,
+}
+
+impl TryFrom<ListPostRevisionsRequest> for ListPostRevisionsRepo {
+    type Error = RepositoryError;
+
+    fn try_from(value: ListPostRevisionsRequest) -> Result<Self, Self::Error> {
+        Ok(Self {
+            post_id: parse_uuid(&value.post_id, "post_id")?,
         })
     }
+}
+
+// -----------------------------
+
+pub struct GetPostRevisionRepo {
+    pub post_id: Uuid,
+    pub revision: i32,
+}
+
+impl TryFrom<GetPostRevisionRequest> for GetPostRevisionRepo {
+    type Error = RepositoryError;
+
+    fn try_from(value: GetPostRevisionRequest) -> Result<Self, Self::Error> {
+        Ok(Self {
+            post_id: parse_uuid(&value.post_id, "post_id")?,
+            revision: value.revision,
+        })
+    }
+}
+
+// -----------------------------
+
+pub struct ListDraftsRepo {
+    pub user_id: Uuid,
+}
+
+impl TryFrom<ListDraftsRequest> for ListDraftsRepo {
+    type Error = RepositoryError;
+
+    fn try_from(value: ListDraftsRequest) -> Result<Self, Self::Error> {
+        Ok(Self {
+            user_id: parse_uuid(&value.user_id, "user_id")?,
+        })
+    }
+}
+
+// -----------------------------
+
+pub struct PublishPostRepo {
+    pub id: Uuid,
+    pub requester_id: Uuid,
+    pub publish_at: Option<DateTime<Utc>>,
+}
+
+impl TryFrom<PublishPostRequest> for PublishPostRepo {
+    type Error = RepositoryError;
+
+    fn try_from(value: PublishPostRequest) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: parse_uuid(&value.id, "id")?,
+            requester_id: parse_uuid(&value.requester_id, "requester_id")?,
+            publish_at: optional_timestamp_to_datetime(value.publish_at),
+        })
+    }
+}
+
+// -----------------------------
+
+pub struct ListPostsByTagRepo {
+    pub tag: String,
+    pub limit: i64,
+    pub before: Option<DateTime<Utc>>,
+}
+
+// The requester is resolved into a `Viewer` by the service.
+impl TryFrom<ListPostsByTagRequest> for ListPostsByTagRepo {
+    type Error = RepositoryError;
+
+    fn try_from(value: ListPostsByTagRequest) -> Result<Self, Self::Error> {
+        Ok(Self {
+            tag: parse_tag(&value.tag)?,
+            limit: match value.limit {
+                1..=100 => value.limit as i64,
+                _ => 50,
+            },
+            before: optional_timestamp_to_datetime(value.before),
+        })
+    }
+}
+
+// -----------------------------
+
+pub struct ListPopularTagsRepo {
+    pub since: DateTime<Utc>,
+    pub limit: i64,
+}
+
+impl From<ListPopularTagsRequest> for ListPopularTagsRepo {
+    fn from(value: ListPopularTagsRequest) -> Self {
+        let hours = match value.hours {
+            1..=720 => value.hours as i64,
+            _ => 24,
+        };
+
+        Self {
+            since: Utc::now() - chrono::Duration::hours(hours),
+            limit: match value.limit {
+                1..=100 => value.limit as i64,
+                _ => 10,
+            },
+        }
+    }
+}
+
+// -----------------------------
+
+pub struct SearchPostsRepo {
+    pub query: String,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+// The requester is resolved into a `Viewer` by the service.
+impl From<SearchPostsRequest> for SearchPostsRepo {
+    fn from(value: SearchPostsRequest) -> Self {
+        Self {
+            query: value.query,
+            limit: match value.limit {
+                1..=100 => value.limit as i64,
+                _ => 20,
+            },
+            offset: value.offset.max(0) as i64,
+        }
+    }
 }
\ No newline at end of file
@@ -1,47 +1,85 @@
-use sqlx::{Pool, Postgres};
+use std::time::Duration;
 
-use crate::model::{CreatePostRepo, DeletePostRepo, GetPostRepo, Post, UpdatePostRepo};
-use crate::proto::proto::posts::{CreatePostRequest, DeletePostRequest, GetPostRequest, UpdatePostRequest};
+use chrono::{DateTime, Utc};
+use sqlx::{Pool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::domain::hashtags::extract_hashtags;
+use crate::domain::status::PostStatus;
+use crate::domain::visibility::Viewer;
+use crate::model::{CreatePostRepo, DeletePostRepo, GetPostRepo, GetPostRevisionRepo, ListDraftsRepo, ListPopularTagsRepo, ListPostRevisionsRepo, ListPostsByTagRepo, LockPostRepo, PopularTag, Post, PostRevision, PostSearchHit, PublishPostRepo, RestorePostRepo, SearchPostsRepo, UpdatePostRepo};
+use crate::proto::proto::posts::{CreatePostRequest, DeletePostRequest, GetPostRequest, GetPostRevisionRequest, ListDraftsRequest, ListPopularTagsRequest, ListPostRevisionsRequest, ListPostsByTagRequest, LockPostRequest, PublishPostRequest, RestorePostRequest, SearchPostsRequest, UnlockPostRequest, UpdatePostRequest};
 use crate::{error::RepositoryError};
 
+// Rows removed per statement by the purge job, keeps each delete short.
+const PURGE_BATCH_SIZE: i64 = 1000;
+
 #[derive(Debug, Clone)]
 pub struct PostsRepository {
-    pub db: Pool<Postgres>
+    pub db: Pool<Postgres>,
+    pub retention: Duration,
 }
 
 impl PostsRepository {
-    pub fn new(db: Pool<Postgres>) -> Self {
-        Self { db }
+    pub fn new(db: Pool<Postgres>, retention: Duration) -> Self {
+        Self { db, retention }
+    }
+
+    // Deleted posts older than this are past restoring and up for purging.
+    fn retention_cutoff(&self) -> DateTime<Utc> {
+        Utc::now() - self.retention
     }
 
+    // Authors see all their posts. Everyone else only sees published ones that are public, or
+    // followers-only with the author among the viewer's `following`. Anything else is not found.
     pub async fn get_post(
         &self,
         value: GetPostRequest,
+        viewer: &Viewer,
     ) -> Result<Post, RepositoryError> {
         let GetPostRepo { id } = value.try_into()?;
 
         let result = sqlx::query_as!(
             Post,
             r#"
-            SELECT * FROM posts
-            WHERE id = $1
+            SELECT id, title, description, user_id, created_at, updated_at, locked_at, deleted_at, edited_at, revision, version, status, publish_at, published_at, visibility
+            FROM posts
+            WHERE id = $1 AND deleted_at IS NULL
+            AND (
+                user_id = $2
+                OR (status = 'published' AND (visibility = 'public' OR (visibility = 'followers' AND user_id = ANY($3))))
+            )
             "#,
-            id
+            id,
+            viewer.id,
+            &viewer.following,
         )
-        .fetch_one(&self.db)
-        .await?;
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or(RepositoryError::PostNotFound)?;
 
         Ok(result)
     }
 
+    // Only published posts are listed, the author's own included, filtered like `get_post`.
     pub async fn get_posts(
-        &self
+        &self,
+        viewer: &Viewer,
     ) -> Result<Vec<Post>, RepositoryError> {
         let result = sqlx::query_as!(
             Post,
             r#"
-            SELECT * FROM posts
-            "#
+            SELECT id, title, description, user_id, created_at, updated_at, locked_at, deleted_at, edited_at, revision, version, status, publish_at, published_at, visibility
+            FROM posts
+            WHERE deleted_at IS NULL AND status = 'published'
+            AND (
+                visibility = 'public'
+                OR user_id = $1
+                OR (visibility = 'followers' AND user_id = ANY($2))
+            )
+            "#,
+            viewer.id,
+            &viewer.following,
         )
         .fetch_all(&self.db)
         .await?;
@@ -49,67 +87,1216 @@ impl PostsRepository {
         Ok(result)
     }
 
+    // Inserts the post together with its first revision and its hashtags.
     pub async fn create_post(
         &self,
         value: CreatePostRequest,
     ) -> Result<Post, RepositoryError> {
-        let CreatePostRepo { title, description, user_id } = value.try_into()?;
+        let CreatePostRepo { title, description, user_id, status, publish_at, visibility } = value.try_into()?;
 
-        let post = sqlx::query_as!(
+        let mut tx = self.db.begin().await?;
+
+        let result = sqlx::query_as!(
             Post,
             r#"
-            INSERT INTO posts (title, description, user_id)
-            VALUES ($1, $2, $3)
-            RETURNING id, title, description, user_id, created_at, updated_at
+            INSERT INTO posts (title, description, user_id, status, publish_at, published_at, visibility)
+            VALUES ($1, $2, $3, $4, $5, CASE WHEN $4 = 'published' THEN now() END, $6)
+            RETURNING id, title, description, user_id, created_at, updated_at, locked_at, deleted_at, edited_at, revision, version, status, publish_at, published_at, visibility
             "#,
             title,
             description,
-            user_id
+            user_id,
+            status.as_str(),
+            publish_at,
+            visibility.as_str(),
         )
-        .fetch_one(&self.db)
-        .await?;
+        .fetch_one(&mut *tx)
+        .await;
+
+        let post = match result {
+            Ok(post) => post,
+            Err(err) => {
+                if let Some(db_err) = err.as_database_error()
+                    && db_err.is_foreign_key_violation() {
+                    return Err(RepositoryError::UserNotFound)
+                }
+
+                return Err(RepositoryError::DatabaseError(err))
+            }
+        };
+
+        Self::insert_revision(&mut tx, &post).await?;
+        Self::sync_tags(&mut tx, &post).await?;
+
+        tx.commit().await?;
 
         Ok(post)
     }
 
+    // Only the supplied fields are written, each edit is recorded as a new revision. Saving unchanged
+    // content is not an edit and leaves the post as is, neither is changing just the visibility. With
+    // `expected_versions` set, a post written to since the caller read it is left alone. Someone
+    // else's post is not found, so its existence isn't confirmed either.
     pub async fn update_post(
         &self,
         value: UpdatePostRequest,
     ) -> Result<Post, RepositoryError> {
-        let UpdatePostRepo { id, title, description } = value.try_into()?;
+        let UpdatePostRepo { id, title, description, expected_versions, visibility, requester_id } = value.try_into()?;
 
-        let result = sqlx::query_as!(
+        let mut tx = self.db.begin().await?;
+
+        let current = sqlx::query_as!(
+            Post,
+            r#"
+            SELECT id, title, description, user_id, created_at, updated_at, locked_at, deleted_at, edited_at, revision, version, status, publish_at, published_at, visibility
+            FROM posts
+            WHERE id = $1 AND user_id = $2 AND deleted_at IS NULL
+            FOR UPDATE
+            "#, id, requester_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(RepositoryError::PostNotFound)?;
+
+        if !expected_versions.is_empty() && !expected_versions.contains(&current.version) {
+            return Err(RepositoryError::VersionMismatch);
+        }
+
+        let title_changed = title.as_ref().is_some_and(|title| *title != current.title);
+        let description_changed = description.as_ref().is_some_and(|description| *description != current.description);
+
+        let content_changed = title_changed || description_changed;
+        let visibility_changed = visibility.is_some_and(|visibility| visibility.as_str() != current.visibility);
+
+        if !content_changed && !visibility_changed {
+            return Ok(current);
+        }
+
+        let post = sqlx::query_as!(
             Post,
             r#"
             UPDATE posts
-            SET title = $1, description = $2
-            WHERE id = $3
-            RETURNING id, title, description, user_id, created_at, updated_at
-            "#, title, description, id
+            SET title = COALESCE($1, title),
+                description = COALESCE($2, description),
+                visibility = COALESCE($3, visibility),
+                edited_at = CASE WHEN $4 THEN now() ELSE edited_at END,
+                revision = CASE WHEN $4 THEN revision + 1 ELSE revision END
+            WHERE id = $5
+            RETURNING id, title, description, user_id, created_at, updated_at, locked_at, deleted_at, edited_at, revision, version, status, publish_at, published_at, visibility
+            "#,
+            title,
+            description,
+            visibility.map(|visibility| visibility.as_str()),
+            content_changed,
+            id,
         )
-        .fetch_one(&self.db)
+        .fetch_one(&mut *tx)
         .await?;
 
-        Ok(result)
+        if content_changed {
+            Self::insert_revision(&mut tx, &post).await?;
+            Self::sync_tags(&mut tx, &post).await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(post)
+    }
+
+    async fn insert_revision(
+        tx: &mut Transaction<'_, Postgres>,
+        post: &Post,
+    ) -> Result<(), RepositoryError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO post_revisions (post_id, revision, title, description, created_at)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+            post.id,
+            post.revision,
+            post.title,
+            post.description,
+            post.edited_at.unwrap_or(post.created_at),
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    // Makes the post's tags match the hashtags in its current title and description.
+    async fn sync_tags(
+        tx: &mut Transaction<'_, Postgres>,
+        post: &Post,
+    ) -> Result<(), RepositoryError> {
+        let tags = extract_hashtags(&[&post.title, &post.description]);
+
+        sqlx::query!(
+            r#"
+            INSERT INTO tags (name)
+            SELECT unnest($1::text[])
+            ON CONFLICT (name) DO NOTHING
+            "#,
+            &tags,
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            DELETE FROM post_tags
+            WHERE post_id = $1
+            AND tag_id NOT IN (SELECT id FROM tags WHERE name = ANY($2))
+            "#,
+            post.id,
+            &tags,
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO post_tags (post_id, tag_id)
+            SELECT $1, id FROM tags WHERE name = ANY($2)
+            ON CONFLICT DO NOTHING
+            "#,
+            post.id,
+            &tags,
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    // Revisions are hidden whenever the post itself is, see `get_post`.
+    async fn ensure_post_visible(&self, id: Uuid, viewer: &Viewer) -> Result<(), RepositoryError> {
+        sqlx::query!(
+            r#"
+            SELECT id
+            FROM posts
+            WHERE id = $1 AND deleted_at IS NULL
+            AND (
+                user_id = $2
+                OR (status = 'published' AND (visibility = 'public' OR (visibility = 'followers' AND user_id = ANY($3))))
+            )
+            "#,
+            id,
+            viewer.id,
+            &viewer.following,
+        )
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or(RepositoryError::PostNotFound)?;
+
+        Ok(())
+    }
+
+    // Newest revision first.
+    pub async fn list_post_revisions(
+        &self,
+        value: ListPostRevisionsRequest,
+        viewer: &Viewer,
+    ) -> Result<Vec<PostRevision>, RepositoryError> {
+        let ListPostRevisionsRepo { post_id } = value.try_into()?;
+
+        self.ensure_post_visible(post_id, viewer).await?;
+
+        let revisions = sqlx::query_as!(
+            PostRevision,
+            r#"
+            SELECT post_id, revision, title, description, created_at
+            FROM post_revisions
+            WHERE post_id = $1
+            ORDER BY revision DESC
+            "#, post_id
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(revisions)
+    }
+
+    pub async fn get_post_revision(
+        &self,
+        value: GetPostRevisionRequest,
+        viewer: &Viewer,
+    ) -> Result<PostRevision, RepositoryError> {
+        let GetPostRevisionRepo { post_id, revision } = value.try_into()?;
+
+        self.ensure_post_visible(post_id, viewer).await?;
+
+        let revision = sqlx::query_as!(
+            PostRevision,
+            r#"
+            SELECT post_id, revision, title, description, created_at
+            FROM post_revisions
+            WHERE post_id = $1 AND revision = $2
+            "#, post_id, revision
+        )
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or(RepositoryError::RevisionNotFound)?;
+
+        Ok(revision)
+    }
+
+    pub async fn list_drafts(
+        &self,
+        value: ListDraftsRequest,
+    ) -> Result<Vec<Post>, RepositoryError> {
+        let ListDraftsRepo { user_id } = value.try_into()?;
+
+        let posts = sqlx::query_as!(
+            Post,
+            r#"
+            SELECT id, title, description, user_id, created_at, updated_at, locked_at, deleted_at, edited_at, revision, version, status, publish_at, published_at, visibility
+            FROM posts
+            WHERE user_id = $1 AND deleted_at IS NULL AND status <> 'published'
+            ORDER BY updated_at DESC
+            "#, user_id
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(posts)
+    }
+
+    // Newest first, filtered like `get_posts`.
+    pub async fn list_posts_by_tag(
+        &self,
+        value: ListPostsByTagRequest,
+        viewer: &Viewer,
+    ) -> Result<Vec<Post>, RepositoryError> {
+        let ListPostsByTagRepo { tag, limit, before } = value.try_into()?;
+
+        let posts = sqlx::query_as!(
+            Post,
+            r#"
+            SELECT posts.id, title, description, user_id, posts.created_at, updated_at, locked_at, deleted_at, edited_at, revision, version, status, publish_at, published_at, visibility
+            FROM posts
+            JOIN post_tags ON post_tags.post_id = posts.id
+            JOIN tags ON tags.id = post_tags.tag_id
+            WHERE tags.name = $1 AND deleted_at IS NULL AND status = 'published'
+            AND (
+                visibility = 'public'
+                OR user_id = $2
+                OR (visibility = 'followers' AND user_id = ANY($3))
+            )
+            AND ($4::timestamptz IS NULL OR published_at < $4)
+            ORDER BY published_at DESC
+            LIMIT $5
+            "#,
+            tag,
+            viewer.id,
+            &viewer.following,
+            before,
+            limit,
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(posts)
+    }
+
+    // Counts only public posts, so trending tags don't give away what restricted posts are about.
+    pub async fn list_popular_tags(
+        &self,
+        value: ListPopularTagsRequest,
+    ) -> Result<Vec<PopularTag>, RepositoryError> {
+        let ListPopularTagsRepo { since, limit } = value.into();
+
+        let tags = sqlx::query_as!(
+            PopularTag,
+            r#"
+            SELECT tags.name AS tag, COUNT(*) AS "post_count!"
+            FROM post_tags
+            JOIN tags ON tags.id = post_tags.tag_id
+            JOIN posts ON posts.id = post_tags.post_id
+            WHERE posts.deleted_at IS NULL AND posts.status = 'published' AND posts.visibility = 'public'
+            AND posts.published_at >= $1
+            GROUP BY tags.name
+            ORDER BY "post_count!" DESC, tags.name
+            LIMIT $2
+            "#,
+            since,
+            limit,
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(tags)
+    }
+
+    // Best match first, filtered like `get_posts`. Titles are highlighted whole, descriptions as a
+    // couple of short excerpts around the matches.
+    pub async fn search_posts(
+        &self,
+        value: SearchPostsRequest,
+        viewer: &Viewer,
+    ) -> Result<Vec<PostSearchHit>, RepositoryError> {
+        let SearchPostsRepo { query, limit, offset } = value.into();
+
+        let rows = sqlx::query!(
+            r#"
+            SELECT id, title, description, user_id, created_at, updated_at, locked_at, deleted_at, edited_at, revision, version, status, publish_at, published_at, visibility,
+                ts_rank_cd(search_vector, query) AS "rank!",
+                ts_headline('english', html_escape(title), query, 'StartSel=<mark>, StopSel=</mark>, HighlightAll=true') AS "title_highlight!",
+                ts_headline('english', html_escape(description), query, 'StartSel=<mark>, StopSel=</mark>, MaxFragments=2, MinWords=5, MaxWords=20') AS "description_highlight!"
+            FROM posts, websearch_to_tsquery('english', $1) AS query
+            WHERE search_vector @@ query AND deleted_at IS NULL AND status = 'published'
+            AND (
+                visibility = 'public'
+                OR user_id = $2
+                OR (visibility = 'followers' AND user_id = ANY($3))
+            )
+            ORDER BY "rank!" DESC, published_at DESC
+            LIMIT $4 OFFSET $5
+            "#,
+            query,
+            viewer.id,
+            &viewer.following,
+            limit,
+            offset,
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        let hits = rows.into_iter()
+            .map(|row| PostSearchHit {
+                post: Post {
+                    id: row.id,
+                    title: row.title,
+                    description: row.description,
+                    user_id: row.user_id,
+                    created_at: row.created_at,
+                    updated_at: row.updated_at,
+                    locked_at: row.locked_at,
+                    deleted_at: row.deleted_at,
+                    edited_at: row.edited_at,
+                    revision: row.revision,
+                    version: row.version,
+                    status: row.status,
+                    publish_at: row.publish_at,
+                    published_at: row.published_at,
+                    visibility: row.visibility,
+                },
+                rank: row.rank,
+                title_highlight: row.title_highlight,
+                description_highlight: row.description_highlight,
+            })
+            .collect();
+
+        Ok(hits)
+    }
+
+    // Publishes a draft or scheduled post now, or (re)schedules it for a future `publish_at`.
+    pub async fn publish_post(
+        &self,
+        value: PublishPostRequest,
+    ) -> Result<Post, RepositoryError> {
+        let PublishPostRepo { id, requester_id, publish_at } = value.try_into()?;
+
+        let mut tx = self.db.begin().await?;
+
+        // Someone else's draft is reported as missing, the same as `get_post` does.
+        let current = sqlx::query!(
+            r#"
+            SELECT status
+            FROM posts
+            WHERE id = $1 AND deleted_at IS NULL AND user_id = $2
+            FOR UPDATE
+            "#, id, requester_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(RepositoryError::PostNotFound)?;
+
+        if current.status == PostStatus::Published.as_str() {
+            return Err(RepositoryError::PostAlreadyPublished);
+        }
+
+        let scheduled_at = publish_at.filter(|publish_at| *publish_at > Utc::now());
+
+        let post = match scheduled_at {
+            Some(publish_at) => sqlx::query_as!(
+                Post,
+                r#"
+                UPDATE posts
+                SET status = 'scheduled', publish_at = $2
+                WHERE id = $1
+                RETURNING id, title, description, user_id, created_at, updated_at, locked_at, deleted_at, edited_at, revision, version, status, publish_at, published_at, visibility
+                "#, id, publish_at
+            )
+            .fetch_one(&mut *tx)
+            .await?,
+            None => sqlx::query_as!(
+                Post,
+                r#"
+                UPDATE posts
+                SET status = 'published', publish_at = NULL, published_at = now()
+                WHERE id = $1
+                RETURNING id, title, description, user_id, created_at, updated_at, locked_at, deleted_at, edited_at, revision, version, status, publish_at, published_at, visibility
+                "#, id
+            )
+            .fetch_one(&mut *tx)
+            .await?,
+        };
+
+        tx.commit().await?;
+
+        Ok(post)
     }
 
+    // Publishes every scheduled post that is due. The status check in the `WHERE` makes running it
+    // twice, or from several replicas at once, publish each post only once.
+    pub async fn publish_due_posts(&self) -> Result<u64, RepositoryError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE posts
+            SET status = 'published', published_at = publish_at
+            WHERE status = 'scheduled' AND publish_at <= now() AND deleted_at IS NULL
+            "#
+        )
+        .execute(&self.db)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // Soft deletes the post along with its live comments. They share the post's `deleted_at`
+    // (`now()` is fixed per transaction), which is how restoring tells them apart from comments
+    // deleted on their own before. Only the author's own posts are found.
     pub async fn delete_post(
         &self,
         value: DeletePostRequest,
     ) -> Result<Post, RepositoryError> {
-        let DeletePostRepo { id } = value.try_into()?;
+        let DeletePostRepo { id, requester_id } = value.try_into()?;
 
-        let result = sqlx::query_as!(
+        let mut tx = self.db.begin().await?;
+
+        let post = sqlx::query_as!(
             Post,
             r#"
-            DELETE FROM posts
+            UPDATE posts
+            SET deleted_at = now()
+            WHERE id = $1 AND user_id = $2 AND deleted_at IS NULL
+            RETURNING id, title, description, user_id, created_at, updated_at, locked_at, deleted_at, edited_at, revision, version, status, publish_at, published_at, visibility
+            "#, id, requester_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(RepositoryError::PostNotFound)?;
+
+        sqlx::query!(
+            r#"
+            UPDATE comments
+            SET deleted_at = now()
+            WHERE post_id = $1 AND deleted_at IS NULL
+            "#, id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(post)
+    }
+
+    // Brings back the post and the comments deleted together with it. Only the author's own posts are found.
+    pub async fn restore_post(
+        &self,
+        value: RestorePostRequest,
+    ) -> Result<Post, RepositoryError> {
+        let RestorePostRepo { id, requester_id } = value.try_into()?;
+
+        let mut tx = self.db.begin().await?;
+
+        let deleted = sqlx::query!(
+            r#"
+            SELECT deleted_at
+            FROM posts
+            WHERE id = $1 AND user_id = $2
+            FOR UPDATE
+            "#, id, requester_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(RepositoryError::PostNotFound)?;
+
+        let deleted_at = deleted.deleted_at.ok_or(RepositoryError::PostNotDeleted)?;
+
+        if deleted_at < self.retention_cutoff() {
+            return Err(RepositoryError::RetentionExpired);
+        }
+
+        let post = sqlx::query_as!(
+            Post,
+            r#"
+            UPDATE posts
+            SET deleted_at = NULL
             WHERE id = $1
-            RETURNING id, title, description, user_id, created_at, updated_at
+            RETURNING id, title, description, user_id, created_at, updated_at, locked_at, deleted_at, edited_at, revision, version, status, publish_at, published_at, visibility
             "#, id
         )
-        .fetch_one(&self.db)
+        .fetch_one(&mut *tx)
         .await?;
 
-        Ok(result)
+        sqlx::query!(
+            r#"
+            UPDATE comments
+            SET deleted_at = NULL
+            WHERE post_id = $1 AND deleted_at = $2
+            "#, id, deleted_at
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(post)
+    }
+
+    // Stops new comments on the post. Moderators find any live post, anyone else only their own.
+    pub async fn lock_post(
+        &self,
+        value: LockPostRequest,
+        is_moderator: bool,
+    ) -> Result<Post, RepositoryError> {
+        self.set_locked(value.try_into()?, true, is_moderator).await
+    }
+
+    pub async fn unlock_post(
+        &self,
+        value: UnlockPostRequest,
+        is_moderator: bool,
+    ) -> Result<Post, RepositoryError> {
+        self.set_locked(value.try_into()?, false, is_moderator).await
+    }
+
+    // Locking an already locked post keeps when it was first locked.
+    async fn set_locked(
+        &self,
+        value: LockPostRepo,
+        locked: bool,
+        is_moderator: bool,
+    ) -> Result<Post, RepositoryError> {
+        let LockPostRepo { id, requester_id } = value;
+
+        let post = sqlx::query_as!(
+            Post,
+            r#"
+            UPDATE posts
+            SET locked_at = CASE WHEN $3 THEN COALESCE(locked_at, now()) END
+            WHERE id = $1 AND deleted_at IS NULL AND (user_id = $2 OR $4)
+            RETURNING id, title, description, user_id, created_at, updated_at, locked_at, deleted_at, edited_at, revision, version, status, publish_at, published_at, visibility
+            "#, id, requester_id, locked, is_moderator
+        )
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or(RepositoryError::PostNotFound)?;
+
+        Ok(post)
+    }
+
+    // Permanently removes one batch of posts deleted before the retention window, their comments
+    // go with them through `ON DELETE CASCADE`. Returns how many posts were removed.
+    pub async fn purge_deleted(&self) -> Result<u64, RepositoryError> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM posts
+            WHERE id IN (
+                SELECT id FROM posts
+                WHERE deleted_at < $1
+                LIMIT $2
+            )
+            "#,
+            self.retention_cutoff(),
+            PURGE_BATCH_SIZE,
+        )
+        .execute(&self.db)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Fixture {
+        repository: PostsRepository,
+        author: Uuid,
+        follower: Uuid,
+        stranger: Uuid,
+    }
+
+    async fn insert_user(db: &Pool<Postgres>, name: &str) -> Uuid {
+        sqlx::query_scalar!(
+            "INSERT INTO users (username, email, password) VALUES ($1, $2, 'x') RETURNING id",
+            name,
+            format!("{name}@example.com"),
+        )
+        .fetch_one(db)
+        .await
+        .unwrap()
+    }
+
+    async fn fixture(db: Pool<Postgres>) -> Fixture {
+        let author = insert_user(&db, "author").await;
+        let follower = insert_user(&db, "follower").await;
+        let stranger = insert_user(&db, "stranger").await;
+
+        Fixture {
+            repository: PostsRepository::new(db, Duration::from_secs(3600)),
+            author,
+            follower,
+            stranger,
+        }
+    }
+
+    impl Fixture {
+        async fn create(&self, status: &str, visibility: &str) -> Post {
+            self.repository
+                .create_post(CreatePostRequest {
+                    user_id: self.author.to_string(),
+                    title: "Title".to_string(),
+                    description: "Description".to_string(),
+                    status: status.to_string(),
+                    publish_at: None,
+                    visibility: visibility.to_string(),
+                })
+                .await
+                .unwrap()
+        }
+
+        // Followers follow the author, strangers follow no one.
+        fn viewer(&self, id: Uuid) -> Viewer {
+            let following = if id == self.follower { vec![self.author] } else { vec![] };
+
+            Viewer { id: Some(id), following }
+        }
+
+        async fn can_see(&self, post: &Post, viewer: &Viewer) -> bool {
+            let request = GetPostRequest { id: post.id.to_string(), requester_id: String::new() };
+
+            match self.repository.get_post(request, viewer).await {
+                Ok(_) => true,
+                Err(RepositoryError::PostNotFound) => false,
+                Err(err) => panic!("unexpected error: {err}"),
+            }
+        }
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn visibility_decides_who_finds_a_post(db: Pool<Postgres>) {
+        let f = fixture(db).await;
+
+        let anonymous = Viewer::default();
+        let author = f.viewer(f.author);
+        let follower = f.viewer(f.follower);
+        let stranger = f.viewer(f.stranger);
+
+        let public = f.create("", "public").await;
+        assert!(f.can_see(&public, &anonymous).await);
+        assert!(f.can_see(&public, &stranger).await);
+
+        let followers = f.create("", "followers").await;
+        assert!(!f.can_see(&followers, &anonymous).await);
+        assert!(!f.can_see(&followers, &stranger).await);
+        assert!(f.can_see(&followers, &follower).await);
+        assert!(f.can_see(&followers, &author).await);
+
+        let private = f.create("", "private").await;
+        assert!(!f.can_see(&private, &follower).await);
+        assert!(f.can_see(&private, &author).await);
+
+        let listed = f.repository.get_posts(&follower).await.unwrap();
+        let ids: Vec<Uuid> = listed.iter().map(|post| post.id).collect();
+        assert!(ids.contains(&public.id) && ids.contains(&followers.id));
+        assert!(!ids.contains(&private.id));
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn only_the_author_writes_a_post(db: Pool<Postgres>) {
+        let f = fixture(db).await;
+        let post = f.create("", "public").await;
+
+        let update = |requester_id: Uuid| UpdatePostRequest {
+            id: post.id.to_string(),
+            title: None,
+            description: None,
+            expected_versions: vec![],
+            visibility: Some("private".to_string()),
+            requester_id: requester_id.to_string(),
+        };
+        let delete = |requester_id: Uuid| DeletePostRequest {
+            id: post.id.to_string(),
+            requester_id: requester_id.to_string(),
+        };
+        let restore = |requester_id: Uuid| RestorePostRequest {
+            id: post.id.to_string(),
+            requester_id: requester_id.to_string(),
+        };
+
+        let err = f.repository.update_post(update(f.stranger)).await.unwrap_err();
+        assert!(matches!(err, RepositoryError::PostNotFound));
+
+        let err = f.repository.delete_post(delete(f.stranger)).await.unwrap_err();
+        assert!(matches!(err, RepositoryError::PostNotFound));
+
+        f.repository.delete_post(delete(f.author)).await.unwrap();
+
+        let err = f.repository.restore_post(restore(f.stranger)).await.unwrap_err();
+        assert!(matches!(err, RepositoryError::PostNotFound));
+
+        f.repository.restore_post(restore(f.author)).await.unwrap();
+
+        let updated = f.repository.update_post(update(f.author)).await.unwrap();
+        assert_eq!(updated.visibility, "private");
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn only_the_author_sees_and_changes_a_draft(db: Pool<Postgres>) {
+        let f = fixture(db).await;
+        let draft = f.create("draft", "public").await;
+
+        assert!(!f.can_see(&draft, &Viewer::default()).await);
+        assert!(!f.can_see(&draft, &f.viewer(f.follower)).await);
+        assert!(f.can_see(&draft, &f.viewer(f.author)).await);
+
+        let listed = f.repository.get_posts(&f.viewer(f.follower)).await.unwrap();
+        assert!(listed.iter().all(|post| post.id != draft.id));
+
+        let update = UpdatePostRequest {
+            id: draft.id.to_string(),
+            title: Some("Taken over".to_string()),
+            description: None,
+            expected_versions: vec![],
+            visibility: None,
+            requester_id: f.stranger.to_string(),
+        };
+        let err = f.repository.update_post(update).await.unwrap_err();
+        assert!(matches!(err, RepositoryError::PostNotFound));
+
+        let publish = PublishPostRequest {
+            id: draft.id.to_string(),
+            requester_id: f.stranger.to_string(),
+            publish_at: None,
+        };
+        let err = f.repository.publish_post(publish).await.unwrap_err();
+        assert!(matches!(err, RepositoryError::PostNotFound));
+
+        let delete = DeletePostRequest {
+            id: draft.id.to_string(),
+            requester_id: f.stranger.to_string(),
+        };
+        let err = f.repository.delete_post(delete).await.unwrap_err();
+        assert!(matches!(err, RepositoryError::PostNotFound));
+
+        let drafts = f.repository.list_drafts(ListDraftsRequest { user_id: f.stranger.to_string() }).await.unwrap();
+        assert!(drafts.is_empty());
+
+        let unchanged = f.repository.get_post(
+            GetPostRequest { id: draft.id.to_string(), requester_id: String::new() },
+            &f.viewer(f.author),
+        ).await.unwrap();
+        assert_eq!(unchanged.title, "Title");
+        assert_eq!(unchanged.status, "draft");
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn the_author_or_a_moderator_locks_a_post(db: Pool<Postgres>) {
+        let f = fixture(db).await;
+        let post = f.create("", "public").await;
+
+        let lock = |requester_id: Uuid| LockPostRequest {
+            id: post.id.to_string(),
+            requester_id: requester_id.to_string(),
+        };
+        let unlock = |requester_id: Uuid| UnlockPostRequest {
+            id: post.id.to_string(),
+            requester_id: requester_id.to_string(),
+        };
+
+        let err = f.repository.lock_post(lock(f.stranger), false).await.unwrap_err();
+        assert!(matches!(err, RepositoryError::PostNotFound));
+
+        let locked = f.repository.lock_post(lock(f.author), false).await.unwrap();
+        let locked_at = locked.locked_at.expect("locked");
+
+        let relocked = f.repository.lock_post(lock(f.author), false).await.unwrap();
+        assert_eq!(relocked.locked_at, Some(locked_at));
+
+        let err = f.repository.unlock_post(unlock(f.stranger), false).await.unwrap_err();
+        assert!(matches!(err, RepositoryError::PostNotFound));
+
+        // A moderator is anyone the service says is one.
+        let unlocked = f.repository.unlock_post(unlock(f.stranger), true).await.unwrap();
+        assert_eq!(unlocked.locked_at, None);
+
+        f.repository.delete_post(DeletePostRequest {
+            id: post.id.to_string(),
+            requester_id: f.author.to_string(),
+        }).await.unwrap();
+
+        let err = f.repository.lock_post(lock(f.author), false).await.unwrap_err();
+        assert!(matches!(err, RepositoryError::PostNotFound));
+    }
+
+    async fn insert_comment(db: &Pool<Postgres>, post_id: Uuid, user_id: Uuid) -> Uuid {
+        sqlx::query_scalar!(
+            "INSERT INTO comments (content, post_id, user_id) VALUES ('Comment', $1, $2) RETURNING id",
+            post_id,
+            user_id,
+        )
+        .fetch_one(db)
+        .await
+        .unwrap()
+    }
+
+    // `None` once the comment is gone for good.
+    async fn comment_deleted_at(db: &Pool<Postgres>, id: Uuid) -> Option<Option<DateTime<Utc>>> {
+        sqlx::query_scalar!("SELECT deleted_at FROM comments WHERE id = $1", id)
+            .fetch_optional(db)
+            .await
+            .unwrap()
+    }
+
+    // Moves a post's deletion, and that of the comments deleted with it, `ago` into the past.
+    async fn backdate_deletion(db: &Pool<Postgres>, post_id: Uuid, ago: Duration) {
+        let deleted_at = Utc::now() - ago;
+
+        sqlx::query!("UPDATE posts SET deleted_at = $2 WHERE id = $1", post_id, deleted_at)
+            .execute(db)
+            .await
+            .unwrap();
+        sqlx::query!("UPDATE comments SET deleted_at = $2 WHERE post_id = $1 AND deleted_at IS NOT NULL", post_id, deleted_at)
+            .execute(db)
+            .await
+            .unwrap();
+    }
+
+    impl Fixture {
+        async fn delete(&self, post: &Post) -> Post {
+            self.repository
+                .delete_post(DeletePostRequest { id: post.id.to_string(), requester_id: self.author.to_string() })
+                .await
+                .unwrap()
+        }
+
+        async fn restore(&self, post: &Post) -> Result<Post, RepositoryError> {
+            self.repository
+                .restore_post(RestorePostRequest { id: post.id.to_string(), requester_id: self.author.to_string() })
+                .await
+        }
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn comments_go_and_come_back_with_their_post(db: Pool<Postgres>) {
+        let f = fixture(db.clone()).await;
+        let post = f.create("", "public").await;
+
+        let kept = insert_comment(&db, post.id, f.stranger).await;
+        let removed_before = insert_comment(&db, post.id, f.stranger).await;
+        sqlx::query!("UPDATE comments SET deleted_at = now() - interval '1 minute' WHERE id = $1", removed_before)
+            .execute(&db)
+            .await
+            .unwrap();
+
+        let deleted = f.delete(&post).await;
+        assert_eq!(comment_deleted_at(&db, kept).await, Some(deleted.deleted_at));
+
+        f.restore(&post).await.unwrap();
+
+        assert_eq!(comment_deleted_at(&db, kept).await, Some(None));
+        // Deleted on its own before the post was, so it stays deleted.
+        assert!(comment_deleted_at(&db, removed_before).await.unwrap().is_some());
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn a_post_past_the_retention_window_stays_deleted(db: Pool<Postgres>) {
+        let f = fixture(db.clone()).await;
+        let post = f.create("", "public").await;
+        let comment = insert_comment(&db, post.id, f.stranger).await;
+
+        assert!(matches!(f.restore(&post).await.unwrap_err(), RepositoryError::PostNotDeleted));
+
+        f.delete(&post).await;
+        backdate_deletion(&db, post.id, Duration::from_secs(2 * 3600)).await;
+
+        assert!(matches!(f.restore(&post).await.unwrap_err(), RepositoryError::RetentionExpired));
+        assert!(comment_deleted_at(&db, comment).await.unwrap().is_some());
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn purging_removes_expired_posts_with_their_comments(db: Pool<Postgres>) {
+        let f = fixture(db.clone()).await;
+
+        let live = f.create("", "public").await;
+        let recent = f.create("", "public").await;
+        let expired = f.create("", "public").await;
+
+        let live_comment = insert_comment(&db, live.id, f.stranger).await;
+        let recent_comment = insert_comment(&db, recent.id, f.stranger).await;
+        let expired_comment = insert_comment(&db, expired.id, f.stranger).await;
+
+        f.delete(&recent).await;
+        f.delete(&expired).await;
+        backdate_deletion(&db, expired.id, Duration::from_secs(2 * 3600)).await;
+
+        assert_eq!(f.repository.purge_deleted().await.unwrap(), 1);
+        assert_eq!(f.repository.purge_deleted().await.unwrap(), 0);
+
+        let mut remaining: Vec<Uuid> = sqlx::query_scalar!("SELECT id FROM posts")
+            .fetch_all(&db)
+            .await
+            .unwrap();
+        let mut expected = vec![live.id, recent.id];
+        remaining.sort();
+        expected.sort();
+        assert_eq!(remaining, expected);
+
+        assert!(comment_deleted_at(&db, live_comment).await.is_some());
+        assert!(comment_deleted_at(&db, recent_comment).await.is_some());
+        assert_eq!(comment_deleted_at(&db, expired_comment).await, None);
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn an_edit_is_recorded_as_a_revision(db: Pool<Postgres>) {
+        let f = fixture(db).await;
+        let post = f.create("", "public").await;
+        assert_eq!(post.revision, 1);
+        assert!(post.edited_at.is_none());
+
+        let update = |title: &str, visibility: Option<&str>| UpdatePostRequest {
+            id: post.id.to_string(),
+            title: Some(title.to_string()),
+            description: None,
+            expected_versions: vec![],
+            visibility: visibility.map(str::to_string),
+            requester_id: f.author.to_string(),
+        };
+        let author = f.viewer(f.author);
+        let revisions = || f.repository.list_post_revisions(
+            ListPostRevisionsRequest { post_id: post.id.to_string(), requester_id: String::new() },
+            &author,
+        );
+
+        let edited = f.repository.update_post(update("Edited", None)).await.unwrap();
+        assert_eq!(edited.revision, 2);
+        assert!(edited.edited_at.is_some());
+
+        let history = revisions().await.unwrap();
+        let titles: Vec<(i32, &str)> = history.iter().map(|r| (r.revision, r.title.as_str())).collect();
+        assert_eq!(titles, vec![(2, "Edited"), (1, "Title")]);
+        assert_eq!(history[0].description, "Description");
+
+        // Saving the same content, or only changing who can see the post, is not an edit.
+        let unchanged = f.repository.update_post(update("Edited", None)).await.unwrap();
+        assert_eq!(unchanged.revision, 2);
+        let hidden = f.repository.update_post(update("Edited", Some("private"))).await.unwrap();
+        assert_eq!(hidden.revision, 2);
+        assert_eq!(revisions().await.unwrap().len(), 2);
+
+        let first = f.repository.get_post_revision(
+            GetPostRevisionRequest { post_id: post.id.to_string(), revision: 1, requester_id: String::new() },
+            &author,
+        ).await.unwrap();
+        assert_eq!(first.title, "Title");
+
+        // The history is hidden along with the now private post.
+        let err = f.repository.list_post_revisions(
+            ListPostRevisionsRequest { post_id: post.id.to_string(), requester_id: String::new() },
+            &f.viewer(f.follower),
+        ).await.unwrap_err();
+        assert!(matches!(err, RepositoryError::PostNotFound));
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn fields_left_out_of_an_update_keep_their_value(db: Pool<Postgres>) {
+        let f = fixture(db).await;
+        let post = f.create("", "followers").await;
+
+        let update = |title: Option<&str>, description: Option<&str>| UpdatePostRequest {
+            id: post.id.to_string(),
+            title: title.map(str::to_string),
+            description: description.map(str::to_string),
+            expected_versions: vec![],
+            visibility: None,
+            requester_id: f.author.to_string(),
+        };
+
+        let retitled = f.repository.update_post(update(Some("New title"), None)).await.unwrap();
+        assert_eq!(retitled.title, "New title");
+        assert_eq!(retitled.description, "Description");
+        assert_eq!(retitled.visibility, "followers");
+
+        let described = f.repository.update_post(update(None, Some("New description"))).await.unwrap();
+        assert_eq!(described.title, "New title");
+        assert_eq!(described.description, "New description");
+        assert_eq!(described.visibility, "followers");
+
+        let untouched = f.repository.update_post(update(None, None)).await.unwrap();
+        assert_eq!(untouched.version, described.version);
+        assert_eq!(untouched.revision, described.revision);
+    }
+
+    impl Fixture {
+        // A draft scheduled for an hour from now.
+        async fn schedule(&self) -> Post {
+            let draft = self.create("draft", "public").await;
+            let publish_at = Utc::now() + Duration::from_secs(3600);
+
+            self.repository
+                .publish_post(PublishPostRequest {
+                    id: draft.id.to_string(),
+                    requester_id: self.author.to_string(),
+                    publish_at: Some(prost_types::Timestamp { seconds: publish_at.timestamp(), nanos: 0 }),
+                })
+                .await
+                .unwrap()
+        }
     }
-}
\ No newline at end of file
+
+    // Moves a scheduled post's publish time into the past, as if the hour went by.
+    async fn make_due(db: &Pool<Postgres>, post_id: Uuid) {
+        sqlx::query!("UPDATE posts SET publish_at = now() - interval '1 second' WHERE id = $1", post_id)
+            .execute(db)
+            .await
+            .unwrap();
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn a_scheduled_post_is_published_once_due(db: Pool<Postgres>) {
+        let f = fixture(db.clone()).await;
+        let post = f.schedule().await;
+        assert_eq!(post.status, "scheduled");
+
+        let follower = f.viewer(f.follower);
+
+        assert_eq!(f.repository.publish_due_posts().await.unwrap(), 0);
+        assert!(!f.can_see(&post, &follower).await);
+        assert!(!f.can_see(&post, &Viewer::default()).await);
+        assert!(f.can_see(&post, &f.viewer(f.author)).await);
+        assert!(f.repository.get_posts(&follower).await.unwrap().iter().all(|listed| listed.id != post.id));
+
+        make_due(&db, post.id).await;
+
+        assert_eq!(f.repository.publish_due_posts().await.unwrap(), 1);
+        assert!(f.can_see(&post, &follower).await);
+        assert!(f.can_see(&post, &Viewer::default()).await);
+
+        let published = f.repository.get_post(
+            GetPostRequest { id: post.id.to_string(), requester_id: String::new() },
+            &follower,
+        ).await.unwrap();
+        assert_eq!(published.status, "published");
+        assert_eq!(published.published_at, published.publish_at);
+
+        // Already published, so the next run has nothing to do.
+        assert_eq!(f.repository.publish_due_posts().await.unwrap(), 0);
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn a_deleted_scheduled_post_is_not_published(db: Pool<Postgres>) {
+        let f = fixture(db.clone()).await;
+        let post = f.schedule().await;
+
+        f.delete(&post).await;
+        make_due(&db, post.id).await;
+
+        assert_eq!(f.repository.publish_due_posts().await.unwrap(), 0);
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn every_read_applies_the_same_visibility(db: Pool<Postgres>) {
+        let f = fixture(db).await;
+
+        let mut posts = vec![];
+        for visibility in ["public", "followers", "private"] {
+            let post = f.repository
+                .create_post(CreatePostRequest {
+                    user_id: f.author.to_string(),
+                    title: format!("Zebra {visibility}"),
+                    description: "Spotted a zebra #safari".to_string(),
+                    status: String::new(),
+                    publish_at: None,
+                    visibility: visibility.to_string(),
+                })
+                .await
+                .unwrap();
+            posts.push(post);
+        }
+        let [public, followers, private] = [&posts[0], &posts[1], &posts[2]].map(|post| post.id);
+
+        let matrix = [
+            ("anonymous", Viewer::default(), vec![public]),
+            ("stranger", f.viewer(f.stranger), vec![public]),
+            ("follower", f.viewer(f.follower), vec![public, followers]),
+            ("author", f.viewer(f.author), vec![public, followers, private]),
+        ];
+
+        for (name, viewer, expected) in matrix {
+            let sorted = |mut ids: Vec<Uuid>| { ids.sort(); ids };
+            let expected = sorted(expected);
+
+            let listed = f.repository.get_posts(&viewer).await.unwrap();
+            assert_eq!(sorted(listed.iter().map(|post| post.id).collect()), expected, "{name} listing posts");
+
+            let tagged = f.repository.list_posts_by_tag(
+                ListPostsByTagRequest { tag: "safari".to_string(), requester_id: String::new(), limit: 0, before: None },
+                &viewer,
+            ).await.unwrap();
+            assert_eq!(sorted(tagged.iter().map(|post| post.id).collect()), expected, "{name} listing by tag");
+
+            let found = f.repository.search_posts(
+                SearchPostsRequest { query: "zebra".to_string(), requester_id: String::new(), limit: 0, offset: 0 },
+                &viewer,
+            ).await.unwrap();
+            assert_eq!(sorted(found.iter().map(|hit| hit.post.id).collect()), expected, "{name} searching");
+
+            for post in &posts {
+                let visible = expected.contains(&post.id);
+
+                assert_eq!(f.can_see(post, &viewer).await, visible, "{name} reading {}", post.visibility);
+
+                let revisions = f.repository.list_post_revisions(
+                    ListPostRevisionsRequest { post_id: post.id.to_string(), requester_id: String::new() },
+                    &viewer,
+                ).await;
+                assert_eq!(revisions.is_ok(), visible, "{name} reading revisions of {}", post.visibility);
+            }
+        }
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn highlights_escape_the_text_around_the_matches(db: Pool<Postgres>) {
+        let f = fixture(db).await;
+
+        f.repository
+            .create_post(CreatePostRequest {
+                user_id: f.author.to_string(),
+                title: "<script>alert(1)</script> zebra".to_string(),
+                description: r#"A "zebra" & <img src=x onerror=alert(1)>"#.to_string(),
+                status: String::new(),
+                publish_at: None,
+                visibility: String::new(),
+            })
+            .await
+            .unwrap();
+
+        let hits = f.repository.search_posts(
+            SearchPostsRequest { query: "zebra".to_string(), requester_id: String::new(), limit: 0, offset: 0 },
+            &Viewer::default(),
+        ).await.unwrap();
+
+        assert_eq!(hits[0].title_highlight, "&lt;script&gt;alert(1)&lt;/script&gt; <mark>zebra</mark>");
+
+        // Excerpts may start and end anywhere, but apart from the marks nothing in them is markup.
+        let excerpt = &hits[0].description_highlight;
+        assert!(excerpt.contains("<mark>zebra</mark>&quot;"), "{excerpt}");
+        assert!(!excerpt.replace("<mark>", "").replace("</mark>", "").contains(['<', '>', '"']), "{excerpt}");
+    }
+}
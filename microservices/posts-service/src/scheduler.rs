@@ -0,0 +1,71 @@
+use std::time::Duration;
+
+use metrics::counter;
+
+use crate::repository::PostsRepository;
+
+// Publishes scheduled posts once their `publish_at` has passed. A post is at most one interval late.
+pub async fn run(repository: PostsRepository, every: Duration) {
+    let mut interval = tokio::time::interval(every);
+
+    loop {
+        interval.tick().await;
+
+        match repository.publish_due_posts().await {
+            Ok(0) => {},
+            Ok(published) => {
+                counter!("scheduled_posts_published_total").increment(published);
+                tracing::info!("Published {published} scheduled posts");
+            },
+            Err(err) => tracing::warn!("Publishing scheduled posts failed: {err}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Utc};
+    use sqlx::{Pool, Postgres};
+    use uuid::Uuid;
+
+    use super::*;
+
+    async fn status(db: &Pool<Postgres>, id: Uuid) -> String {
+        sqlx::query_scalar!("SELECT status FROM posts WHERE id = $1", id)
+            .fetch_one(db)
+            .await
+            .unwrap()
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn due_posts_are_published_on_the_next_tick(db: Pool<Postgres>) {
+        let author: Uuid = sqlx::query_scalar!(
+            "INSERT INTO users (username, email, password) VALUES ('author', 'author@example.com', 'x') RETURNING id"
+        )
+        .fetch_one(&db)
+        .await
+        .unwrap();
+
+        let insert = |publish_at: DateTime<Utc>| {
+            sqlx::query_scalar!(
+                "INSERT INTO posts (title, description, user_id, status, publish_at) VALUES ('Title', 'Description', $1, 'scheduled', $2) RETURNING id",
+                author,
+                publish_at,
+            )
+            .fetch_one(&db)
+        };
+        let due = insert(Utc::now() - Duration::from_secs(60)).await.unwrap();
+        let later = insert(Utc::now() + Duration::from_secs(3600)).await.unwrap();
+
+        let scheduler = tokio::spawn(run(PostsRepository::new(db.clone(), Duration::from_secs(3600)), Duration::from_millis(10)));
+
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+        while status(&db, due).await != "published" {
+            assert!(tokio::time::Instant::now() < deadline, "due post was never published");
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        scheduler.abort();
+
+        assert_eq!(status(&db, later).await, "scheduled");
+    }
+}
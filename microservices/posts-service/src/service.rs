@@ -1,14 +1,41 @@
-use tonic::{Request, Response, Status};
-use crate::{proto::proto::posts::{CreatePostRequest, CreatePostResponse, DeletePostRequest, DeletePostResponse, GetPostRequest, GetPostResponse, GetPostsRequest, GetPostsResponse, UpdatePostRequest, UpdatePostResponse, posts_server::Posts}, repository::PostsRepository, validation::validate_post};
+use tonic::{Request, Response, Status, service::interceptor::InterceptedService, transport::Channel};
+use uuid::Uuid;
+use crate::{proto::proto::posts::{CreatePostRequest, CreatePostResponse, DeletePostRequest, DeletePostResponse, GetPostRequest, GetPostResponse, GetPostsRequest, GetPostsResponse, GetPostRevisionRequest, GetPostRevisionResponse, ListDraftsRequest, ListDraftsResponse, ListPopularTagsRequest, ListPopularTagsResponse, ListPostRevisionsRequest, ListPostRevisionsResponse, ListPostsByTagRequest, ListPostsByTagResponse, LockPostRequest, LockPostResponse, PublishPostRequest, PublishPostResponse, RestorePostRequest, RestorePostResponse, SearchPostsRequest, SearchPostsResponse, UnlockPostRequest, UnlockPostResponse, UpdatePostRequest, UpdatePostResponse, posts_server::Posts}, error::{map_repo_err, map_users_err, map_validation_err}, repository::PostsRepository, validation::{check_search_query, validate_post}};
+use crate::domain::visibility::Viewer;
+use crate::model::parse_optional_uuid;
+use crate::proto::proto::users::{ListFollowingRequest, users_client::UsersClient};
+use common::telemetry::TracePropagation;
 
 #[derive(Debug)]
 pub struct PostsService {
-    repository: PostsRepository
+    repository: PostsRepository,
+    users_client: UsersClient<InterceptedService<Channel, TracePropagation>>,
+    moderator_user_ids: Vec<String>,
 }
 
 impl PostsService {
-    pub fn new(repository: PostsRepository) -> Self {
-        Self { repository }
+    pub fn new(repository: PostsRepository, users_channel: Channel, moderator_user_ids: Vec<String>) -> Self {
+        let users_client = UsersClient::with_interceptor(users_channel, TracePropagation);
+
+        Self { repository, users_client, moderator_user_ids }
+    }
+
+    // Looks up who the requester follows, anonymous callers follow no one.
+    async fn viewer(&self, requester_id: &str) -> Result<Viewer, Status> {
+        let Some(id) = parse_optional_uuid(requester_id, "requester_id").map_err(map_repo_err)? else {
+            return Ok(Viewer::default());
+        };
+
+        let following = self.users_client.clone()
+            .list_following(ListFollowingRequest { user_id: id.to_string() })
+            .await.map_err(map_users_err)?
+            .into_inner()
+            .user_ids
+            .iter()
+            .filter_map(|user_id| Uuid::parse_str(user_id).ok())
+            .collect();
+
+        Ok(Viewer { id: Some(id), following })
     }
 }
 
@@ -19,9 +46,10 @@ impl Posts for PostsService {
         request: Request<GetPostRequest>,
     ) -> Result<Response<GetPostResponse>, Status> {
         let request = request.into_inner();
+        let viewer = self.viewer(&request.requester_id).await?;
 
-        let post = self.repository.get_post(request)
-            .await.map_err(|_| Status::not_found("post with this id not found"))?;
+        let post = self.repository.get_post(request, &viewer)
+            .await.map_err(map_repo_err)?;
 
         let response = GetPostResponse {
             post: Some(post.into())
@@ -32,10 +60,13 @@ impl Posts for PostsService {
 
     async fn get_posts(
         &self,
-        _request: Request<GetPostsRequest>,
+        request: Request<GetPostsRequest>,
     ) -> Result<Response<GetPostsResponse>, Status> {
-        let posts = self.repository.get_posts()
-            .await.map_err(|_| Status::internal("Error on getting posts"))?;
+        let request = request.into_inner();
+        let viewer = self.viewer(&request.requester_id).await?;
+
+        let posts = self.repository.get_posts(&viewer)
+            .await.map_err(map_repo_err)?;
 
         let response = GetPostsResponse {
             posts: posts.into_iter().map(Into::into).collect()
@@ -50,11 +81,11 @@ impl Posts for PostsService {
     ) -> Result<Response<CreatePostResponse>, Status> {
         let request = request.into_inner();
 
-        validate_post(&request.title, &request.description)
-        .map_err(|e| Status::invalid_argument(e))?;
+        validate_post(Some(&request.title), Some(&request.description))
+        .map_err(map_validation_err)?;
 
         let created_post = self.repository.create_post(request)
-            .await.map_err(|_| Status::internal("Error on creating post"))?;
+            .await.map_err(map_repo_err)?;
 
         let response = CreatePostResponse {
             post: Some(created_post.into())
@@ -69,11 +100,11 @@ impl Posts for PostsService {
     ) -> Result<Response<UpdatePostResponse>, Status> {
         let request = request.into_inner();
 
-        validate_post(&request.title, &request.description)
-        .map_err(|e| Status::invalid_argument(e))?;
+        validate_post(request.title.as_deref(), request.description.as_deref())
+        .map_err(map_validation_err)?;
 
         let updated_post = self.repository.update_post(request)
-            .await.map_err(|_| Status::internal("Error on updating post"))?;
+            .await.map_err(map_repo_err)?;
 
         let response = UpdatePostResponse {
             post: Some(updated_post.into())
@@ -89,7 +120,7 @@ impl Posts for PostsService {
         let request = request.into_inner();
 
         let deleted_post = self.repository.delete_post(request)
-            .await.map_err(|_| Status::internal("Error on deleting post"))?;
+            .await.map_err(map_repo_err)?;
 
         let response = DeletePostResponse {
             post: Some(deleted_post.into())
@@ -97,4 +128,174 @@ impl Posts for PostsService {
 
         Ok(Response::new(response))
     }
+
+    async fn restore_post(
+        &self,
+        request: Request<RestorePostRequest>,
+    ) -> Result<Response<RestorePostResponse>, Status> {
+        let request = request.into_inner();
+
+        let restored_post = self.repository.restore_post(request)
+            .await.map_err(map_repo_err)?;
+
+        let response = RestorePostResponse {
+            post: Some(restored_post.into())
+        };
+
+        Ok(Response::new(response))
+    }
+
+    async fn lock_post(
+        &self,
+        request: Request<LockPostRequest>,
+    ) -> Result<Response<LockPostResponse>, Status> {
+        let request = request.into_inner();
+        let is_moderator = self.moderator_user_ids.contains(&request.requester_id);
+
+        let locked_post = self.repository.lock_post(request, is_moderator)
+            .await.map_err(map_repo_err)?;
+
+        let response = LockPostResponse {
+            post: Some(locked_post.into())
+        };
+
+        Ok(Response::new(response))
+    }
+
+    async fn unlock_post(
+        &self,
+        request: Request<UnlockPostRequest>,
+    ) -> Result<Response<UnlockPostResponse>, Status> {
+        let request = request.into_inner();
+        let is_moderator = self.moderator_user_ids.contains(&request.requester_id);
+
+        let unlocked_post = self.repository.unlock_post(request, is_moderator)
+            .await.map_err(map_repo_err)?;
+
+        let response = UnlockPostResponse {
+            post: Some(unlocked_post.into())
+        };
+
+        Ok(Response::new(response))
+    }
+
+    async fn list_post_revisions(
+        &self,
+        request: Request<ListPostRevisionsRequest>,
+    ) -> Result<Response<ListPostRevisionsResponse>, Status> {
+        let request = request.into_inner();
+        let viewer = self.viewer(&request.requester_id).await?;
+
+        let revisions = self.repository.list_post_revisions(request, &viewer)
+            .await.map_err(map_repo_err)?;
+
+        let response = ListPostRevisionsResponse {
+            revisions: revisions.into_iter().map(Into::into).collect()
+        };
+
+        Ok(Response::new(response))
+    }
+
+    async fn get_post_revision(
+        &self,
+        request: Request<GetPostRevisionRequest>,
+    ) -> Result<Response<GetPostRevisionResponse>, Status> {
+        let request = request.into_inner();
+        let viewer = self.viewer(&request.requester_id).await?;
+
+        let revision = self.repository.get_post_revision(request, &viewer)
+            .await.map_err(map_repo_err)?;
+
+        let response = GetPostRevisionResponse {
+            revision: Some(revision.into())
+        };
+
+        Ok(Response::new(response))
+    }
+
+    async fn list_drafts(
+        &self,
+        request: Request<ListDraftsRequest>,
+    ) -> Result<Response<ListDraftsResponse>, Status> {
+        let request = request.into_inner();
+
+        let posts = self.repository.list_drafts(request)
+            .await.map_err(map_repo_err)?;
+
+        let response = ListDraftsResponse {
+            posts: posts.into_iter().map(Into::into).collect()
+        };
+
+        Ok(Response::new(response))
+    }
+
+    async fn publish_post(
+        &self,
+        request: Request<PublishPostRequest>,
+    ) -> Result<Response<PublishPostResponse>, Status> {
+        let request = request.into_inner();
+
+        let published_post = self.repository.publish_post(request)
+            .await.map_err(map_repo_err)?;
+
+        let response = PublishPostResponse {
+            post: Some(published_post.into())
+        };
+
+        Ok(Response::new(response))
+    }
+
+    async fn list_posts_by_tag(
+        &self,
+        request: Request<ListPostsByTagRequest>,
+    ) -> Result<Response<ListPostsByTagResponse>, Status> {
+        let request = request.into_inner();
+        let viewer = self.viewer(&request.requester_id).await?;
+
+        let posts = self.repository.list_posts_by_tag(request, &viewer)
+            .await.map_err(map_repo_err)?;
+
+        let response = ListPostsByTagResponse {
+            posts: posts.into_iter().map(Into::into).collect()
+        };
+
+        Ok(Response::new(response))
+    }
+
+    async fn list_popular_tags(
+        &self,
+        request: Request<ListPopularTagsRequest>,
+    ) -> Result<Response<ListPopularTagsResponse>, Status> {
+        let request = request.into_inner();
+
+        let tags = self.repository.list_popular_tags(request)
+            .await.map_err(map_repo_err)?;
+
+        let response = ListPopularTagsResponse {
+            tags: tags.into_iter().map(Into::into).collect()
+        };
+
+        Ok(Response::new(response))
+    }
+
+    async fn search_posts(
+        &self,
+        request: Request<SearchPostsRequest>,
+    ) -> Result<Response<SearchPostsResponse>, Status> {
+        let request = request.into_inner();
+
+        check_search_query(&request.query)
+        .map_err(map_validation_err)?;
+
+        let viewer = self.viewer(&request.requester_id).await?;
+
+        let hits = self.repository.search_posts(request, &viewer)
+            .await.map_err(map_repo_err)?;
+
+        let response = SearchPostsResponse {
+            hits: hits.into_iter().map(Into::into).collect()
+        };
+
+        Ok(Response::new(response))
+    }
 }
\ No newline at end of file
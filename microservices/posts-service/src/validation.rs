@@ -1,24 +1,113 @@
-pub fn check_title(title: &str) -> Result<(), &'static str> {
+use chrono::{DateTime, Utc};
+
+use crate::domain::hashtags::normalize_tag;
+use crate::domain::status::PostStatus;
+use crate::domain::visibility::PostVisibility;
+
+// A request field that failed validation.
+#[derive(Debug)]
+pub struct ValidationError {
+    pub field: &'static str,
+    pub message: &'static str,
+}
+
+pub fn check_title(title: &str) -> Result<(), ValidationError> {
     if !(1..=50).contains(&title.len()) {
-        return Err("Title must be 1-50 characters long");
+        return Err(ValidationError { field: "title", message: "Title must be 1-50 characters long" });
     }
 
     Ok(())
 }
 
-pub fn check_description(description: &str) -> Result<(), &'static str> {
+pub fn check_description(description: &str) -> Result<(), ValidationError> {
     if description.len() > 500 {
-        return Err("Description is too long");
+        return Err(ValidationError { field: "description", message: "Description is too long" });
     }
 
     Ok(())
 }
 
+// Fields left out of a partial update are already stored and aren't checked again.
 pub fn validate_post(
-    title: &str, description: &str
-) -> Result<(), &'static str> {
-    check_title(title)?;
-    check_description(description)?;
+    title: Option<&str>, description: Option<&str>
+) -> Result<(), ValidationError> {
+    if let Some(title) = title {
+        check_title(title)?;
+    }
+
+    if let Some(description) = description {
+        check_description(description)?;
+    }
 
     Ok(())
+}
+
+// An empty status publishes right away, like posts did before drafts existed.
+pub fn parse_status(status: &str) -> Result<PostStatus, ValidationError> {
+    if status.is_empty() {
+        return Ok(PostStatus::Published);
+    }
+
+    PostStatus::parse(status)
+        .ok_or(ValidationError { field: "status", message: "Status must be draft, scheduled or published" })
+}
+
+// An empty visibility makes the post public, like posts were before visibility existed.
+pub fn parse_visibility(visibility: &str) -> Result<PostVisibility, ValidationError> {
+    if visibility.is_empty() {
+        return Ok(PostVisibility::Public);
+    }
+
+    PostVisibility::parse(visibility)
+        .ok_or(ValidationError { field: "visibility", message: "Visibility must be public, followers or private" })
+}
+
+pub fn check_publish_at(status: PostStatus, publish_at: Option<DateTime<Utc>>) -> Result<(), ValidationError> {
+    match (status, publish_at) {
+        (PostStatus::Scheduled, None) => {
+            Err(ValidationError { field: "publish_at", message: "Scheduled posts need a publish time" })
+        },
+        (PostStatus::Scheduled, Some(publish_at)) if publish_at <= Utc::now() => {
+            Err(ValidationError { field: "publish_at", message: "Publish time must be in the future" })
+        },
+        (PostStatus::Draft | PostStatus::Published, Some(_)) => {
+            Err(ValidationError { field: "publish_at", message: "Publish time is only allowed for scheduled posts" })
+        },
+        _ => Ok(()),
+    }
+}
+
+// A tag asked for by name, the leading '#' is optional.
+pub fn parse_tag(tag: &str) -> Result<String, ValidationError> {
+    let body = tag.strip_prefix('#').unwrap_or(tag);
+
+    if body.chars().all(|c| c.is_alphanumeric() || c == '_') && let Some(tag) = normalize_tag(body) {
+        return Ok(tag);
+    }
+
+    Err(ValidationError { field: "tag", message: "Tag must be up to 50 letters, digits or underscores with at least one letter" })
+}
+
+// Search terms, checked the same way by every service that searches.
+pub fn check_search_query(query: &str) -> Result<(), ValidationError> {
+    common::search::check_query(query).map_err(|message| ValidationError { field: "query", message })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_fields_of_a_partial_update_are_checked() {
+        assert!(validate_post(None, None).is_ok());
+        assert!(validate_post(None, Some("New description")).is_ok());
+        assert_eq!(validate_post(Some(""), None).unwrap_err().field, "title");
+        assert_eq!(validate_post(None, Some(&"a".repeat(501))).unwrap_err().field, "description");
+    }
+
+    #[test]
+    fn a_bad_search_query_is_reported_on_the_query_field() {
+        assert!(check_search_query("rust").is_ok());
+        assert_eq!(check_search_query(" ").unwrap_err().field, "query");
+    }
 }
\ No newline at end of file
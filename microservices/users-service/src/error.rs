@@ -9,20 +9,54 @@ pub enum RepositoryError {
     #[error("user with this email not found")]
     UserNotFound,
 
+    #[error("invalid uuid: {0}")]
+    InvalidUUID(#[from] uuid::Error),
+
     #[error("user already exists")]
-    UserAlreadyExists
+    UserAlreadyExists,
+
+    #[error("users cannot follow themselves")]
+    CannotFollowSelf
 }
 
 pub fn map_repo_err(err: RepositoryError) -> Status {
     match err {
         RepositoryError::UserNotFound => {
-            Status::not_found("user with email not found")
+            Status::not_found("user not found")
+        },
+        RepositoryError::InvalidUUID(_) => {
+            Status::invalid_argument("invalid user id")
         },
         RepositoryError::UserAlreadyExists => {
             Status::already_exists("user with this email already exists")
         },
+        RepositoryError::CannotFollowSelf => {
+            Status::invalid_argument("users cannot follow themselves")
+        },
         RepositoryError::DatabaseError(_) => {
             Status::internal("internal server error")
         }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use tonic::Code;
+
+    use super::*;
+
+    #[test]
+    fn repository_errors_map_to_codes() {
+        let cases = [
+            (RepositoryError::UserNotFound, Code::NotFound),
+            (RepositoryError::InvalidUUID("x".parse::<uuid::Uuid>().unwrap_err()), Code::InvalidArgument),
+            (RepositoryError::UserAlreadyExists, Code::AlreadyExists),
+            (RepositoryError::CannotFollowSelf, Code::InvalidArgument),
+            (RepositoryError::DatabaseError(sqlx::Error::RowNotFound), Code::Internal),
+        ];
+
+        for (err, code) in cases {
+            assert_eq!(map_repo_err(err).code(), code);
+        }
+    }
 }
\ No newline at end of file
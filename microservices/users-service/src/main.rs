@@ -18,21 +18,33 @@ pub mod config;
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     dotenvy::dotenv().ok();
 
+    let _tracer_provider = common::telemetry::init("users-service")?;
+
     let config = Config::from_env();
 
     let address: SocketAddr = config.microservice_url.parse()?;
 
+    common::metrics::install(config.metrics_url.parse()?, "users-service")?;
+
     let db = PgPoolOptions::new()
         .max_connections(5)
         .connect(&config.database_url)
         .await?;
 
+    let (health_reporter, health_service) = tonic_health::server::health_reporter();
+    tokio::spawn(common::metrics::watch_pool(db.clone()));
+    tokio::spawn(common::health::watch_database::<UsersServer<UsersService>>(health_reporter, db.clone()));
+
     let repository = UsersRepository::new(db);
     let service = UsersService::new(repository);
 
-    println!("Users service listening on {}", address);
+    tracing::info!("Users service listening on {}", address);
 
     Server::builder()
+        .trace_fn(common::telemetry::request_span)
+        .layer(common::metrics::GrpcMetricsLayer)
+        .layer(common::telemetry::RequestIdLayer)
+        .add_service(health_service)
         .add_service(UsersServer::new(service))
         .serve(address)
         .await?;
@@ -1,7 +1,10 @@
 use tonic::Status;
 use uuid::Uuid;
 
-use crate::proto::users::{self, CreateUserRequest, GetUserRequest};
+use crate::proto::users::{
+    self, CreateUserRequest, FollowUserRequest, GetUserByIdRequest, GetUserRequest,
+    IsFollowingRequest, ListFollowingRequest, SearchUsersRequest, UnfollowUserRequest,
+};
 
 #[derive(Debug)]
 pub struct User {
@@ -24,6 +27,24 @@ impl TryFrom<User> for users::User {
     }
 }
 
+pub struct UserSearchHit {
+    pub id: Uuid,
+    pub username: String,
+    pub rank: f32,
+    pub username_highlight: String,
+}
+
+impl From<UserSearchHit> for users::UserSearchHit {
+    fn from(value: UserSearchHit) -> Self {
+        Self {
+            id: value.id.to_string(),
+            username: value.username,
+            rank: value.rank,
+            username_highlight: value.username_highlight,
+        }
+    }
+}
+
 // --------------------
 
 pub struct GetUserRepo {
@@ -40,6 +61,22 @@ impl From<GetUserRequest> for GetUserRepo {
 
 // --------------------
 
+pub struct GetUserByIdRepo {
+    pub id: Uuid
+}
+
+impl TryFrom<GetUserByIdRequest> for GetUserByIdRepo {
+    type Error = uuid::Error;
+
+    fn try_from(value: GetUserByIdRequest) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: Uuid::parse_str(&value.id)?
+        })
+    }
+}
+
+// --------------------
+
 pub struct CreateUserRepo {
     pub username: String,
     pub email: String,
@@ -54,4 +91,81 @@ impl From<CreateUserRequest> for CreateUserRepo {
             username: value.username,
         }
     }
-}
\ No newline at end of file
+}
+
+// --------------------
+
+pub struct FollowRepo {
+    pub follower_id: Uuid,
+    pub followee_id: Uuid,
+}
+
+impl FollowRepo {
+    fn parse(follower_id: &str, followee_id: &str) -> Result<Self, uuid::Error> {
+        Ok(Self {
+            follower_id: Uuid::parse_str(follower_id)?,
+            followee_id: Uuid::parse_str(followee_id)?,
+        })
+    }
+}
+
+impl TryFrom<FollowUserRequest> for FollowRepo {
+    type Error = uuid::Error;
+
+    fn try_from(value: FollowUserRequest) -> Result<Self, Self::Error> {
+        Self::parse(&value.follower_id, &value.followee_id)
+    }
+}
+
+impl TryFrom<UnfollowUserRequest> for FollowRepo {
+    type Error = uuid::Error;
+
+    fn try_from(value: UnfollowUserRequest) -> Result<Self, Self::Error> {
+        Self::parse(&value.follower_id, &value.followee_id)
+    }
+}
+
+impl TryFrom<IsFollowingRequest> for FollowRepo {
+    type Error = uuid::Error;
+
+    fn try_from(value: IsFollowingRequest) -> Result<Self, Self::Error> {
+        Self::parse(&value.follower_id, &value.followee_id)
+    }
+}
+
+// --------------------
+
+pub struct ListFollowingRepo {
+    pub user_id: Uuid
+}
+
+impl TryFrom<ListFollowingRequest> for ListFollowingRepo {
+    type Error = uuid::Error;
+
+    fn try_from(value: ListFollowingRequest) -> Result<Self, Self::Error> {
+        Ok(Self {
+            user_id: Uuid::parse_str(&value.user_id)?
+        })
+    }
+}
+
+// --------------------
+
+pub struct SearchUsersRepo {
+    pub query: String,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+impl From<SearchUsersRequest> for SearchUsersRepo {
+    fn from(value: SearchUsersRequest) -> Self {
+        Self {
+            query: value.query,
+            limit: match value.limit {
+                1..=100 => value.limit as i64,
+                _ => 20,
+            },
+            offset: value.offset.max(0) as i64,
+        }
+    }
+}
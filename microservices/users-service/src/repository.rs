@@ -1,7 +1,10 @@
 use sqlx::{ Pool, Postgres };
-use crate::model::{CreateUserRepo, GetUserRepo, User};
+use crate::model::{CreateUserRepo, FollowRepo, GetUserByIdRepo, GetUserRepo, ListFollowingRepo, SearchUsersRepo, User, UserSearchHit};
 use crate::error::RepositoryError;
-use crate::proto::users::{CreateUserRequest, GetUserRequest};
+use crate::proto::users::{
+    CreateUserRequest, FollowUserRequest, GetUserByIdRequest, GetUserRequest,
+    IsFollowingRequest, ListFollowingRequest, SearchUsersRequest, UnfollowUserRequest,
+};
 
 #[derive(Clone, Debug)]
 pub struct UsersRepository {
@@ -28,8 +31,31 @@ impl UsersRepository {
             "#,
             email
         )
-        .fetch_one(&self.db)
-        .await?;
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or(RepositoryError::UserNotFound)?;
+
+        Ok(result)
+    }
+
+    pub async fn get_user_by_id(
+        &self,
+        value: GetUserByIdRequest,
+    ) -> Result<User, RepositoryError> {
+        let GetUserByIdRepo { id } = value.try_into()?;
+
+        let result = sqlx::query_as!(
+            User,
+            r#"
+            SELECT id, username, email, password
+            FROM users
+            WHERE id = $1
+            "#,
+            id
+        )
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or(RepositoryError::UserNotFound)?;
 
         Ok(result)
     }
@@ -57,14 +83,187 @@ impl UsersRepository {
         match result {
             Ok(user) => Ok(user),
             Err(err) => {
-                if let Some(db_err) = err.as_database_error() {
-                    if db_err.is_unique_violation() {
-                            return Err(RepositoryError::UserAlreadyExists)
-                        }
+                if let Some(db_err) = err.as_database_error()
+                    && db_err.is_unique_violation() {
+                    return Err(RepositoryError::UserAlreadyExists)
                 }
 
                 Err(RepositoryError::DatabaseError(err))
             }
         }
     }
-}
\ No newline at end of file
+
+    pub async fn follow_user(
+        &self,
+        value: FollowUserRequest,
+    ) -> Result<(), RepositoryError> {
+        let FollowRepo { follower_id, followee_id } = value.try_into()?;
+
+        if follower_id == followee_id {
+            return Err(RepositoryError::CannotFollowSelf);
+        }
+
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO follows (follower_id, followee_id)
+            VALUES ($1, $2)
+            ON CONFLICT DO NOTHING
+            "#,
+            follower_id,
+            followee_id,
+        )
+        .execute(&self.db)
+        .await;
+
+        match result {
+            Ok(_) => Ok(()),
+            Err(err) => {
+                if let Some(db_err) = err.as_database_error()
+                    && db_err.is_foreign_key_violation() {
+                    return Err(RepositoryError::UserNotFound)
+                }
+
+                Err(RepositoryError::DatabaseError(err))
+            }
+        }
+    }
+
+    pub async fn unfollow_user(
+        &self,
+        value: UnfollowUserRequest,
+    ) -> Result<(), RepositoryError> {
+        let FollowRepo { follower_id, followee_id } = value.try_into()?;
+
+        sqlx::query!(
+            r#"
+            DELETE FROM follows
+            WHERE follower_id = $1 AND followee_id = $2
+            "#,
+            follower_id,
+            followee_id,
+        )
+        .execute(&self.db)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn is_following(
+        &self,
+        value: IsFollowingRequest,
+    ) -> Result<bool, RepositoryError> {
+        let FollowRepo { follower_id, followee_id } = value.try_into()?;
+
+        let following = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM follows
+                WHERE follower_id = $1 AND followee_id = $2
+            ) AS "following!"
+            "#,
+            follower_id,
+            followee_id,
+        )
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(following)
+    }
+
+    pub async fn list_following(
+        &self,
+        value: ListFollowingRequest,
+    ) -> Result<Vec<uuid::Uuid>, RepositoryError> {
+        let ListFollowingRepo { user_id } = value.try_into()?;
+
+        let result = sqlx::query_scalar!(
+            r#"
+            SELECT followee_id
+            FROM follows
+            WHERE follower_id = $1
+            "#,
+            user_id,
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(result)
+    }
+
+    // Best match first, usernames are matched word by word without stemming.
+    pub async fn search_users(
+        &self,
+        value: SearchUsersRequest,
+    ) -> Result<Vec<UserSearchHit>, RepositoryError> {
+        let SearchUsersRepo { query, limit, offset } = value.into();
+
+        let result = sqlx::query_as!(
+            UserSearchHit,
+            r#"
+            SELECT id, username,
+                ts_rank_cd(search_vector, query) AS "rank!",
+                ts_headline('simple', html_escape(username), query, 'StartSel=<mark>, StopSel=</mark>, HighlightAll=true') AS "username_highlight!"
+            FROM users, websearch_to_tsquery('simple', $1) AS query
+            WHERE search_vector @@ query
+            ORDER BY "rank!" DESC, username
+            LIMIT $2 OFFSET $3
+            "#,
+            query,
+            limit,
+            offset,
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn search(query: &str) -> SearchUsersRequest {
+        SearchUsersRequest { query: query.to_string(), limit: 0, offset: 0 }
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn users_are_found_by_username(db: Pool<Postgres>) {
+        let repository = UsersRepository::new(db);
+
+        for username in ["alice", "bob"] {
+            repository
+                .create_user(CreateUserRequest {
+                    username: username.to_string(),
+                    email: format!("{username}@example.com"),
+                    password: "x".to_string(),
+                })
+                .await
+                .unwrap();
+        }
+
+        let hits = repository.search_users(search("alice")).await.unwrap();
+
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].username, "alice");
+        assert_eq!(hits[0].username_highlight, "<mark>alice</mark>");
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn highlights_escape_the_rest_of_the_username(db: Pool<Postgres>) {
+        let repository = UsersRepository::new(db);
+
+        repository
+            .create_user(CreateUserRequest {
+                username: "zebra<script>alert(1)</script>".to_string(),
+                email: "zebra@example.com".to_string(),
+                password: "x".to_string(),
+            })
+            .await
+            .unwrap();
+
+        let hits = repository.search_users(search("zebra")).await.unwrap();
+
+        assert_eq!(hits[0].username_highlight, "<mark>zebra</mark>&lt;script&gt;alert(1)&lt;/script&gt;");
+    }
+}
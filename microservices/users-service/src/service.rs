@@ -2,7 +2,11 @@ use tonic::{ Request, Response, Status };
 use crate::error::map_repo_err;
 use crate::repository::{UsersRepository};
 use crate::proto::users::users_server::Users;
-use crate::proto::users::{CreateUserRequest, GetUserRequest, User};
+use crate::proto::users::{
+    CreateUserRequest, FollowUserRequest, FollowUserResponse, GetUserByIdRequest, GetUserRequest,
+    IsFollowingRequest, IsFollowingResponse, ListFollowingRequest, ListFollowingResponse,
+    SearchUsersRequest, SearchUsersResponse, UnfollowUserRequest, UnfollowUserResponse, User,
+};
 
 #[derive(Debug, Clone)]
 pub struct UsersService {
@@ -35,6 +39,24 @@ impl Users for UsersService {
         Ok(Response::new(response))
     }
 
+    async fn get_user_by_id(
+        &self,
+        request: Request<GetUserByIdRequest>,
+    ) -> Result<Response<User>, Status> {
+        let request = request.into_inner();
+
+        let user = self
+            .repository
+            .get_user_by_id(request)
+            .await
+            .map_err(map_repo_err)?;
+
+        let response: User = user.try_into()
+            .map_err(|_| Status::internal("Internal server error"))?;
+
+        Ok(Response::new(response))
+    }
+
     async fn create_user(
         &self,
         request: Request<CreateUserRequest>,
@@ -52,4 +74,113 @@ impl Users for UsersService {
 
         Ok(Response::new(response))
     }
-}
\ No newline at end of file
+
+    async fn follow_user(
+        &self,
+        request: Request<FollowUserRequest>,
+    ) -> Result<Response<FollowUserResponse>, Status> {
+        let request = request.into_inner();
+
+        self
+            .repository
+            .follow_user(request)
+            .await
+            .map_err(map_repo_err)?;
+
+        Ok(Response::new(FollowUserResponse {}))
+    }
+
+    async fn unfollow_user(
+        &self,
+        request: Request<UnfollowUserRequest>,
+    ) -> Result<Response<UnfollowUserResponse>, Status> {
+        let request = request.into_inner();
+
+        self
+            .repository
+            .unfollow_user(request)
+            .await
+            .map_err(map_repo_err)?;
+
+        Ok(Response::new(UnfollowUserResponse {}))
+    }
+
+    async fn is_following(
+        &self,
+        request: Request<IsFollowingRequest>,
+    ) -> Result<Response<IsFollowingResponse>, Status> {
+        let request = request.into_inner();
+
+        let following = self
+            .repository
+            .is_following(request)
+            .await
+            .map_err(map_repo_err)?;
+
+        Ok(Response::new(IsFollowingResponse { following }))
+    }
+
+    async fn list_following(
+        &self,
+        request: Request<ListFollowingRequest>,
+    ) -> Result<Response<ListFollowingResponse>, Status> {
+        let request = request.into_inner();
+
+        let user_ids = self
+            .repository
+            .list_following(request)
+            .await
+            .map_err(map_repo_err)?
+            .into_iter()
+            .map(|id| id.to_string())
+            .collect();
+
+        Ok(Response::new(ListFollowingResponse { user_ids }))
+    }
+
+    async fn search_users(
+        &self,
+        request: Request<SearchUsersRequest>,
+    ) -> Result<Response<SearchUsersResponse>, Status> {
+        let request = request.into_inner();
+
+        common::search::check_query(&request.query)
+            .map_err(Status::invalid_argument)?;
+
+        let hits = self
+            .repository
+            .search_users(request)
+            .await
+            .map_err(map_repo_err)?
+            .into_iter()
+            .map(Into::into)
+            .collect();
+
+        Ok(Response::new(SearchUsersResponse { hits }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::{ Pool, Postgres };
+    use tonic::Code;
+
+    use super::*;
+
+    fn search(query: &str) -> Request<SearchUsersRequest> {
+        Request::new(SearchUsersRequest { query: query.to_string(), limit: 0, offset: 0 })
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn a_search_query_is_1_to_200_characters(db: Pool<Postgres>) {
+        let service = UsersService::new(UsersRepository::new(db));
+
+        for query in ["", "   ", &"a".repeat(201)] {
+            let status = service.search_users(search(query)).await.unwrap_err();
+
+            assert_eq!(status.code(), Code::InvalidArgument, "{query:?}");
+        }
+
+        assert!(service.search_users(search(&"a".repeat(200))).await.is_ok());
+    }
+}
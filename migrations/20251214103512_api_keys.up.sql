@@ -0,0 +1,17 @@
+CREATE TABLE api_keys (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name TEXT NOT NULL,
+    prefix TEXT NOT NULL,
+    key_hash TEXT NOT NULL UNIQUE,
+    scopes TEXT[] NOT NULL DEFAULT '{}',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    last_used_at TIMESTAMPTZ,
+    revoked_at TIMESTAMPTZ,
+
+    user_id UUID NOT NULL,
+
+    CONSTRAINT api_keys_users_fkey
+    FOREIGN KEY (user_id) REFERENCES users(id)
+);
+
+CREATE INDEX api_keys_user_id_idx ON api_keys (user_id);
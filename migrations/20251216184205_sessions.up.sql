@@ -0,0 +1,17 @@
+CREATE TABLE sessions (
+    id UUID PRIMARY KEY,
+    refresh_token_hash TEXT NOT NULL UNIQUE,
+    user_agent TEXT NOT NULL DEFAULT '',
+    ip_address TEXT NOT NULL DEFAULT '',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    last_used_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    expires_at TIMESTAMPTZ NOT NULL,
+    revoked_at TIMESTAMPTZ,
+
+    user_id UUID NOT NULL,
+
+    CONSTRAINT sessions_users_fkey
+    FOREIGN KEY (user_id) REFERENCES users(id)
+);
+
+CREATE INDEX sessions_user_id_idx ON sessions (user_id);
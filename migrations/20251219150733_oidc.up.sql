@@ -0,0 +1,24 @@
+CREATE TABLE external_identities (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    provider TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    email TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+
+    user_id UUID NOT NULL,
+
+    CONSTRAINT external_identities_users_fkey
+    FOREIGN KEY (user_id) REFERENCES users(id),
+    CONSTRAINT external_identities_provider_subject_key
+    UNIQUE (provider, subject)
+);
+
+-- Pending authorization requests, consumed by the callback.
+CREATE TABLE oidc_states (
+    state TEXT PRIMARY KEY,
+    provider TEXT NOT NULL,
+    code_verifier TEXT NOT NULL,
+    nonce TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    expires_at TIMESTAMPTZ NOT NULL
+);
@@ -0,0 +1,15 @@
+CREATE TABLE magic_links (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    email TEXT NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    expires_at TIMESTAMPTZ NOT NULL,
+    consumed_at TIMESTAMPTZ,
+
+    user_id UUID NOT NULL,
+
+    CONSTRAINT magic_links_users_fkey
+    FOREIGN KEY (user_id) REFERENCES users(id)
+);
+
+CREATE INDEX magic_links_email_created_at_idx ON magic_links (email, created_at);
@@ -0,0 +1,30 @@
+CREATE TABLE security_events (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    event_type TEXT NOT NULL,
+    ip_address TEXT NOT NULL DEFAULT '',
+    user_agent TEXT NOT NULL DEFAULT '',
+    country TEXT NOT NULL DEFAULT '',
+    details JSONB NOT NULL DEFAULT '{}',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+
+    -- Empty for failed sign ins with an unknown email.
+    user_id UUID,
+
+    CONSTRAINT security_events_users_fkey
+    FOREIGN KEY (user_id) REFERENCES users(id)
+);
+
+CREATE INDEX security_events_user_id_created_at_idx ON security_events (user_id, created_at DESC);
+
+-- The log is append-only.
+CREATE OR REPLACE FUNCTION reject_security_events_change()
+RETURNS TRIGGER AS $$
+BEGIN
+    RAISE EXCEPTION 'security_events is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER security_events_append_only
+BEFORE UPDATE OR DELETE ON security_events
+FOR EACH ROW
+EXECUTE FUNCTION reject_security_events_change();
@@ -0,0 +1,3 @@
+ALTER TABLE comments DROP CONSTRAINT IF EXISTS comments_content_check;
+
+ALTER TABLE posts DROP COLUMN IF EXISTS locked_at;
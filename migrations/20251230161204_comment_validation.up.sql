@@ -0,0 +1,8 @@
+-- Set by moderators to stop new comments on a post.
+ALTER TABLE posts ADD COLUMN locked_at TIMESTAMPTZ;
+
+-- Mirrors the rules in comments-service validation.rs. NOT VALID keeps existing rows, new and updated ones are checked.
+ALTER TABLE comments
+ADD CONSTRAINT comments_content_check
+CHECK (char_length(content) BETWEEN 1 AND 2000 AND btrim(content) <> '')
+NOT VALID;
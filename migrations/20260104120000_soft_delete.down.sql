@@ -0,0 +1,14 @@
+DELETE FROM comments WHERE deleted_at IS NOT NULL;
+DELETE FROM comments WHERE post_id IN (SELECT id FROM posts WHERE deleted_at IS NOT NULL);
+DELETE FROM posts WHERE deleted_at IS NOT NULL;
+
+ALTER TABLE comments DROP CONSTRAINT comments_posts_fkey;
+ALTER TABLE comments
+ADD CONSTRAINT comments_posts_fkey
+FOREIGN KEY (post_id) REFERENCES posts(id);
+
+DROP INDEX IF EXISTS comments_deleted_at_idx;
+DROP INDEX IF EXISTS posts_deleted_at_idx;
+
+ALTER TABLE comments DROP COLUMN IF EXISTS deleted_at;
+ALTER TABLE posts DROP COLUMN IF EXISTS deleted_at;
@@ -0,0 +1,13 @@
+-- Deleting posts and comments only sets deleted_at, rows are restorable until the purge job removes them.
+ALTER TABLE posts ADD COLUMN deleted_at TIMESTAMPTZ;
+ALTER TABLE comments ADD COLUMN deleted_at TIMESTAMPTZ;
+
+-- The purge job scans for expired rows, live rows never need to be indexed.
+CREATE INDEX posts_deleted_at_idx ON posts (deleted_at) WHERE deleted_at IS NOT NULL;
+CREATE INDEX comments_deleted_at_idx ON comments (deleted_at) WHERE deleted_at IS NOT NULL;
+
+-- A deleted post takes its comments with it, purging the post removes them as well.
+ALTER TABLE comments DROP CONSTRAINT comments_posts_fkey;
+ALTER TABLE comments
+ADD CONSTRAINT comments_posts_fkey
+FOREIGN KEY (post_id) REFERENCES posts(id) ON DELETE CASCADE;
@@ -0,0 +1,21 @@
+-- Every version of a post, revision 1 is the post as created. Edits append a row instead of losing the old text.
+CREATE TABLE post_revisions (
+    post_id UUID NOT NULL,
+    revision INT NOT NULL,
+    title TEXT NOT NULL,
+    description TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+
+    PRIMARY KEY (post_id, revision),
+
+    CONSTRAINT post_revisions_posts_fkey
+    FOREIGN KEY (post_id) REFERENCES posts(id) ON DELETE CASCADE
+);
+
+-- Only set by edits, unlike updated_at which also moves on locking or deleting.
+ALTER TABLE posts ADD COLUMN edited_at TIMESTAMPTZ;
+-- Number of the latest row in post_revisions.
+ALTER TABLE posts ADD COLUMN revision INT NOT NULL DEFAULT 1;
+
+INSERT INTO post_revisions (post_id, revision, title, description, created_at)
+SELECT id, 1, title, description, created_at FROM posts;
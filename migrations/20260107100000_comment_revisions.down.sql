@@ -0,0 +1,4 @@
+ALTER TABLE comments DROP COLUMN IF EXISTS edit_count;
+ALTER TABLE comments DROP COLUMN IF EXISTS edited_at;
+
+DROP TABLE IF EXISTS comment_revisions;
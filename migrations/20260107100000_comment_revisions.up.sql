@@ -0,0 +1,19 @@
+-- Every version of a comment, revision 1 is the comment as posted.
+CREATE TABLE comment_revisions (
+    comment_id UUID NOT NULL,
+    revision INT NOT NULL,
+    content TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+
+    PRIMARY KEY (comment_id, revision),
+
+    CONSTRAINT comment_revisions_comments_fkey
+    FOREIGN KEY (comment_id) REFERENCES comments(id) ON DELETE CASCADE
+);
+
+-- Only set by edits, unlike updated_at which also moves on deleting and restoring.
+ALTER TABLE comments ADD COLUMN edited_at TIMESTAMPTZ;
+ALTER TABLE comments ADD COLUMN edit_count INT NOT NULL DEFAULT 0;
+
+INSERT INTO comment_revisions (comment_id, revision, content, created_at)
+SELECT id, 1, content, created_at FROM comments;
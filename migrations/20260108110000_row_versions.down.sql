@@ -0,0 +1,6 @@
+DROP TRIGGER IF EXISTS bump_comments_version ON comments;
+DROP TRIGGER IF EXISTS bump_posts_version ON posts;
+DROP FUNCTION IF EXISTS bump_version();
+
+ALTER TABLE comments DROP COLUMN IF EXISTS version;
+ALTER TABLE posts DROP COLUMN IF EXISTS version;
@@ -0,0 +1,21 @@
+-- Bumped on every write, the gateway hands it out as the ETag and updates can require it through If-Match.
+ALTER TABLE posts ADD COLUMN version BIGINT NOT NULL DEFAULT 1;
+ALTER TABLE comments ADD COLUMN version BIGINT NOT NULL DEFAULT 1;
+
+CREATE OR REPLACE FUNCTION bump_version()
+RETURNS TRIGGER AS $$
+BEGIN
+    NEW.version = OLD.version + 1;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER bump_posts_version
+BEFORE UPDATE ON posts
+FOR EACH ROW
+EXECUTE FUNCTION bump_version();
+
+CREATE TRIGGER bump_comments_version
+BEFORE UPDATE ON comments
+FOR EACH ROW
+EXECUTE FUNCTION bump_version();
@@ -0,0 +1,7 @@
+DROP INDEX IF EXISTS posts_user_id_unpublished_idx;
+DROP INDEX IF EXISTS posts_publish_at_idx;
+
+ALTER TABLE posts DROP CONSTRAINT IF EXISTS posts_publish_at_check;
+ALTER TABLE posts DROP COLUMN IF EXISTS published_at;
+ALTER TABLE posts DROP COLUMN IF EXISTS publish_at;
+ALTER TABLE posts DROP COLUMN IF EXISTS status;
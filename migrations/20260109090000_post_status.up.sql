@@ -0,0 +1,21 @@
+-- Drafts and scheduled posts are only visible to their author until they are published.
+ALTER TABLE posts
+ADD COLUMN status TEXT NOT NULL DEFAULT 'published'
+CONSTRAINT posts_status_check CHECK (status IN ('draft', 'scheduled', 'published'));
+
+-- When a scheduled post goes out, and when a post actually went public.
+ALTER TABLE posts ADD COLUMN publish_at TIMESTAMPTZ;
+ALTER TABLE posts ADD COLUMN published_at TIMESTAMPTZ;
+
+ALTER TABLE posts
+ADD CONSTRAINT posts_publish_at_check
+CHECK (status <> 'scheduled' OR publish_at IS NOT NULL);
+
+-- Backfilling isn't an edit, updated_at and version stay as they are.
+ALTER TABLE posts DISABLE TRIGGER USER;
+UPDATE posts SET published_at = created_at;
+ALTER TABLE posts ENABLE TRIGGER USER;
+
+-- The scheduler only looks at posts waiting to go out.
+CREATE INDEX posts_publish_at_idx ON posts (publish_at) WHERE status = 'scheduled';
+CREATE INDEX posts_user_id_unpublished_idx ON posts (user_id) WHERE status <> 'published';
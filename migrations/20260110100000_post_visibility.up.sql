@@ -0,0 +1,22 @@
+-- Who follows whom, owned by users-service.
+CREATE TABLE follows (
+    follower_id UUID NOT NULL,
+    followee_id UUID NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+
+    PRIMARY KEY (follower_id, followee_id),
+
+    CONSTRAINT follows_follower_fkey
+    FOREIGN KEY (follower_id) REFERENCES users(id) ON DELETE CASCADE,
+    CONSTRAINT follows_followee_fkey
+    FOREIGN KEY (followee_id) REFERENCES users(id) ON DELETE CASCADE,
+    CONSTRAINT follows_not_self_check
+    CHECK (follower_id <> followee_id)
+);
+
+CREATE INDEX follows_followee_id_idx ON follows (followee_id);
+
+-- Public posts are visible to everyone, followers posts to the author's followers, private ones to the author only.
+ALTER TABLE posts
+ADD COLUMN visibility TEXT NOT NULL DEFAULT 'public'
+CONSTRAINT posts_visibility_check CHECK (visibility IN ('public', 'followers', 'private'));
@@ -0,0 +1,39 @@
+-- Hashtags used in posts, stored lowercase without the '#'.
+CREATE TABLE tags (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name TEXT NOT NULL UNIQUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE TABLE post_tags (
+    post_id UUID NOT NULL,
+    tag_id UUID NOT NULL,
+
+    PRIMARY KEY (post_id, tag_id),
+
+    CONSTRAINT post_tags_posts_fkey
+    FOREIGN KEY (post_id) REFERENCES posts(id) ON DELETE CASCADE,
+    CONSTRAINT post_tags_tags_fkey
+    FOREIGN KEY (tag_id) REFERENCES tags(id) ON DELETE CASCADE
+);
+
+CREATE INDEX post_tags_tag_id_idx ON post_tags (tag_id, post_id);
+
+-- Tags existing posts by the same rules posts-service applies on write: a '#' not preceded by a
+-- word character, followed by up to 50 letters, digits or underscores including at least one letter.
+CREATE TEMPORARY TABLE backfill_post_tags AS
+SELECT DISTINCT posts.id AS post_id, lower(match[1]) AS name
+FROM posts,
+LATERAL regexp_matches(posts.title || ' ' || posts.description, '(?:^|[^[:alnum:]_])#([[:alnum:]_]+)', 'g') AS match
+WHERE length(match[1]) <= 50 AND match[1] ~ '[[:alpha:]]';
+
+INSERT INTO tags (name)
+SELECT DISTINCT name FROM backfill_post_tags
+ON CONFLICT (name) DO NOTHING;
+
+INSERT INTO post_tags (post_id, tag_id)
+SELECT backfill_post_tags.post_id, tags.id
+FROM backfill_post_tags
+JOIN tags ON tags.name = backfill_post_tags.name;
+
+DROP TABLE backfill_post_tags;
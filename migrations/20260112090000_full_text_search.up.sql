@@ -0,0 +1,18 @@
+-- Search documents kept up to date by Postgres. Titles weigh more than descriptions when ranking.
+ALTER TABLE posts
+ADD COLUMN search_vector TSVECTOR GENERATED ALWAYS AS (
+    setweight(to_tsvector('english', title), 'A') || setweight(to_tsvector('english', description), 'B')
+) STORED;
+
+CREATE INDEX posts_search_vector_idx ON posts USING GIN (search_vector);
+
+ALTER TABLE comments
+ADD COLUMN search_vector TSVECTOR GENERATED ALWAYS AS (to_tsvector('english', content)) STORED;
+
+CREATE INDEX comments_search_vector_idx ON comments USING GIN (search_vector);
+
+-- Usernames are names, not English words, so they are not stemmed.
+ALTER TABLE users
+ADD COLUMN search_vector TSVECTOR GENERATED ALWAYS AS (to_tsvector('simple', username)) STORED;
+
+CREATE INDEX users_search_vector_idx ON users USING GIN (search_vector);
@@ -0,0 +1,13 @@
+-- Every magic link request, whether or not the address has an account. Rate limits count these,
+-- counting the links actually sent would treat unknown addresses differently and give them away.
+CREATE TABLE magic_link_requests (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    -- sha256 of the lowercased address, unknown addresses aren't kept in the clear.
+    email_hash TEXT NOT NULL,
+    -- Empty when the caller didn't forward one.
+    ip_address TEXT NOT NULL DEFAULT '',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX magic_link_requests_email_hash_created_at_idx ON magic_link_requests (email_hash, created_at);
+CREATE INDEX magic_link_requests_ip_address_created_at_idx ON magic_link_requests (ip_address, created_at);
@@ -0,0 +1,8 @@
+-- Search highlights are HTML, so the text is escaped before `ts_headline` adds its <mark> tags.
+-- The text search parser reads entities as single tokens, so highlighting never splits one.
+CREATE FUNCTION html_escape(value TEXT) RETURNS TEXT
+LANGUAGE SQL IMMUTABLE STRICT PARALLEL SAFE
+AS $$
+    SELECT replace(replace(replace(replace(replace(value,
+        '&', '&amp;'), '<', '&lt;'), '>', '&gt;'), '"', '&quot;'), '''', '&#39;')
+$$;